@@ -32,6 +32,7 @@ pub struct AttestorClient {
     host: String,
     public_key: XOnlyPublicKey,
     client: reqwest::Client,
+    attestation_timeout: Option<Duration>,
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
@@ -134,9 +135,18 @@ impl AttestorClient {
             host,
             public_key,
             client,
+            attestation_timeout: None,
         })
     }
 
+    /// Sets how long to wait for this attestor to answer an attestation
+    /// request, so that a slow attestor does not hold back the closing of
+    /// contracts other attestors already attested.
+    pub fn with_attestation_timeout(mut self, timeout: Duration) -> Self {
+        self.attestation_timeout = Some(timeout);
+        self
+    }
+
     pub async fn get_chain(&self, event_id: &str) -> Result<String, DlcManagerError> {
         debug!("Getting chain for event_id {event_id}");
         let path = announcement_path(&self.host, event_id);
@@ -166,14 +176,44 @@ impl AttestorClient {
     }
 
     async fn get_json(&self, path: &str) -> Result<Value, DlcManagerError> {
-        self.client
-            .get(path)
-            .send()
-            .await
-            .map_err(|x| dlc_manager::error::Error::OracleError(x.to_string()))?
-            .json::<Value>()
-            .await
-            .map_err(|x| dlc_manager::error::Error::OracleError(x.to_string()))
+        self.get_json_with_timeout(path, None).await
+    }
+
+    #[cfg_attr(target_arch = "wasm32", allow(unused_mut))]
+    async fn get_json_with_timeout(
+        &self,
+        path: &str,
+        timeout: Option<Duration>,
+    ) -> Result<Value, DlcManagerError> {
+        let mut request = self.client.get(path);
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(timeout) = timeout {
+            request = request.timeout(timeout);
+        }
+        let response = async move {
+            request
+                .send()
+                .await
+                .map_err(|x| dlc_manager::error::Error::OracleError(x.to_string()))?
+                .json::<Value>()
+                .await
+                .map_err(|x| dlc_manager::error::Error::OracleError(x.to_string()))
+        };
+        // Requests cannot be given a timeout on wasm, they are raced against
+        // a timer instead.
+        #[cfg(target_arch = "wasm32")]
+        if let Some(timeout) = timeout {
+            use futures::future::{select, Either};
+            let timer = gloo_timers::future::sleep(timeout);
+            return match select(Box::pin(response), timer).await {
+                Either::Left((result, _)) => result,
+                Either::Right(_) => Err(DlcManagerError::OracleError(format!(
+                    "No answer from {} within {:?}",
+                    path, timeout
+                ))),
+            };
+        }
+        response.await
     }
 }
 
@@ -261,7 +301,9 @@ impl AsyncOracle for AttestorClient {
         event_id: &str,
     ) -> Result<OracleAttestation, dlc_manager::error::Error> {
         let path = attestation_path(&self.host, event_id);
-        let v = self.get_json(&path).await?;
+        let v = self
+            .get_json_with_timeout(&path, self.attestation_timeout)
+            .await?;
 
         //TODO: this next line might be None, throwing at unwrap, fix
         let encoded_hex_attestation = match v["rust_attestation"].as_str() {
@@ -1,19 +1,26 @@
-use dlc_link_manager::AsyncStorage;
+use dlc_link_manager::{AsyncStorage, ContractMetadata, ContractState};
+use dlc_manager::channel::offered_channel::OfferedChannel;
+use dlc_manager::channel::signed_channel::{SignedChannel, SignedChannelStateType};
+use dlc_manager::channel::Channel;
 use dlc_manager::contract::offered_contract::OfferedContract;
 use dlc_manager::contract::signed_contract::SignedContract;
 use dlc_manager::contract::Contract as DlcContract;
 use dlc_manager::contract::PreClosedContract;
 use dlc_manager::error::Error;
-use dlc_manager::ContractId;
+use dlc_manager::{ChannelId, ContractId};
 use secp256k1_zkp::SecretKey;
 
 use crate::utils::{get_contract_id_string, to_storage_error};
 use crate::{
-    ApiError, Contract, ContractRequestParams, ContractsRequestParams, NewContract,
-    StorageApiClient, UpdateContract,
+    ApiError, ChannelsRequestParams, Contract, ContractRequestParams, ContractsRequestParams,
+    NewContract, NewContractMetadata, ReplaceContract, StorageApiClient, UpdateContract,
+    UpsertChannel,
 };
 
-use super::utils::{deserialize_contract, get_contract_state_str, serialize_contract};
+use super::utils::{
+    deserialize_channel, deserialize_contract, get_channel_state_str, get_contract_state_str,
+    get_signed_channel_state_str, get_state_str, serialize_channel, serialize_contract,
+};
 
 pub struct AsyncStorageApiProvider {
     client: StorageApiClient,
@@ -61,6 +68,32 @@ impl AsyncStorageApiProvider {
         }
         Ok(contracts)
     }
+
+    async fn get_channels(
+        &self,
+        state: Option<&str>,
+        signed_state: Option<SignedChannelStateType>,
+    ) -> Result<Vec<Channel>, Error> {
+        let records = self
+            .client
+            .get_channels(
+                ChannelsRequestParams {
+                    key: self.public_key.clone(),
+                    uuid: None,
+                    state: state.map(|state| state.to_string()),
+                    signed_state: signed_state.map(get_signed_channel_state_str),
+                },
+                self.secret_key,
+            )
+            .await
+            .map_err(to_storage_error)?;
+        let mut channels = Vec::new();
+        for record in records {
+            let bytes = base64::decode(record.content).map_err(to_storage_error)?;
+            channels.push(deserialize_channel(&bytes)?);
+        }
+        Ok(channels)
+    }
 }
 
 impl AsyncStorage for AsyncStorageApiProvider {
@@ -197,6 +230,22 @@ impl AsyncStorage for AsyncStorageApiProvider {
         }
     }
 
+    async fn replace_contract(&self, id: &ContractId, contract: &DlcContract) -> Result<(), Error> {
+        self.client
+            .replace_contract(
+                ReplaceContract {
+                    uuid: get_contract_id_string(*id),
+                    new_uuid: get_contract_id_string(contract.get_id()),
+                    state: get_contract_state_str(contract),
+                    content: base64::encode(serialize_contract(contract)?),
+                    key: self.public_key.clone(),
+                },
+                self.secret_key,
+            )
+            .await
+            .map_err(to_storage_error)
+    }
+
     async fn get_contract_offers(&self) -> Result<Vec<OfferedContract>, Error> {
         let contracts_per_state = self.get_contracts_by_state("offered".to_string()).await?;
         let mut res: Vec<OfferedContract> = Vec::new();
@@ -242,4 +291,161 @@ impl AsyncStorage for AsyncStorageApiProvider {
         }
         Ok(res)
     }
+
+    async fn get_contracts_in_state(
+        &self,
+        state: ContractState,
+    ) -> Result<Vec<DlcContract>, Error> {
+        self.get_contracts_by_state(get_state_str(state).to_string())
+            .await
+    }
+
+    async fn get_contract_metadata(
+        &self,
+        id: &ContractId,
+    ) -> Result<Option<ContractMetadata>, Error> {
+        let metadata = self
+            .client
+            .get_contract_metadata(
+                ContractRequestParams {
+                    key: self.public_key.clone(),
+                    uuid: get_contract_id_string(*id),
+                },
+                self.secret_key,
+            )
+            .await
+            .map_err(to_storage_error)?;
+        match metadata {
+            Some(metadata) => {
+                let bytes = base64::decode(metadata.content).map_err(to_storage_error)?;
+                let metadata = serde_json::from_slice(&bytes).map_err(to_storage_error)?;
+                Ok(Some(metadata))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn delete_contract_metadata(&self, id: &ContractId) -> Result<(), Error> {
+        // Contracts stored before metadata was recorded have none, which the
+        // API does not treat as an error.
+        self.client
+            .delete_contract_metadata(
+                ContractRequestParams {
+                    key: self.public_key.clone(),
+                    uuid: get_contract_id_string(*id),
+                },
+                self.secret_key,
+            )
+            .await
+            .map_err(to_storage_error)?;
+        Ok(())
+    }
+
+    async fn upsert_contract_metadata(
+        &self,
+        id: &ContractId,
+        metadata: &ContractMetadata,
+    ) -> Result<(), Error> {
+        let content = base64::encode(serde_json::to_vec(metadata).map_err(to_storage_error)?);
+        self.client
+            .upsert_contract_metadata(
+                NewContractMetadata {
+                    uuid: get_contract_id_string(*id),
+                    content,
+                    key: self.public_key.clone(),
+                },
+                self.secret_key,
+            )
+            .await
+            .map_err(to_storage_error)?;
+        Ok(())
+    }
+
+    async fn get_channel(&self, id: &ChannelId) -> Result<Option<Channel>, Error> {
+        let mut records = self
+            .client
+            .get_channels(
+                ChannelsRequestParams {
+                    key: self.public_key.clone(),
+                    uuid: Some(get_contract_id_string(*id)),
+                    state: None,
+                    signed_state: None,
+                },
+                self.secret_key,
+            )
+            .await
+            .map_err(to_storage_error)?;
+        match records.pop() {
+            Some(record) => {
+                let bytes = base64::decode(record.content).map_err(to_storage_error)?;
+                Ok(Some(deserialize_channel(&bytes)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn upsert_channel(&self, channel: &Channel) -> Result<(), Error> {
+        let temporary_uuid = match channel {
+            Channel::Accepted(_) | Channel::Signed(_) | Channel::FailedSign(_) => {
+                Some(get_contract_id_string(channel.get_temporary_id()))
+            }
+            _ => None,
+        };
+        let signed_state = match channel {
+            Channel::Signed(c) => Some(get_signed_channel_state_str(c.state.get_type())),
+            _ => None,
+        };
+        self.client
+            .upsert_channel(
+                UpsertChannel {
+                    uuid: get_contract_id_string(channel.get_id()),
+                    temporary_uuid,
+                    state: get_channel_state_str(channel).to_string(),
+                    signed_state,
+                    content: base64::encode(serialize_channel(channel).map_err(to_storage_error)?),
+                    key: self.public_key.clone(),
+                },
+                self.secret_key,
+            )
+            .await
+            .map_err(to_storage_error)?;
+        Ok(())
+    }
+
+    async fn delete_channel(&self, id: &ChannelId) -> Result<(), Error> {
+        self.client
+            .delete_channel(
+                ContractRequestParams {
+                    key: self.public_key.clone(),
+                    uuid: get_contract_id_string(*id),
+                },
+                self.secret_key,
+            )
+            .await
+            .map_err(to_storage_error)?;
+        Ok(())
+    }
+
+    async fn get_offered_channels(&self) -> Result<Vec<OfferedChannel>, Error> {
+        let mut res = Vec::new();
+        for channel in self.get_channels(Some("offered"), None).await? {
+            if let Channel::Offered(c) = channel {
+                res.push(c);
+            }
+        }
+        Ok(res)
+    }
+
+    async fn get_signed_channels(
+        &self,
+        channel_state: Option<SignedChannelStateType>,
+    ) -> Result<Vec<SignedChannel>, Error> {
+        let mut res = Vec::new();
+        for channel in self.get_channels(Some("signed"), channel_state).await? {
+            if let Channel::Signed(c) = channel {
+                res.push(c);
+            }
+        }
+        Ok(res)
+    }
 }
@@ -100,6 +100,15 @@ pub struct UpdateContract {
     pub key: String,
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ReplaceContract {
+    pub uuid: String,
+    pub new_uuid: String,
+    pub state: String,
+    pub content: String,
+    pub key: String,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct ContractRequestParams {
     pub key: String,
@@ -177,6 +186,65 @@ pub struct EventsRequestParams {
     pub event_id: Option<String>,
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct NewContractMetadata {
+    pub uuid: String,
+    pub content: String,
+    pub key: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ContractMetadataRecord {
+    pub id: i32,
+    pub uuid: String,
+    pub content: String,
+    pub key: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+struct SignedContractMetadataRequestParams {
+    key: String,
+    uuid: String,
+    signature: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ChannelRecord {
+    pub id: i32,
+    pub uuid: String,
+    pub state: String,
+    pub signed_state: Option<String>,
+    pub content: String,
+    pub key: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct UpsertChannel {
+    pub uuid: String,
+    pub temporary_uuid: Option<String>,
+    pub state: String,
+    pub signed_state: Option<String>,
+    pub content: String,
+    pub key: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ChannelsRequestParams {
+    pub key: String,
+    pub uuid: Option<String>,
+    pub state: Option<String>,
+    pub signed_state: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+struct SignedChannelsRequestParams {
+    key: String,
+    uuid: Option<String>,
+    state: Option<String>,
+    signed_state: Option<String>,
+    signature: String,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 struct EffectedNumResponse {
     pub effected_num: u32,
@@ -499,6 +567,46 @@ impl StorageApiClient {
         }
     }
 
+    /// Replaces the contract stored under `uuid` by the one stored under
+    /// `new_uuid`, in a single operation.
+    pub async fn replace_contract(
+        &self,
+        contract: ReplaceContract,
+        secret_key: SecretKey,
+    ) -> Result<(), ApiError> {
+        let uri = format!("{}/contracts/replace", String::as_str(&self.host.clone()));
+        debug!("calling contract replace on url: {:?}", uri);
+        let (nonce, message_body) = self
+            .build_signed_message(secret_key, json!(contract))
+            .await?;
+        let res = self
+            .client
+            .put(uri)
+            .header("authorization", nonce)
+            .json(&json!(message_body))
+            .send()
+            .await?;
+        let status = res.status().into();
+        match res
+            .json::<EffectedNumResponse>()
+            .await
+            .map_err(|e| ApiError {
+                message: format!(
+                    "Replacing contract failed, response from API not a number, error: {}",
+                    e
+                ),
+                status,
+            })?
+            .effected_num
+        {
+            0 => Err(ApiError {
+                message: "No contract replaced".to_string(),
+                status,
+            }),
+            _ => Ok(()),
+        }
+    }
+
     pub async fn delete_event(
         &self,
         event: EventRequestParams,
@@ -583,6 +691,220 @@ impl StorageApiClient {
         }
     }
 
+    pub async fn get_contract_metadata(
+        &self,
+        metadata_req: ContractRequestParams,
+        secret_key: SecretKey,
+    ) -> Result<Option<ContractMetadataRecord>, ApiError> {
+        let uri = format!("{}/contract-metadata", String::as_str(&self.host.clone()));
+        debug!("getting contract metadata with uuid: {}", metadata_req.uuid);
+
+        let nonce = self.request_nonce().await?;
+        let (sig, _pubkey) = self.sign(secret_key, nonce.clone());
+        let signed_request_params = SignedContractMetadataRequestParams {
+            key: metadata_req.key,
+            uuid: metadata_req.uuid,
+            signature: sig.to_string(),
+        };
+
+        let res = self
+            .client
+            .get(uri)
+            .header("authorization", nonce)
+            .query(&signed_request_params)
+            .send()
+            .await?;
+        let status = res.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let metadata = res
+            .json::<ContractMetadataRecord>()
+            .await
+            .map_err(|e| ApiError {
+                message: format!(
+                    "get contract metadata failed, response from API not a contract metadata object, error: {}",
+                    e
+                ),
+                status: status.into(),
+            })?;
+        Ok(Some(metadata))
+    }
+
+    /// Creates the metadata of the contract, or replaces it if it exists.
+    pub async fn upsert_contract_metadata(
+        &self,
+        metadata: NewContractMetadata,
+        secret_key: SecretKey,
+    ) -> Result<ContractMetadataRecord, ApiError> {
+        let uri = format!("{}/contract-metadata", String::as_str(&self.host.clone()));
+        debug!("calling contract metadata upsert on url: {:?}", uri);
+
+        let (nonce, message_body) = self
+            .build_signed_message(secret_key, json!(metadata))
+            .await?;
+
+        let res = self
+            .client
+            .put(uri)
+            .header("authorization", nonce)
+            .json(&message_body)
+            .send()
+            .await?;
+        let status = res.status().into();
+        let metadata = res
+            .json::<ContractMetadataRecord>()
+            .await
+            .map_err(|e| ApiError {
+                message: format!(
+                    "Upserting contract metadata failed, response from API not a contract metadata object, error: {}",
+                    e
+                ),
+                status,
+            })?;
+        Ok(metadata)
+    }
+
+    /// Deletes the metadata of the contract, returning whether there was any.
+    pub async fn delete_contract_metadata(
+        &self,
+        metadata: ContractRequestParams,
+        secret_key: SecretKey,
+    ) -> Result<bool, ApiError> {
+        let uri = format!("{}/contract-metadata", String::as_str(&self.host.clone()));
+        debug!("calling contract metadata delete on url: {:?}", uri);
+
+        let (nonce, message_body) = self
+            .build_signed_message(secret_key, json!(metadata))
+            .await?;
+
+        let res = self
+            .client
+            .delete(uri)
+            .header("authorization", nonce)
+            .json(&message_body)
+            .send()
+            .await?;
+        let status = res.status().into();
+        let effected_num = res
+            .json::<EffectedNumResponse>()
+            .await
+            .map_err(|e| ApiError {
+                message: format!(
+                    "Deleting contract metadata failed, response from API not a number, error: {}",
+                    e
+                ),
+                status,
+            })?
+            .effected_num;
+        Ok(effected_num > 0)
+    }
+
+    pub async fn get_channels(
+        &self,
+        channel_req: ChannelsRequestParams,
+        secret_key: SecretKey,
+    ) -> Result<Vec<ChannelRecord>, ApiError> {
+        let uri = format!("{}/channels", String::as_str(&self.host.clone()));
+
+        let nonce = self.request_nonce().await?;
+        let (sig, _pubkey) = self.sign(secret_key, nonce.clone());
+        let signed_request_params = SignedChannelsRequestParams {
+            key: channel_req.key,
+            uuid: channel_req.uuid,
+            state: channel_req.state,
+            signed_state: channel_req.signed_state,
+            signature: sig.to_string(),
+        };
+
+        let res = self
+            .client
+            .get(uri)
+            .header("authorization", nonce)
+            .query(&signed_request_params)
+            .send()
+            .await?;
+        let status = res.status().into();
+        let channels = res
+            .json::<Vec<ChannelRecord>>()
+            .await
+            .map_err(|e| ApiError {
+                message: format!(
+                    "get channels failed, response from API not a list of channel objects, error: {}",
+                    e
+                ),
+                status,
+            })?;
+        Ok(channels)
+    }
+
+    /// Creates the channel, or replaces it if it exists, deleting the record
+    /// kept under its temporary id if given.
+    pub async fn upsert_channel(
+        &self,
+        channel: UpsertChannel,
+        secret_key: SecretKey,
+    ) -> Result<ChannelRecord, ApiError> {
+        let uri = format!("{}/channels", String::as_str(&self.host.clone()));
+        debug!("calling channel upsert on url: {:?}", uri);
+
+        let (nonce, message_body) = self
+            .build_signed_message(secret_key, json!(channel))
+            .await?;
+
+        let res = self
+            .client
+            .put(uri)
+            .header("authorization", nonce)
+            .json(&message_body)
+            .send()
+            .await?;
+        let status = res.status().into();
+        let channel = res.json::<ChannelRecord>().await.map_err(|e| ApiError {
+            message: format!(
+                "Upserting channel failed, response from API not a channel object, error: {}",
+                e
+            ),
+            status,
+        })?;
+        Ok(channel)
+    }
+
+    /// Deletes the channel, returning whether there was any.
+    pub async fn delete_channel(
+        &self,
+        channel: ContractRequestParams,
+        secret_key: SecretKey,
+    ) -> Result<bool, ApiError> {
+        let uri = format!("{}/channel", String::as_str(&self.host.clone()));
+        debug!("calling channel delete on url: {:?}", uri);
+
+        let (nonce, message_body) = self
+            .build_signed_message(secret_key, json!(channel))
+            .await?;
+
+        let res = self
+            .client
+            .delete(uri)
+            .header("authorization", nonce)
+            .json(&message_body)
+            .send()
+            .await?;
+        let status = res.status().into();
+        let effected_num = res
+            .json::<EffectedNumResponse>()
+            .await
+            .map_err(|e| ApiError {
+                message: format!(
+                    "Deleting channel failed, response from API not a number, error: {}",
+                    e
+                ),
+                status,
+            })?
+            .effected_num;
+        Ok(effected_num > 0)
+    }
+
     // For testing only, should be removed
     // pub async fn delete_contracts(&self, key: String) -> Result<(), ApiError> {
     //     self.delete_resources("contracts".to_string(), key).await
@@ -1,3 +1,8 @@
+use dlc_link_manager::ContractState;
+use dlc_manager::channel::accepted_channel::AcceptedChannel;
+use dlc_manager::channel::offered_channel::OfferedChannel;
+use dlc_manager::channel::signed_channel::{SignedChannel, SignedChannelStateType};
+use dlc_manager::channel::{Channel, FailedAccept, FailedSign};
 use dlc_manager::contract::offered_contract::OfferedContract;
 use dlc_manager::contract::signed_contract::SignedContract;
 use dlc_manager::contract::{Contract, PreClosedContract};
@@ -130,20 +135,90 @@ pub fn deserialize_contract(buff: &Vec<u8>) -> Result<Contract, Error> {
     Ok(contract)
 }
 
-pub fn get_contract_state_str(contract: &Contract) -> String {
-    let state = match contract {
-        Contract::Offered(_) => "offered",
-        Contract::Accepted(_) => "accepted",
-        Contract::Signed(_) => "signed",
-        Contract::Confirmed(_) => "confirmed",
-        Contract::PreClosed(_) => "pre_closed",
-        Contract::Closed(_) => "closed",
-        Contract::Refunded(_) => "refunded",
-        Contract::FailedAccept(_) => "failed_accept",
-        Contract::FailedSign(_) => "failed_sign",
-        Contract::Rejected(_) => "rejected",
+convertible_enum!(
+    enum ChannelPrefix {
+        Offered = 1,
+        Accepted,
+        Signed,
+        FailedAccept,
+        FailedSign,;
+    },
+    Channel
+);
+
+pub fn serialize_channel(channel: &Channel) -> Result<Vec<u8>, ::std::io::Error> {
+    let serialized = match channel {
+        Channel::Offered(c) => c.serialize(),
+        Channel::Accepted(c) => c.serialize(),
+        Channel::Signed(c) => c.serialize(),
+        Channel::FailedAccept(c) => c.serialize(),
+        Channel::FailedSign(c) => c.serialize(),
     };
-    state.to_string()
+    let mut serialized = serialized?;
+    let mut res = Vec::with_capacity(serialized.len() + 1);
+    res.push(ChannelPrefix::get_prefix(channel));
+    res.append(&mut serialized);
+    Ok(res)
+}
+
+pub fn deserialize_channel(buff: &Vec<u8>) -> Result<Channel, Error> {
+    let mut cursor = ::std::io::Cursor::new(buff);
+    let mut prefix = [0u8; 1];
+    std::io::Read::read_exact(&mut cursor, &mut prefix)?;
+    let channel_prefix: ChannelPrefix = prefix[0].try_into()?;
+    let channel = match channel_prefix {
+        ChannelPrefix::Offered => {
+            Channel::Offered(OfferedChannel::deserialize(&mut cursor).map_err(to_storage_error)?)
+        }
+        ChannelPrefix::Accepted => {
+            Channel::Accepted(AcceptedChannel::deserialize(&mut cursor).map_err(to_storage_error)?)
+        }
+        ChannelPrefix::Signed => {
+            Channel::Signed(SignedChannel::deserialize(&mut cursor).map_err(to_storage_error)?)
+        }
+        ChannelPrefix::FailedAccept => {
+            Channel::FailedAccept(FailedAccept::deserialize(&mut cursor).map_err(to_storage_error)?)
+        }
+        ChannelPrefix::FailedSign => {
+            Channel::FailedSign(FailedSign::deserialize(&mut cursor).map_err(to_storage_error)?)
+        }
+    };
+    Ok(channel)
+}
+
+pub fn get_contract_state_str(contract: &Contract) -> String {
+    get_state_str(contract.into()).to_string()
+}
+
+pub fn get_state_str(state: ContractState) -> &'static str {
+    match state {
+        ContractState::Offered => "offered",
+        ContractState::Accepted => "accepted",
+        ContractState::Signed => "signed",
+        ContractState::Confirmed => "confirmed",
+        ContractState::PreClosed => "pre_closed",
+        ContractState::Closed => "closed",
+        ContractState::Refunded => "refunded",
+        ContractState::FailedAccept => "failed_accept",
+        ContractState::FailedSign => "failed_sign",
+        ContractState::Rejected => "rejected",
+    }
+}
+
+pub fn get_channel_state_str(channel: &Channel) -> &'static str {
+    match channel {
+        Channel::Offered(_) => "offered",
+        Channel::Accepted(_) => "accepted",
+        Channel::Signed(_) => "signed",
+        Channel::FailedAccept(_) => "failed_accept",
+        Channel::FailedSign(_) => "failed_sign",
+    }
+}
+
+/// Signed channels are also stored with the type of their state, so that
+/// they can be queried by it.
+pub fn get_signed_channel_state_str(state: SignedChannelStateType) -> String {
+    format!("{:?}", state)
 }
 
 pub fn get_contract_id_string(contract_id: [u8; 32]) -> String {
@@ -0,0 +1,324 @@
+//! #AttestationStatus which oracles attested the event of a contract.
+
+use dlc::secp_utils::schnorrsig_decompose;
+use dlc::Payout;
+use dlc_manager::contract::contract_info::ContractInfo;
+use dlc_manager::contract::AdaptorInfo;
+use dlc_manager::error::Error;
+use dlc_manager::ContractId;
+use dlc_messages::oracle_msgs::{OracleAnnouncement, OracleAttestation};
+use secp256k1_zkp::hashes::sha256;
+use secp256k1_zkp::{Message, Secp256k1, Verification, XOnlyPublicKey};
+
+/// An oracle whose attestation could not be used.
+#[derive(Clone, Debug)]
+pub struct MissingAttestation {
+    /// The public key of the oracle.
+    pub oracle_public_key: XOnlyPublicKey,
+    /// Why the attestation is missing: the oracle is unknown, could not be
+    /// reached in time, has not attested the event yet or sent an invalid
+    /// attestation.
+    pub reason: String,
+}
+
+/// How far the attestation of the oracle event of a contract went.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttestationState {
+    /// No oracle attested the event yet.
+    NotAttested,
+    /// Some oracles attested the event, but fewer than the threshold.
+    PartiallyAttested,
+    /// Enough oracles attested, but their outcomes do not match, so that no
+    /// CET can be signed.
+    Disagreement,
+    /// Enough oracles attested matching outcomes to close the contract.
+    Attested,
+}
+
+/// The attestations gathered for the oracle event of a contract.
+#[derive(Clone, Debug)]
+pub struct AttestationStatus {
+    /// The id of the contract.
+    pub contract_id: ContractId,
+    /// The number of oracles that must attest matching outcomes to close the
+    /// contract.
+    pub threshold: usize,
+    /// How far the attestation went.
+    pub state: AttestationState,
+    /// The oracles that sent a valid attestation, in announcement order.
+    pub attested: Vec<XOnlyPublicKey>,
+    /// The valid attestations received, in the order of `attested`.
+    pub attestations: Vec<OracleAttestation>,
+    /// The oracles whose valid attestation does not match the outcome attested
+    /// by the other oracles.
+    pub disagreeing: Vec<XOnlyPublicKey>,
+    /// The oracles whose attestation could not be used.
+    pub missing: Vec<MissingAttestation>,
+    /// The oracles that were not waited for, as enough matching attestations
+    /// were already available.
+    pub pending: Vec<XOnlyPublicKey>,
+}
+
+impl AttestationStatus {
+    pub(crate) fn new(contract_id: ContractId, threshold: usize) -> Self {
+        AttestationStatus {
+            contract_id,
+            threshold,
+            state: AttestationState::NotAttested,
+            attested: Vec::new(),
+            attestations: Vec::new(),
+            disagreeing: Vec::new(),
+            missing: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Records the valid attestations and fills in the oracles that did not
+    /// answer and the ones that disagree with the agreed outcomes, or with the
+    /// most common outcomes if the threshold was not reached.
+    pub(crate) fn complete(
+        &mut self,
+        announcements: &[OracleAnnouncement],
+        attestations: &[(usize, OracleAttestation)],
+        agreed_outcomes: Option<&Vec<String>>,
+    ) {
+        let mut sorted = attestations.to_vec();
+        sorted.sort_by_key(|(index, _)| *index);
+        self.attested = sorted
+            .iter()
+            .map(|(_, attestation)| attestation.oracle_public_key)
+            .collect();
+        self.attestations = sorted
+            .into_iter()
+            .map(|(_, attestation)| attestation)
+            .collect();
+        self.pending = announcements
+            .iter()
+            .map(|announcement| announcement.oracle_public_key)
+            .filter(|key| {
+                !self.attested.contains(key)
+                    && !self
+                        .missing
+                        .iter()
+                        .any(|missing| missing.oracle_public_key == *key)
+            })
+            .collect();
+        self.disagreeing = match agreed_outcomes.or_else(|| get_most_common_outcomes(attestations))
+        {
+            Some(reference) => attestations
+                .iter()
+                .filter(|(_, attestation)| &attestation.outcomes != reference)
+                .map(|(_, attestation)| attestation.oracle_public_key)
+                .collect(),
+            None => Vec::new(),
+        };
+        self.state = if agreed_outcomes.is_some() {
+            AttestationState::Attested
+        } else if attestations.len() >= self.threshold && !self.disagreeing.is_empty() {
+            AttestationState::Disagreement
+        } else if attestations.is_empty() {
+            AttestationState::NotAttested
+        } else {
+            AttestationState::PartiallyAttested
+        };
+    }
+}
+
+/// Checks that the attestation is signed by the announcing oracle with the
+/// announced nonces.
+pub(crate) fn validate_attestation<C: Verification>(
+    secp: &Secp256k1<C>,
+    announcement: &OracleAnnouncement,
+    attestation: &OracleAttestation,
+) -> Result<(), Error> {
+    if attestation.oracle_public_key != announcement.oracle_public_key {
+        return Err(Error::OracleError(
+            "Attestation is not signed by the announcing oracle".to_string(),
+        ));
+    }
+    let nonces = &announcement.oracle_event.oracle_nonces;
+    if attestation.signatures.len() != nonces.len() || attestation.outcomes.len() != nonces.len() {
+        return Err(Error::OracleError(format!(
+            "Attestation has {} outcomes and {} signatures for {} announced nonces",
+            attestation.outcomes.len(),
+            attestation.signatures.len(),
+            nonces.len()
+        )));
+    }
+    for ((signature, outcome), nonce) in attestation
+        .signatures
+        .iter()
+        .zip(attestation.outcomes.iter())
+        .zip(nonces.iter())
+    {
+        let (signature_nonce, _) = schnorrsig_decompose(signature)
+            .map_err(|e| Error::OracleError(format!("Invalid attestation signature: {}", e)))?;
+        if signature_nonce != *nonce {
+            return Err(Error::OracleError(
+                "Attestation signature does not use the announced nonce".to_string(),
+            ));
+        }
+        let message = Message::from_hashed_data::<sha256::Hash>(outcome.as_bytes());
+        secp.verify_schnorr(signature, &message, &attestation.oracle_public_key)
+            .map_err(|_| {
+                Error::OracleError(format!(
+                    "Invalid attestation signature for outcome {}",
+                    outcome
+                ))
+            })?;
+    }
+    Ok(())
+}
+
+/// Returns the outcomes attested by the oracles selecting a CET of the contract
+/// info, if the attestations are enough to close it.
+pub(crate) fn get_agreed_outcomes(
+    contract_info: &ContractInfo,
+    adaptor_info: &AdaptorInfo,
+    attestations: &[(usize, OracleAttestation)],
+) -> Result<Option<Vec<String>>, Error> {
+    let outcomes: Vec<(usize, &Vec<String>)> = attestations
+        .iter()
+        .map(|(index, attestation)| (*index, &attestation.outcomes))
+        .collect();
+    let selected_oracle = contract_info
+        .get_range_info_for_outcome(adaptor_info, &outcomes, 0)?
+        .and_then(|(selected, _)| selected.first().map(|(index, _)| *index));
+    Ok(selected_oracle.and_then(|selected| {
+        attestations
+            .iter()
+            .find(|(index, _)| *index == selected)
+            .map(|(_, attestation)| attestation.outcomes.clone())
+    }))
+}
+
+/// Returns the payout of the CET selected by the attestations of the contract
+/// info, whose total collateral is given.
+pub(crate) fn get_attested_payout(
+    contract_info: &ContractInfo,
+    adaptor_info: &AdaptorInfo,
+    attestations: &[(usize, OracleAttestation)],
+    total_collateral: u64,
+) -> Result<Payout, Error> {
+    let outcomes: Vec<(usize, &Vec<String>)> = attestations
+        .iter()
+        .map(|(index, attestation)| (*index, &attestation.outcomes))
+        .collect();
+    let (_, range_info) = contract_info
+        .get_range_info_for_outcome(adaptor_info, &outcomes, 0)?
+        .ok_or(Error::InvalidState(
+            "The attestations select no CET of the contract".to_string(),
+        ))?;
+    contract_info
+        .get_payouts(total_collateral)?
+        .get(range_info.cet_index)
+        .cloned()
+        .ok_or(Error::InvalidState(format!(
+            "No payout for CET {}",
+            range_info.cet_index
+        )))
+}
+
+fn get_most_common_outcomes(attestations: &[(usize, OracleAttestation)]) -> Option<&Vec<String>> {
+    attestations
+        .iter()
+        .map(|(_, attestation)| &attestation.outcomes)
+        .max_by_key(|outcomes| {
+            attestations
+                .iter()
+                .filter(|(_, attestation)| &attestation.outcomes == *outcomes)
+                .count()
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dlc::secp_utils::schnorrsig_sign_with_nonce;
+    use dlc_messages::oracle_msgs::{EnumEventDescriptor, EventDescriptor, OracleEvent};
+    use secp256k1_zkp::rand::thread_rng;
+    use secp256k1_zkp::{KeyPair, SecretKey};
+
+    fn attest(
+        secp: &Secp256k1<secp256k1_zkp::All>,
+        key_pair: &KeyPair,
+        nonce: &SecretKey,
+        outcome: &str,
+    ) -> OracleAttestation {
+        let message = Message::from_hashed_data::<sha256::Hash>(outcome.as_bytes());
+        OracleAttestation {
+            oracle_public_key: key_pair.public_key().into(),
+            signatures: vec![schnorrsig_sign_with_nonce(
+                secp,
+                &message,
+                key_pair,
+                nonce.as_ref(),
+            )],
+            outcomes: vec![outcome.to_string()],
+        }
+    }
+
+    #[test]
+    fn test_validate_attestation() {
+        let secp = Secp256k1::new();
+        let key_pair = KeyPair::new(&secp, &mut thread_rng());
+        let nonce = SecretKey::new(&mut thread_rng());
+        let oracle_event = OracleEvent {
+            oracle_nonces: vec![KeyPair::from_secret_key(&secp, &nonce).public_key().into()],
+            event_maturity_epoch: 0,
+            event_descriptor: EventDescriptor::EnumEvent(EnumEventDescriptor {
+                outcomes: vec!["a".to_string(), "b".to_string()],
+            }),
+            event_id: "event".to_string(),
+        };
+        let announcement = OracleAnnouncement {
+            announcement_signature: secp.sign_schnorr(
+                &Message::from_hashed_data::<sha256::Hash>(b"event"),
+                &key_pair,
+            ),
+            oracle_public_key: key_pair.public_key().into(),
+            oracle_event,
+        };
+
+        let attestation = attest(&secp, &key_pair, &nonce, "a");
+        assert!(validate_attestation(&secp, &announcement, &attestation).is_ok());
+
+        let mut forged = attestation.clone();
+        forged.outcomes = vec!["b".to_string()];
+        assert!(validate_attestation(&secp, &announcement, &forged).is_err());
+
+        let other_nonce = SecretKey::new(&mut thread_rng());
+        let reused = attest(&secp, &key_pair, &other_nonce, "a");
+        assert!(validate_attestation(&secp, &announcement, &reused).is_err());
+    }
+
+    #[test]
+    fn test_disagreeing_oracles() {
+        let secp = Secp256k1::new();
+        let nonce = SecretKey::new(&mut thread_rng());
+        let key_pairs: Vec<_> = (0..3)
+            .map(|_| KeyPair::new(&secp, &mut thread_rng()))
+            .collect();
+        let attestations: Vec<_> = key_pairs
+            .iter()
+            .zip(["a", "b", "a"])
+            .enumerate()
+            .map(|(i, (key_pair, outcome))| (i, attest(&secp, key_pair, &nonce, outcome)))
+            .collect();
+
+        let mut status = AttestationStatus::new([0; 32], 2);
+        status.complete(&[], &attestations, None);
+
+        let disagreeing: XOnlyPublicKey = key_pairs[1].public_key().into();
+        assert_eq!(status.state, AttestationState::Disagreement);
+        assert_eq!(status.disagreeing, vec![disagreeing]);
+
+        status.complete(&[], &attestations, Some(&vec!["a".to_string()]));
+        assert_eq!(status.state, AttestationState::Attested);
+        assert_eq!(status.disagreeing, vec![disagreeing]);
+
+        status.complete(&[], &attestations[..1], None);
+        assert_eq!(status.state, AttestationState::PartiallyAttested);
+        assert!(status.disagreeing.is_empty());
+    }
+}
@@ -0,0 +1,60 @@
+//! #AsyncClock source of the time compared with the time locks of contracts.
+
+use std::ops::Deref;
+
+use dlc_manager::error::Error;
+
+/// Provides the current time to decide whether the refund transaction of a
+/// contract can be broadcast. Unlike the Manager's
+/// [`Time`](crate::dlc_manager::Time) provider, it may ask a remote server, so
+/// that a wrong local clock does not delay the refund.
+pub trait AsyncClock {
+    /// Returns the current unix time, in seconds.
+    async fn get_unix_time(&self) -> Result<u64, Error>;
+}
+
+impl<C: Deref> AsyncClock for C
+where
+    C::Target: AsyncClock,
+{
+    async fn get_unix_time(&self) -> Result<u64, Error> {
+        self.deref().get_unix_time().await
+    }
+}
+
+/// An [`AsyncClock`] fetching the time from the worldtimeapi.org time server.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NetworkClock;
+
+impl NetworkClock {
+    async fn get_json(&self, path: &str) -> Result<serde_json::Value, Error> {
+        reqwest::get(path)
+            .await
+            .map_err(|x| Error::WalletError(Box::new(x)))?
+            .json::<serde_json::Value>()
+            .await
+            .map_err(|x| Error::WalletError(Box::new(x)))
+    }
+}
+
+impl AsyncClock for NetworkClock {
+    async fn get_unix_time(&self) -> Result<u64, Error> {
+        let path = "https://worldtimeapi.org/api/timezone/Etc/UTC";
+        let v = match self.get_json(path).await {
+            Ok(v) => v,
+            Err(e) => {
+                return Err(Error::WalletError(
+                    format!("Error getting unixtime: {e}").into(),
+                ))
+            }
+        };
+
+        let unixtime = match v["unixtime"].as_u64() {
+            //call to_string instead of as_str and watch your world crumble to pieces
+            None => return Err(Error::WalletError("unable to get unixtime".into())),
+            Some(s) => s,
+        };
+
+        Ok(unixtime)
+    }
+}
@@ -0,0 +1,170 @@
+//! #ManagerConfig confirmation and timing parameters of the Manager.
+
+use crate::{AcceptPolicy, FIFTY_YEARS, NB_CONFIRMATIONS};
+
+/// A confirmation depth that applies to contracts whose total collateral is at
+/// least `min_total_collateral`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfirmationTier {
+    /// The total collateral, in satoshis, from which this tier applies.
+    pub min_total_collateral: u64,
+    /// The number of confirmations required for contracts in this tier.
+    pub confirmations: u32,
+}
+
+/// What to do with FailedAccept and FailedSign records.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FailedContractPolicy {
+    /// Keep failed records forever.
+    Keep,
+    /// Delete failed records this many seconds after their offer was created.
+    /// Observers get a chance to archive them through
+    /// [`ContractObserver::on_delete`](crate::ContractObserver::on_delete).
+    Delete { after: u64 },
+}
+
+/// Confirmation and timing parameters used by the [`Manager`](crate::Manager).
+#[derive(Clone, Debug)]
+pub struct ManagerConfig {
+    /// The number of confirmations of the funding transaction required before
+    /// moving a contract to the Confirmed state.
+    pub funding_confirmations: u32,
+    /// The number of confirmations of a CET required before moving a contract
+    /// to the Closed state.
+    pub closing_confirmations: u32,
+    /// The lower bound, in seconds, for the refund delay of a received offer.
+    pub min_refund_delay: u32,
+    /// The upper bound, in seconds, for the refund delay of a received offer.
+    pub max_refund_delay: u32,
+    /// Optional tiers raising the funding and closing confirmation depths for
+    /// contracts with a large total collateral. The tier with the highest
+    /// `min_total_collateral` not above the contract's total collateral is
+    /// used, and never lowers the base depths above.
+    pub confirmation_tiers: Vec<ConfirmationTier>,
+    /// The maximum number of contracts checked concurrently by each step of a
+    /// periodic check.
+    pub max_concurrent_checks: usize,
+    /// The number of blocks a funding, closing or refund transaction may stay
+    /// unconfirmed before its fee is bumped by spending our output of it, and
+    /// between successive bumps. `None` disables fee bumping.
+    pub fee_bump_delay: Option<u32>,
+    /// The confirmation target, in blocks, used to estimate the fee rate of
+    /// fee bumps.
+    pub fee_bump_confirmation_target: u16,
+    /// The delay, in seconds, before broadcasting again a funding transaction
+    /// or CET the blockchain does not know about. The delay doubles after each
+    /// attempt.
+    pub rebroadcast_interval: u64,
+    /// The number of broadcast attempts after which a missing transaction is
+    /// reported as an error instead of being broadcast again.
+    pub max_rebroadcast_attempts: u32,
+    /// The time, in seconds from their creation, after which Offered and
+    /// Accepted contracts that were never signed are moved to the Rejected
+    /// state, releasing the UTXOs reserved to fund them. `None` keeps them
+    /// forever.
+    pub offer_expiry: Option<u64>,
+    /// What to do with failed contract records.
+    pub failed_contract_policy: FailedContractPolicy,
+    /// Limits on the offers we accept.
+    pub accept_policy: AcceptPolicy,
+}
+
+impl Default for ManagerConfig {
+    fn default() -> Self {
+        Self {
+            funding_confirmations: NB_CONFIRMATIONS,
+            closing_confirmations: NB_CONFIRMATIONS,
+            min_refund_delay: 0,
+            max_refund_delay: FIFTY_YEARS,
+            confirmation_tiers: Vec::new(),
+            max_concurrent_checks: 8,
+            fee_bump_delay: None,
+            fee_bump_confirmation_target: 2,
+            rebroadcast_interval: 60,
+            max_rebroadcast_attempts: 10,
+            offer_expiry: None,
+            failed_contract_policy: FailedContractPolicy::Keep,
+            accept_policy: AcceptPolicy::default(),
+        }
+    }
+}
+
+impl ManagerConfig {
+    /// Returns the number of confirmations required for the funding transaction
+    /// of a contract with the given total collateral.
+    pub fn get_funding_confirmations(&self, total_collateral: u64) -> u32 {
+        self.apply_tiers(self.funding_confirmations, total_collateral)
+    }
+
+    /// Returns the number of confirmations required for the closing transaction
+    /// of a contract with the given total collateral.
+    pub fn get_closing_confirmations(&self, total_collateral: u64) -> u32 {
+        self.apply_tiers(self.closing_confirmations, total_collateral)
+    }
+
+    /// Returns the delay, in seconds, to wait after the given number of
+    /// broadcast attempts before the next one.
+    pub fn get_rebroadcast_delay(&self, attempts: u32) -> u64 {
+        let exponent = attempts.saturating_sub(1).min(16);
+        self.rebroadcast_interval.saturating_mul(1 << exponent)
+    }
+
+    fn apply_tiers(&self, base: u32, total_collateral: u64) -> u32 {
+        self.confirmation_tiers
+            .iter()
+            .filter(|tier| tier.min_total_collateral <= total_collateral)
+            .max_by_key(|tier| tier.min_total_collateral)
+            .map_or(base, |tier| base.max(tier.confirmations))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_confirmation_tiers() {
+        let config = ManagerConfig {
+            funding_confirmations: 1,
+            closing_confirmations: 2,
+            confirmation_tiers: vec![
+                ConfirmationTier {
+                    min_total_collateral: 100_000_000,
+                    confirmations: 6,
+                },
+                ConfirmationTier {
+                    min_total_collateral: 10_000_000,
+                    confirmations: 3,
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(config.get_funding_confirmations(1_000), 1);
+        assert_eq!(config.get_closing_confirmations(1_000), 2);
+        assert_eq!(config.get_funding_confirmations(10_000_000), 3);
+        assert_eq!(config.get_closing_confirmations(50_000_000), 3);
+        assert_eq!(config.get_funding_confirmations(200_000_000), 6);
+    }
+
+    #[test]
+    fn test_rebroadcast_delay_doubles() {
+        let config = ManagerConfig {
+            rebroadcast_interval: 30,
+            ..Default::default()
+        };
+
+        assert_eq!(config.get_rebroadcast_delay(0), 30);
+        assert_eq!(config.get_rebroadcast_delay(1), 30);
+        assert_eq!(config.get_rebroadcast_delay(2), 60);
+        assert_eq!(config.get_rebroadcast_delay(4), 240);
+        assert_eq!(config.get_rebroadcast_delay(u32::MAX), 30 << 16);
+    }
+
+    #[test]
+    fn test_default_config() {
+        let config = ManagerConfig::default();
+        assert_eq!(config.get_funding_confirmations(u64::MAX), NB_CONFIRMATIONS);
+        assert_eq!(config.get_closing_confirmations(0), NB_CONFIRMATIONS);
+    }
+}
@@ -0,0 +1,68 @@
+//! #ContractFailure why the verification of an accept or sign message failed
+//! and whether it can be retried.
+
+use dlc_manager::error::Error;
+
+/// Whether the error that failed a contract can go away on its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FailureKind {
+    /// The wallet, blockchain, storage or oracle could not be reached or
+    /// failed to answer. Verifying the message again may succeed.
+    Transient,
+    /// The message itself is invalid, for instance because of a bad signature,
+    /// or the contract is not in a state to process it. Verifying the message
+    /// again would fail the same way.
+    Permanent,
+}
+
+impl FailureKind {
+    /// Classify the error returned while verifying an accept or sign message.
+    pub fn classify(error: &Error) -> Self {
+        match error {
+            Error::IOError(_)
+            | Error::WalletError(_)
+            | Error::BlockchainError(_)
+            | Error::StorageError(_)
+            | Error::OracleError(_) => FailureKind::Transient,
+            _ => FailureKind::Permanent,
+        }
+    }
+}
+
+/// The failure that moved a contract to the FailedAccept or FailedSign state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContractFailure {
+    /// The kind of the latest error.
+    pub kind: FailureKind,
+    /// The unix time of the latest failure.
+    pub failed_at: u64,
+    /// The number of times verifying the message failed, including retries.
+    pub attempts: u32,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            FailureKind::classify(&Error::BlockchainError("timeout".to_string())),
+            FailureKind::Transient
+        );
+        assert_eq!(
+            FailureKind::classify(&Error::WalletError("locked".into())),
+            FailureKind::Transient
+        );
+        assert_eq!(
+            FailureKind::classify(&Error::InvalidParameters("bad signature".to_string())),
+            FailureKind::Permanent
+        );
+        assert_eq!(
+            FailureKind::classify(&Error::InvalidState("not accepted".to_string())),
+            FailureKind::Permanent
+        );
+    }
+}
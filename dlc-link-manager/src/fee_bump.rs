@@ -0,0 +1,148 @@
+//! #FeeBump child-pays-for-parent transactions for stuck funding, closing and
+//! refund transactions.
+
+use bitcoin::{OutPoint, PackedLockTime, Script, Sequence, Transaction, TxIn, TxOut, Witness};
+use dlc_manager::contract::accepted_contract::AcceptedContract;
+use dlc_manager::error::Error;
+
+use crate::mutual_close::get_fund_output;
+use crate::DUST_LIMIT;
+
+/// Returns the script of the local party's change output of the funding
+/// transaction.
+pub(crate) fn get_own_change_script(contract: &AcceptedContract) -> &Script {
+    if contract.offered_contract.is_offer_party {
+        &contract.offered_contract.offer_params.change_script_pubkey
+    } else {
+        &contract.accept_params.change_script_pubkey
+    }
+}
+
+/// Returns the fee paid by the funding transaction of the contract.
+pub(crate) fn get_fund_fee(contract: &AcceptedContract) -> u64 {
+    let input_amount =
+        contract.offered_contract.offer_params.input_amount + contract.accept_params.input_amount;
+    input_amount.saturating_sub(get_output_value(&contract.dlc_transactions.fund))
+}
+
+/// Returns the fee paid by a transaction spending the funding output of the
+/// contract: a CET, the refund or a mutual close transaction.
+pub(crate) fn get_spending_fee(
+    contract: &AcceptedContract,
+    tx: &Transaction,
+) -> Result<u64, Error> {
+    let (_, fund_value, _) = get_fund_output(contract)?;
+    Ok(fund_value.saturating_sub(get_output_value(tx)))
+}
+
+fn get_output_value(tx: &Transaction) -> u64 {
+    tx.output.iter().map(|output| output.value).sum()
+}
+
+/// Returns the virtual size of the transaction, rounded up.
+pub(crate) fn get_vsize(tx: &Transaction) -> u64 {
+    (tx.weight() as u64 + 3) / 4
+}
+
+/// Returns the fee the child transaction must pay for the parent and child
+/// package to reach `fee_rate`, in sats per vbyte. When replacing a previous
+/// child, the fee must also exceed the previous one by the minimum relay fee
+/// of the new child.
+pub(crate) fn get_child_fee(
+    parent: &Transaction,
+    parent_fee: u64,
+    child_vsize: u64,
+    fee_rate: u64,
+    previous_fee: Option<u64>,
+) -> u64 {
+    let package_fee = fee_rate * (get_vsize(parent) + child_vsize);
+    let child_fee = package_fee.saturating_sub(parent_fee).max(child_vsize);
+    match previous_fee {
+        Some(previous_fee) => child_fee.max(previous_fee + child_vsize),
+        None => child_fee,
+    }
+}
+
+/// Builds the unsigned child transaction spending output `vout` of `parent`
+/// to `destination`, paying `fee`. The input signals replaceability so that
+/// the fee can be bumped again.
+pub(crate) fn build_child_transaction(
+    parent: &Transaction,
+    vout: u32,
+    destination: Script,
+    fee: u64,
+) -> Result<Transaction, Error> {
+    let value = parent
+        .output
+        .get(vout as usize)
+        .map(|output| output.value)
+        .ok_or(Error::InvalidParameters(
+            "Parent output not found".to_string(),
+        ))?
+        .checked_sub(fee)
+        .filter(|value| *value >= DUST_LIMIT)
+        .ok_or(Error::InvalidState(
+            "Output too small to pay for the fee bump".to_string(),
+        ))?;
+
+    Ok(Transaction {
+        version: 2,
+        lock_time: PackedLockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: parent.txid(),
+                vout,
+            },
+            script_sig: Script::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::default(),
+        }],
+        output: vec![TxOut {
+            value,
+            script_pubkey: destination,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parent() -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: PackedLockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut {
+                value: 100_000,
+                script_pubkey: Script::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_child_fee_covers_package() {
+        let parent = parent();
+        let parent_vsize = get_vsize(&parent);
+        let fee = get_child_fee(&parent, parent_vsize, 110, 10, None);
+        assert_eq!(fee, 10 * (parent_vsize + 110) - parent_vsize);
+    }
+
+    #[test]
+    fn test_child_fee_replaces_previous_bump() {
+        let parent = parent();
+        assert_eq!(get_child_fee(&parent, 100_000, 110, 1, None), 110);
+        assert_eq!(get_child_fee(&parent, 100_000, 110, 1, Some(500)), 610);
+    }
+
+    #[test]
+    fn test_child_transaction_rejects_dust() {
+        let parent = parent();
+        assert!(build_child_transaction(&parent, 0, Script::new(), 99_500).is_err());
+        assert!(build_child_transaction(&parent, 1, Script::new(), 1_000).is_err());
+        let child = build_child_transaction(&parent, 0, Script::new(), 1_000)
+            .expect("to be able to build the child transaction");
+        assert_eq!(child.output[0].value, 99_000);
+        assert_eq!(child.input[0].previous_output.txid, parent.txid());
+    }
+}
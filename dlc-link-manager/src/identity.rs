@@ -0,0 +1,80 @@
+//! #Identity signatures proving which party sent a DLC message.
+
+use dlc_manager::error::Error;
+use dlc_messages::Message as DlcMessage;
+use lightning::util::ser::Writeable;
+use secp256k1_zkp::ecdsa::Signature;
+use secp256k1_zkp::hashes::{sha256, Hash};
+use secp256k1_zkp::{Message, PublicKey, Secp256k1, SecretKey, Signing, Verification};
+
+/// Prepended to the signed data so that these signatures cannot be replayed
+/// as signatures of anything else.
+const MESSAGE_TAG: &[u8] = b"dlc-link/message";
+
+/// Prepended to the wallet key to derive the identity key from it.
+const IDENTITY_TAG: &[u8] = b"dlc-link/identity";
+
+/// Derives the key identifying a party to its counter parties from the secret
+/// key of its wallet. Signing messages with it does not expose the key that
+/// spends the wallet funds, while the identity stays the same as long as the
+/// wallet does.
+pub fn derive_identity_key(wallet_key: &SecretKey) -> Result<SecretKey, Error> {
+    let hash = sha256::Hash::hash(&[IDENTITY_TAG, &wallet_key.secret_bytes()[..]].concat());
+    SecretKey::from_slice(&hash.into_inner())
+        .map_err(|e| Error::InvalidParameters(format!("Could not derive an identity key: {}", e)))
+}
+
+/// Returns the hash signed to authenticate a DLC message, covering the message
+/// type and its wire encoding.
+fn get_message_hash(msg: &DlcMessage) -> Result<Message, Error> {
+    let (type_tag, encoded): (&[u8], Vec<u8>) = match msg {
+        DlcMessage::Offer(o) => (b"offer", o.encode()),
+        DlcMessage::Accept(a) => (b"accept", a.encode()),
+        DlcMessage::Sign(s) => (b"sign", s.encode()),
+        DlcMessage::OfferChannel(o) => (b"offer_channel", o.encode()),
+        DlcMessage::AcceptChannel(a) => (b"accept_channel", a.encode()),
+        DlcMessage::SignChannel(s) => (b"sign_channel", s.encode()),
+        DlcMessage::SettleOffer(s) => (b"settle_offer", s.encode()),
+        DlcMessage::SettleAccept(s) => (b"settle_accept", s.encode()),
+        DlcMessage::SettleConfirm(s) => (b"settle_confirm", s.encode()),
+        DlcMessage::SettleFinalize(s) => (b"settle_finalize", s.encode()),
+        DlcMessage::RenewOffer(r) => (b"renew_offer", r.encode()),
+        DlcMessage::RenewAccept(r) => (b"renew_accept", r.encode()),
+        DlcMessage::RenewConfirm(r) => (b"renew_confirm", r.encode()),
+        DlcMessage::RenewFinalize(r) => (b"renew_finalize", r.encode()),
+        DlcMessage::CollaborativeCloseOffer(c) => (b"collaborative_close_offer", c.encode()),
+        _ => {
+            return Err(Error::InvalidParameters(
+                "Message type cannot be authenticated.".to_string(),
+            ))
+        }
+    };
+    let data = [MESSAGE_TAG, type_tag, &encoded].concat();
+    Ok(Message::from_hashed_data::<sha256::Hash>(&data))
+}
+
+/// Signs a DLC message with the key identifying us to the counter party, see
+/// [`derive_identity_key`].
+pub fn sign_dlc_message<C: Signing>(
+    secp: &Secp256k1<C>,
+    msg: &DlcMessage,
+    secret_key: &SecretKey,
+) -> Result<Signature, Error> {
+    Ok(secp.sign_ecdsa(&get_message_hash(msg)?, secret_key))
+}
+
+/// Verifies that a DLC message was signed by the given counter party.
+pub fn verify_dlc_message<C: Verification>(
+    secp: &Secp256k1<C>,
+    msg: &DlcMessage,
+    signature: &Signature,
+    counter_party: &PublicKey,
+) -> Result<(), Error> {
+    secp.verify_ecdsa(&get_message_hash(msg)?, signature, counter_party)
+        .map_err(|_| {
+            Error::InvalidParameters(format!(
+                "Message is not signed by counter party {}.",
+                counter_party
+            ))
+        })
+}
@@ -6,6 +6,75 @@
 
 extern crate dlc_manager;
 
+mod attestation;
+mod clock;
+mod config;
+mod failure;
+mod fee_bump;
+mod identity;
+mod lock;
+mod metadata;
+mod mutual_close;
+mod observer;
+mod oracle_resolver;
+mod payout;
+mod policy;
+mod report;
+mod role;
+mod telemetry;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+
+pub use attestation::{AttestationState, AttestationStatus, MissingAttestation};
+pub use clock::{AsyncClock, NetworkClock};
+pub use config::{ConfirmationTier, FailedContractPolicy, ManagerConfig};
+pub use failure::{ContractFailure, FailureKind};
+pub use identity::{derive_identity_key, sign_dlc_message, verify_dlc_message};
+pub use metadata::{
+    ContractMetadata, FeeBump, OracleDisagreement, Rebroadcast, UnconfirmedTransaction,
+};
+pub use mutual_close::{CloseAccept, CloseOffer};
+pub use observer::{ContractObserver, ContractState, ContractTransition};
+pub use oracle_resolver::{NoOracleResolver, OracleResolver};
+pub use payout::{
+    ContractExposure, ContractInfoPayouts, ExposureSummary, Outcome, PayoutEntry, PayoutOutcomes,
+    PayoutTable,
+};
+pub use policy::{AcceptPolicy, PayoutCurveShape};
+pub use report::{
+    ChannelCheckError, ChannelCloseOffer, ContractCheckError, ContractUpdate, PeriodicCheckReport,
+    UnexpectedSpend,
+};
+pub use role::{Acceptor, ManagerRole, Offerer};
+pub use telemetry::describe_metrics;
+
+use attestation::{get_agreed_outcomes, get_attested_payout, validate_attestation};
+use fee_bump::{
+    build_child_transaction, get_child_fee, get_fund_fee, get_own_change_script, get_spending_fee,
+    get_vsize,
+};
+use lock::{ContractLock, ContractLocks};
+use mutual_close::{
+    build_close_transaction, finalize_close_transaction, get_counter_payout, get_fund_output,
+    get_fund_pubkeys, get_own_payout, get_own_payout_script, is_mutual_close_transaction,
+    sign_close_transaction, verify_close_signature,
+};
+use observer::get_contract_event_id;
+use policy::get_paid_protocol_fee;
+use report::{ChannelCheckOutcome, CheckOutcome, CleanupOutcome};
+use role::is_sent_to;
+use telemetry::{
+    contract_span, format_message_id, get_input_event_id, record_broadcast_failure,
+    record_close_latency, record_contract_count, record_contract_id, record_event_id,
+    record_transition, TransactionType,
+};
+
+use crate::dlc_manager::channel::{
+    offered_channel::OfferedChannel,
+    signed_channel::{SignedChannel, SignedChannelState, SignedChannelStateType},
+    Channel,
+};
+use crate::dlc_manager::channel_updater;
 use crate::dlc_manager::contract::{
     accepted_contract::AcceptedContract, contract_info::ContractInfo,
     contract_input::ContractInput, offered_contract::OfferedContract,
@@ -16,22 +85,30 @@ use crate::dlc_manager::contract_updater::{accept_contract, verify_accepted_and_
 use crate::dlc_manager::error::Error;
 use crate::dlc_manager::{Blockchain, Time, Wallet};
 
-use bitcoin::{Address, Transaction, Txid};
+use bitcoin::{Address, OutPoint, Script, Transaction, Txid};
+use dlc::PartyParams;
 
-use dlc_manager::ContractId;
+use dlc_manager::{ChannelId, ContractId};
+use dlc_messages::channel::{
+    AcceptChannel, CollaborativeCloseOffer, OfferChannel, RenewAccept, RenewConfirm, RenewFinalize,
+    RenewOffer, SettleAccept, SettleConfirm, SettleFinalize, SettleOffer, SignChannel,
+};
 use dlc_messages::oracle_msgs::{OracleAnnouncement, OracleAttestation};
 use dlc_messages::{AcceptDlc, Message as DlcMessage, OfferDlc, SignDlc};
 
-use log::*;
+use futures::stream::{self, FuturesUnordered, StreamExt};
+use secp256k1_zkp::ecdsa::Signature;
 use secp256k1_zkp::XOnlyPublicKey;
 use secp256k1_zkp::{All, PublicKey, Secp256k1};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
 use std::ops::Deref;
 use std::string::ToString;
+use tracing::{debug, error, field, info, instrument, warn, Instrument};
 
-/// The number of confirmations required before moving the the confirmed state.
+/// The default number of confirmations required before moving the the confirmed state.
 pub const NB_CONFIRMATIONS: u32 = 6;
-/// The upper bound for the delay refund verification check, 10 years.
+/// The default upper bound for the delay refund verification check, 50 years.
 pub const FIFTY_YEARS: u32 = 86400 * 365 * 50;
 pub const ONE_DAY_IN_SECONDS: u32 = 86400;
 /// The nSequence value used for CETs in DLC channels
@@ -39,6 +116,11 @@ pub const CET_NSEQUENCE: u32 = 288;
 /// Timeout in seconds when waiting for a peer's reply, after which a DLC channel
 /// is forced closed.
 pub const PEER_TIMEOUT: u64 = 3600;
+/// Transaction outputs below this value are not created and their value goes
+/// to fees, as in CETs.
+pub const DUST_LIMIT: u64 = 1000;
+/// The number of basis points in the whole collateral.
+pub const BASIS_POINTS_SCALE: u64 = 10_000;
 
 type ClosableContractInfo<'a> = Option<(
     &'a ContractInfo,
@@ -67,6 +149,9 @@ pub trait AsyncStorage {
     async fn delete_contract(&self, id: &ContractId) -> Result<(), Error>;
     /// Update the given contract.
     async fn update_contract(&self, contract: &Contract) -> Result<(), Error>;
+    /// Replace the record for the contract with the given id by the given
+    /// contract, which may be stored under another id, in a single operation.
+    async fn replace_contract(&self, id: &ContractId, contract: &Contract) -> Result<(), Error>;
     /// Returns the set of contracts in offered state.
     async fn get_contract_offers(&self) -> Result<Vec<OfferedContract>, Error>;
     /// Returns the set of contracts in signed state.
@@ -76,6 +161,44 @@ pub trait AsyncStorage {
     /// Returns the set of contracts whos broadcasted cet has not been verified to be confirmed on
     /// blockchain
     async fn get_preclosed_contracts(&self) -> Result<Vec<PreClosedContract>, Error>;
+    /// Returns the set of contracts in the given state.
+    async fn get_contracts_in_state(&self, state: ContractState) -> Result<Vec<Contract>, Error>;
+    /// Returns the side record kept by the Manager for the contract with the
+    /// given id, if any.
+    async fn get_contract_metadata(
+        &self,
+        id: &ContractId,
+    ) -> Result<Option<ContractMetadata>, Error>;
+    /// Delete the side record for the contract with the given id, if any.
+    async fn delete_contract_metadata(&self, id: &ContractId) -> Result<(), Error>;
+    /// Create or replace the side record for the contract with the given id.
+    async fn upsert_contract_metadata(
+        &self,
+        id: &ContractId,
+        metadata: &ContractMetadata,
+    ) -> Result<(), Error>;
+    /// Returns the channel with given id if found.
+    async fn get_channel(&self, id: &ChannelId) -> Result<Option<Channel>, Error>;
+    /// Create or replace the record for the given channel. Accepted and signed
+    /// channels replace the record kept under their temporary id.
+    async fn upsert_channel(&self, channel: &Channel) -> Result<(), Error>;
+    /// Delete the record for the channel with the given id.
+    async fn delete_channel(&self, id: &ChannelId) -> Result<(), Error>;
+    /// Returns the set of channels in offered state.
+    async fn get_offered_channels(&self) -> Result<Vec<OfferedChannel>, Error>;
+    /// Returns the set of signed channels, only those in the given state if
+    /// any.
+    async fn get_signed_channels(
+        &self,
+        channel_state: Option<SignedChannelStateType>,
+    ) -> Result<Vec<SignedChannel>, Error>;
+}
+
+/// Wallet releasing the UTXOs it reserved to fund a contract, once the
+/// contract is given up before being signed.
+pub trait ReservingWallet {
+    /// Make the given outputs available again to fund other contracts.
+    fn unreserve_utxos(&self, outpoints: &[OutPoint]) -> Result<(), Error>;
 }
 
 pub trait AsyncBlockchain {
@@ -87,30 +210,231 @@ pub trait AsyncBlockchain {
     async fn get_network_async(&self) -> Result<bitcoin::Network, Error>;
 
     async fn get_transaction_async(&self, tx_id: &Txid) -> Result<Transaction, Error>;
+
+    /// Returns the id of the transaction spending the given output, if any.
+    async fn get_spending_txid_async(&self, outpoint: &OutPoint) -> Result<Option<Txid>, Error>;
+
+    /// Returns whether the transaction is known, either in the mempool or in
+    /// the chain.
+    async fn is_transaction_known_async(&self, txid: &Txid) -> Result<bool, Error>;
+
+    /// Returns the height of the tip of the chain.
+    async fn get_blockchain_height_async(&self) -> Result<u64, Error>;
+
+    /// Returns the estimated fee rate, in sats per vbyte, for a transaction to
+    /// confirm within the given number of blocks.
+    async fn get_fee_rate_async(&self, confirmation_target: u16) -> Result<u64, Error>;
+}
+
+/// What, if anything, was found spending the funding output of a contract.
+enum FundingSpend {
+    Unspent,
+    Cet(usize, Transaction),
+    Refund(Transaction),
+    MutualClose(Transaction),
+    Unexpected(Txid),
+}
+
+/// Returns the protocol fee, in satoshis, of `basis_points` of the total
+/// collateral, rounded down to the satoshi.
+fn get_protocol_fee(total_collateral: u64, basis_points: u64) -> Result<u64, Error> {
+    if basis_points > BASIS_POINTS_SCALE {
+        return Err(Error::InvalidParameters(format!(
+            "Protocol fee of {} basis points exceeds the total collateral",
+            basis_points
+        )));
+    }
+    // Neither the product nor the result can overflow once widened.
+    Ok((total_collateral as u128 * basis_points as u128 / BASIS_POINTS_SCALE as u128) as u64)
+}
+
+/// Returns the protocol fee, in satoshis, that an offer made with
+/// `basis_points` charges on the given total collateral, which is exactly the
+/// fee of [`get_protocol_fee`].
+///
+/// Contracts charge `total_collateral / denominator` for an integer
+/// denominator, which cannot express every fee. Fees of basis points dividing
+/// 10,000 always can be charged. Other fees are refused with an error when no
+/// denominator charges them exactly, rather than charging a rounded amount.
+pub fn get_charged_protocol_fee(total_collateral: u64, basis_points: u64) -> Result<u64, Error> {
+    let protocol_fee = get_protocol_fee(total_collateral, basis_points)?;
+    get_fee_denominator(total_collateral, protocol_fee)?;
+    Ok(protocol_fee)
+}
+
+/// Returns the fee denominator to offer contracts with, which charge
+/// `total_collateral / denominator` as protocol fee, so that exactly `fee` is
+/// charged. A denominator of 0 charges no fee.
+fn get_fee_denominator(total_collateral: u64, fee: u64) -> Result<u64, Error> {
+    if fee == 0 {
+        return Ok(0);
+    }
+    // Larger denominators charge less, and this is the largest one charging
+    // at least `fee`. If it charges more, every other one does too.
+    let denominator = (total_collateral / fee).max(1);
+    if total_collateral / denominator == fee {
+        Ok(denominator)
+    } else {
+        Err(Error::InvalidParameters(format!(
+            "Protocol fee of {} sats cannot be charged exactly on a total collateral of {} sats",
+            fee, total_collateral
+        )))
+    }
+}
+
+/// Returns the contract in PreClosed state if the given CET does not have
+/// the required number of confirmations yet, in Closed state otherwise.
+fn get_closed_or_preclosed_contract(
+    contract: &SignedContract,
+    signed_cet: Transaction,
+    attestations: Option<Vec<OracleAttestation>>,
+    confirmations: u32,
+    required_confirmations: u32,
+) -> Contract {
+    if confirmations < required_confirmations {
+        return Contract::PreClosed(PreClosedContract {
+            signed_contract: contract.clone(),
+            attestations,
+            signed_cet,
+        });
+    }
+
+    Contract::Closed(ClosedContract {
+        attestations,
+        pnl: contract.accepted_contract.compute_pnl(&signed_cet),
+        signed_cet: Some(signed_cet),
+        contract_id: contract.accepted_contract.get_contract_id(),
+        temporary_contract_id: contract.accepted_contract.offered_contract.id,
+        counter_party_id: contract.accepted_contract.offered_contract.counter_party,
+    })
+}
+
+/// Returns the event id of the first oracle of the contract.
+fn get_event_id(contract_info: &[ContractInfo]) -> Option<&str> {
+    contract_info
+        .first()
+        .and_then(|info| info.oracle_announcements.first())
+        .map(|announcement| announcement.oracle_event.event_id.as_str())
+}
+
+/// Returns the accepted contract of contracts that got at least as far as
+/// being accepted and still hold their contract transactions.
+fn get_accepted_contract(contract: &Contract) -> Option<&AcceptedContract> {
+    match contract {
+        Contract::Accepted(c) => Some(c),
+        Contract::Signed(c) | Contract::Confirmed(c) | Contract::Refunded(c) => {
+            Some(&c.accepted_contract)
+        }
+        Contract::PreClosed(c) => Some(&c.signed_contract.accepted_contract),
+        _ => None,
+    }
+}
+
+fn unexpected_channel_state(expected: &str, channel: &SignedChannel) -> Error {
+    Error::InvalidState(format!(
+        "Expected channel state {} got {:?}",
+        expected, channel.state
+    ))
+}
+
+/// Returns the time after which the counter party is deemed unresponsive, for
+/// channels waiting on it to complete an update.
+fn get_channel_timeout(state: &SignedChannelState) -> Option<u64> {
+    match state {
+        SignedChannelState::SettledOffered { timeout, .. }
+        | SignedChannelState::SettledAccepted { timeout, .. }
+        | SignedChannelState::SettledConfirmed { timeout, .. }
+        | SignedChannelState::RenewOffered { timeout, .. }
+        | SignedChannelState::RenewAccepted { timeout, .. }
+        | SignedChannelState::RenewConfirmed { timeout, .. }
+        | SignedChannelState::CollaborativeCloseOffered { timeout, .. } => Some(*timeout),
+        _ => None,
+    }
+}
+
+/// Returns the outpoints of our funding inputs of a contract that was not
+/// signed, which the wallet reserved when offering or accepting it.
+fn get_own_funding_outpoints(contract: &Contract) -> Vec<OutPoint> {
+    match contract {
+        Contract::Offered(c) => get_own_inputs(c, None),
+        Contract::FailedAccept(c) => get_own_inputs(&c.offered_contract, None),
+        Contract::Accepted(c) => get_own_inputs(&c.offered_contract, Some(&c.accept_params)),
+        Contract::FailedSign(c) => get_own_inputs(
+            &c.accepted_contract.offered_contract,
+            Some(&c.accepted_contract.accept_params),
+        ),
+        _ => Vec::new(),
+    }
 }
 
-fn calculate_denominator_from_basis_points(basis_points: u64) -> u64 {
-    if basis_points == 0 {
-        return 0;
+/// Returns the outpoints of our funding inputs, given the accept party
+/// parameters once the contract is accepted.
+fn get_own_inputs(
+    offered_contract: &OfferedContract,
+    accept_params: Option<&PartyParams>,
+) -> Vec<OutPoint> {
+    let own_params = if offered_contract.is_offer_party {
+        Some(&offered_contract.offer_params)
+    } else {
+        accept_params
+    };
+    own_params.map_or(Vec::new(), |params| {
+        params.inputs.iter().map(|input| input.outpoint).collect()
+    })
+}
+
+/// Give up on the update a channel is waiting on, restoring the state it was
+/// in before the update was offered.
+fn roll_back_channel(channel: &mut SignedChannel) -> Result<(), Error> {
+    match channel.roll_back_state.take() {
+        Some(state) => {
+            channel.state = state;
+            Ok(())
+        }
+        None => Err(Error::InvalidState(format!(
+            "Channel {:02x?} has no state to roll back to.",
+            channel.channel_id
+        ))),
     }
-    ((100.0 / basis_points as f64) * 100.0) as u64
 }
 
 /// Used to create and update DLCs.
-pub struct Manager<W: Deref, B: Deref, S: Deref, O: Deref, T: Deref>
-where
-    W::Target: Wallet,
+///
+/// The `Role` parameter tells whether the Manager offers contracts, as the
+/// router wallet does, or accepts them, as the user wallets do. Only the APIs
+/// of its role are available.
+pub struct Manager<
+    W: Deref,
+    B: Deref,
+    S: Deref,
+    O: Deref,
+    T: Deref,
+    Role: ManagerRole,
+    R = NoOracleResolver,
+    C = NetworkClock,
+> where
+    W::Target: Wallet + ReservingWallet,
     B::Target: Blockchain + AsyncBlockchain,
     S::Target: AsyncStorage,
     O::Target: AsyncOracle,
     T::Target: Time,
+    R: OracleResolver<O>,
+    C: AsyncClock,
 {
-    pub oracles: Option<HashMap<XOnlyPublicKey, O>>,
+    /// The oracles used to make offers and to fetch the attestations of
+    /// contracts.
+    pub oracles: HashMap<XOnlyPublicKey, O>,
+    oracle_resolver: R,
     wallet: W,
     blockchain: B,
     store: S,
     secp: Secp256k1<All>,
     time: T,
+    clock: C,
+    config: ManagerConfig,
+    observers: Vec<Box<dyn ContractObserver + Send + Sync>>,
+    contract_locks: ContractLocks,
+    role: PhantomData<Role>,
 }
 
 macro_rules! get_object_in_state {
@@ -156,21 +480,79 @@ macro_rules! get_contract_in_state {
     }};
 }
 
-impl<W: Deref, B: Deref, S: Deref, O: Deref, T: Deref> Manager<W, B, S, O, T>
+macro_rules! get_channel_in_state {
+    ($manager: ident, $channel_id: expr, $state: ident, $peer_id: expr) => {{
+        get_object_in_state!(
+            $manager,
+            $channel_id,
+            $state,
+            $peer_id,
+            Channel,
+            get_channel
+        )
+    }};
+}
+
+impl<W: Deref, B: Deref, S: Deref, O: Deref, T: Deref> Manager<W, B, S, O, T, Offerer>
+where
+    W::Target: Wallet + ReservingWallet,
+    B::Target: Blockchain + AsyncBlockchain,
+    S::Target: AsyncStorage,
+    O::Target: AsyncOracle,
+    T::Target: Time,
+{
+    /// Create a Manager making offers with the announcements of the given
+    /// oracles.
+    pub fn new_offerer(
+        wallet: W,
+        blockchain: B,
+        store: S,
+        oracles: HashMap<XOnlyPublicKey, O>,
+        time: T,
+        config: ManagerConfig,
+    ) -> Result<Self, Error> {
+        Self::new(wallet, blockchain, store, oracles, time, config)
+    }
+}
+
+impl<W: Deref, B: Deref, S: Deref, O: Deref, T: Deref> Manager<W, B, S, O, T, Acceptor>
+where
+    W::Target: Wallet + ReservingWallet,
+    B::Target: Blockchain + AsyncBlockchain,
+    S::Target: AsyncStorage,
+    O::Target: AsyncOracle,
+    T::Target: Time,
+{
+    /// Create a Manager accepting offers, fetching attestations from the given
+    /// oracles to close the contracts.
+    pub fn new_acceptor(
+        wallet: W,
+        blockchain: B,
+        store: S,
+        oracles: HashMap<XOnlyPublicKey, O>,
+        time: T,
+        config: ManagerConfig,
+    ) -> Result<Self, Error> {
+        Self::new(wallet, blockchain, store, oracles, time, config)
+    }
+}
+
+impl<W: Deref, B: Deref, S: Deref, O: Deref, T: Deref, Role: ManagerRole>
+    Manager<W, B, S, O, T, Role>
 where
-    W::Target: Wallet,
+    W::Target: Wallet + ReservingWallet,
     B::Target: Blockchain + AsyncBlockchain,
     S::Target: AsyncStorage,
     O::Target: AsyncOracle,
     T::Target: Time,
 {
-    /// Create a new Manager struct.
-    pub fn new(
+    fn new(
         wallet: W,
         blockchain: B,
         store: S,
-        oracles: Option<HashMap<XOnlyPublicKey, O>>,
+        oracles: HashMap<XOnlyPublicKey, O>,
         time: T,
+        config: ManagerConfig,
     ) -> Result<Self, Error> {
         Ok(Manager {
             secp: secp256k1_zkp::Secp256k1::new(),
@@ -178,42 +560,86 @@ where
             blockchain,
             store,
             oracles,
+            oracle_resolver: NoOracleResolver,
             time,
+            clock: NetworkClock,
+            config,
+            observers: Vec::new(),
+            contract_locks: ContractLocks::default(),
+            role: PhantomData,
         })
     }
 
-    /// Get the store from the Manager to access contracts.
-    pub fn get_store(&self) -> &S {
-        &self.store
-    }
-
-    #[doc(hidden)]
-    pub fn get_mut_store(&self) -> &S {
-        &self.store
+    /// Use the given resolver to find the oracles announced in a contract that
+    /// are not among the Manager's oracles when closing it.
+    pub fn with_oracle_resolver<R: OracleResolver<O>>(
+        self,
+        oracle_resolver: R,
+    ) -> Manager<W, B, S, O, T, Role, R> {
+        Manager {
+            secp: self.secp,
+            wallet: self.wallet,
+            blockchain: self.blockchain,
+            store: self.store,
+            oracles: self.oracles,
+            oracle_resolver,
+            time: self.time,
+            clock: self.clock,
+            config: self.config,
+            observers: self.observers,
+            contract_locks: self.contract_locks,
+            role: self.role,
+        }
     }
+}
 
-    /// Function called to pass a DlcMessage to the Manager.
-    pub async fn on_dlc_message(
-        &self,
-        msg: &DlcMessage,
-        counter_party: PublicKey,
-    ) -> Result<Option<DlcMessage>, Error> {
-        match msg {
-            DlcMessage::Offer(o) => {
-                self.on_offer_message(o, counter_party).await?;
-                Ok(None)
-            }
-            DlcMessage::Accept(a) => Ok(Some(self.on_accept_message(a, &counter_party).await?)),
-            DlcMessage::Sign(s) => {
-                self.on_sign_message(s, &counter_party).await?;
-                Ok(None)
-            }
-            _ => Err(Error::InvalidState("Invalid message type.".to_string())),
+impl<W: Deref, B: Deref, S: Deref, O: Deref, T: Deref, Role: ManagerRole, R>
+    Manager<W, B, S, O, T, Role, R>
+where
+    W::Target: Wallet + ReservingWallet,
+    B::Target: Blockchain + AsyncBlockchain,
+    S::Target: AsyncStorage,
+    O::Target: AsyncOracle,
+    T::Target: Time,
+    R: OracleResolver<O>,
+{
+    /// Use the given clock instead of the [`NetworkClock`] time server to decide
+    /// whether the refund transaction of a contract can be broadcast.
+    pub fn with_clock<C: AsyncClock>(self, clock: C) -> Manager<W, B, S, O, T, Role, R, C> {
+        Manager {
+            secp: self.secp,
+            wallet: self.wallet,
+            blockchain: self.blockchain,
+            store: self.store,
+            oracles: self.oracles,
+            oracle_resolver: self.oracle_resolver,
+            time: self.time,
+            clock,
+            config: self.config,
+            observers: self.observers,
+            contract_locks: self.contract_locks,
+            role: self.role,
         }
     }
+}
 
+impl<W: Deref, B: Deref, S: Deref, O: Deref, T: Deref, R, C> Manager<W, B, S, O, T, Offerer, R, C>
+where
+    W::Target: Wallet + ReservingWallet,
+    B::Target: Blockchain + AsyncBlockchain,
+    S::Target: AsyncStorage,
+    O::Target: AsyncOracle,
+    T::Target: Time,
+    R: OracleResolver<O>,
+    C: AsyncClock,
+{
     /// Function called to create a new DLC. The offered contract will be stored
-    /// and an OfferDlc message returned.
+    /// and an OfferDlc message returned. The offer is refused if its protocol
+    /// fee cannot be charged exactly, see [`get_charged_protocol_fee`].
+    #[instrument(
+        skip_all,
+        fields(contract_id = field::Empty, event_id = get_input_event_id(contract_input))
+    )]
     pub async fn send_offer(
         &self,
         contract_input: &ContractInput,
@@ -222,71 +648,11 @@ where
         protocol_fee_basis_points: u64,
         fee_address: Address,
     ) -> Result<OfferDlc, Error> {
-        let manager_oracles = match &self.oracles {
-            // Oracles is now an optional field, so check here before continuing.
-            Some(oracles) => oracles,
-            None => {
-                return Err(Error::InvalidParameters(
-                    "Manager instantiated without oracles, send_offer function not supported"
-                        .to_string(),
-                ));
-            }
-        };
-        contract_input.validate()?;
-
-        // let contract_infos = &contract_input.contract_infos;
-        // let mut oracle_announcements = Vec::new();
-        let event_id = &contract_input
-            .contract_infos
-            .first()
-            .ok_or(Error::InvalidParameters(
-                "Contract Input Info missing".to_string(),
-            ))?
-            .oracles
-            .event_id;
-        debug!(
-            "manager_oracles keys {:?}",
-            manager_oracles
-                .keys()
-                .map(|k| k.to_string())
-                .collect::<Vec<String>>()
-                .join(", ")
-        );
-        let oracle_set: Vec<Vec<&O>> = contract_input
-            .contract_infos
-            .iter()
-            .map(|x| {
-                debug!(
-                    "contract public keys: {}",
-                    x.oracles
-                        .public_keys
-                        .iter()
-                        .map(|k| k.to_string())
-                        .collect::<Vec<String>>()
-                        .join(", ")
-                );
-                x.oracles
-                    .public_keys
-                    .iter()
-                    .map(|pubkey| match manager_oracles.get(pubkey) {
-                        Some(x) => Ok(x),
-                        None => Err(Error::InvalidParameters(
-                            "Unknown oracle public key".to_string(),
-                        )),
-                    })
-                    .collect::<Result<Vec<&O>, Error>>()
-            })
-            .collect::<Result<Vec<Vec<&O>>, Error>>()?;
-
-        let mut oracle_announcements = Vec::new();
+        let total_collateral = contract_input.offer_collateral + contract_input.accept_collateral;
+        let protocol_fee = get_protocol_fee(total_collateral, protocol_fee_basis_points)?;
+        let fee_denominator = get_fee_denominator(total_collateral, protocol_fee)?;
 
-        for oracles in oracle_set {
-            let mut announcements = Vec::new();
-            for oracle in oracles {
-                announcements.push(oracle.get_announcement(event_id).await?);
-            }
-            oracle_announcements.push(announcements)
-        }
+        let oracle_announcements = self.get_oracle_announcements(contract_input).await?;
 
         let (offered_contract, offer_msg) = crate::dlc_manager::contract_updater::offer_contract(
             &self.secp,
@@ -297,24 +663,144 @@ where
             &self.wallet,
             &self.blockchain,
             &self.time,
-            calculate_denominator_from_basis_points(protocol_fee_basis_points),
+            fee_denominator,
             fee_address,
         )?;
 
+        record_contract_id(&offered_contract.id);
         offered_contract.validate()?;
 
-        self.store.create_contract(&offered_contract).await?;
+        self.create_contract(&offered_contract).await?;
 
         Ok(offer_msg)
     }
 
+    /// Function to call to withdraw an offer we made that was not accepted
+    /// yet. Accept messages received for it afterwards are refused.
+    pub async fn abandon_offer(&self, temporary_id: &ContractId) -> Result<(), Error> {
+        let _lock = self.lock_contract(temporary_id)?;
+        let offered_contract =
+            get_contract_in_state!(self, temporary_id, Offered, None as Option<PublicKey>)?;
+        if !offered_contract.is_offer_party {
+            return Err(Error::InvalidState(
+                "Cannot abandon an offer we received, reject it instead.".to_string(),
+            ));
+        }
+
+        self.update_contract(
+            ContractState::Offered,
+            &Contract::Rejected(offered_contract),
+        )
+        .await
+    }
+
+    /// Function to call to verify again the accept message of a contract that
+    /// failed with a transient error, such as an unreachable wallet. Returns
+    /// the SignDlc message to send to the counter party if it succeeds.
+    #[instrument(skip_all, fields(contract_id = field::Empty, event_id = field::Empty))]
+    pub async fn retry_failed_accept(
+        &self,
+        temporary_id: &ContractId,
+    ) -> Result<(SignDlc, PublicKey), Error> {
+        record_contract_id(temporary_id);
+        let _lock = self.lock_contract(temporary_id)?;
+        let failed_contract =
+            get_contract_in_state!(self, temporary_id, FailedAccept, None as Option<PublicKey>)?;
+        record_event_id(&failed_contract.offered_contract.contract_info);
+        self.check_retryable(temporary_id).await?;
+
+        let counter_party = failed_contract.offered_contract.counter_party;
+        let sign_msg = self
+            .process_accept_message(
+                ContractState::FailedAccept,
+                failed_contract.offered_contract,
+                &failed_contract.accept_message,
+            )
+            .await?;
+        self.clear_failure(temporary_id).await?;
+
+        Ok((sign_msg, counter_party))
+    }
+
+    /// Function called to create a new DLC channel. The offered channel and its
+    /// first contract will be stored and an OfferChannel message returned.
+    ///
+    /// Channels are only available through the Manager: neither the router
+    /// wallet nor the user wallets open them yet.
+    pub async fn offer_channel(
+        &self,
+        contract_input: &ContractInput,
+        counter_party: PublicKey,
+        refund_delay: u32,
+    ) -> Result<OfferChannel, Error> {
+        let oracle_announcements = self.get_oracle_announcements(contract_input).await?;
+
+        let (offered_channel, offered_contract) = channel_updater::offer_channel(
+            &self.secp,
+            contract_input,
+            &counter_party,
+            &oracle_announcements,
+            CET_NSEQUENCE,
+            refund_delay,
+            &self.wallet,
+            &self.blockchain,
+            &self.time,
+        )?;
+
+        let msg = offered_channel.get_offer_channel_msg(&offered_contract);
+
+        self.create_contract(&offered_contract).await?;
+        self.store
+            .upsert_channel(&Channel::Offered(offered_channel))
+            .await?;
+
+        Ok(msg)
+    }
+}
+
+impl<W: Deref, B: Deref, S: Deref, O: Deref, T: Deref, R, C> Manager<W, B, S, O, T, Acceptor, R, C>
+where
+    W::Target: Wallet + ReservingWallet,
+    B::Target: Blockchain + AsyncBlockchain,
+    S::Target: AsyncStorage,
+    O::Target: AsyncOracle,
+    T::Target: Time,
+    R: OracleResolver<O>,
+    C: AsyncClock,
+{
     /// Function to call to accept a DLC for which an offer was received.
     pub async fn accept_contract_offer(
         &self,
         contract_id: &ContractId,
     ) -> Result<(ContractId, PublicKey, AcceptDlc), Error> {
+        self.accept_offer(contract_id, None).await
+    }
+
+    /// Accept a DLC for which an offer was received, refusing it unless its
+    /// funding transaction pays exactly the protocol fee charged for
+    /// `protocol_fee_basis_points`, as given by [`get_charged_protocol_fee`].
+    /// The fee is only known once the funding transaction is built, and the
+    /// inputs the wallet selected for it are released if the offer is refused.
+    pub async fn accept_contract_offer_with_protocol_fee(
+        &self,
+        contract_id: &ContractId,
+        protocol_fee_basis_points: u64,
+    ) -> Result<(ContractId, PublicKey, AcceptDlc), Error> {
+        self.accept_offer(contract_id, Some(protocol_fee_basis_points))
+            .await
+    }
+
+    async fn accept_offer(
+        &self,
+        contract_id: &ContractId,
+        protocol_fee_basis_points: Option<u64>,
+    ) -> Result<(ContractId, PublicKey, AcceptDlc), Error> {
+        let _lock = self.lock_contract(contract_id)?;
         let offered_contract =
             get_contract_in_state!(self, contract_id, Offered, None as Option<PublicKey>)?;
+        // Checked again as the policy may have changed since the offer was
+        // received, before the wallet reserves funding inputs for it.
+        self.config.accept_policy.check_offer(&offered_contract)?;
 
         let counter_party = offered_contract.counter_party;
 
@@ -324,305 +810,2281 @@ where
             &self.wallet,
             &self.blockchain,
         )?;
+        let contract_id = accepted_contract.get_contract_id();
+        let own_outpoints = get_own_inputs(
+            &accepted_contract.offered_contract,
+            Some(&accepted_contract.accept_params),
+        );
+
+        if let Err(e) = self
+            .store_accepted_contract(accepted_contract, protocol_fee_basis_points)
+            .await
+        {
+            if let Err(unreserve_error) = self.wallet.unreserve_utxos(&own_outpoints) {
+                warn!(
+                    "Error releasing the funding inputs of refused contract {:02x?}: {}",
+                    contract_id, unreserve_error
+                );
+            }
+            return Err(e);
+        }
+
+        Ok((contract_id, counter_party, accept_msg))
+    }
+
+    /// Checks the protocol fee paid by the funding transaction of a contract
+    /// we just accepted, then stores it. The fee is only known once the
+    /// funding transaction is built.
+    async fn store_accepted_contract(
+        &self,
+        accepted_contract: AcceptedContract,
+        protocol_fee_basis_points: Option<u64>,
+    ) -> Result<(), Error> {
+        let protocol_fee = get_paid_protocol_fee(&accepted_contract);
+        if let Some(basis_points) = protocol_fee_basis_points {
+            let charged_fee = get_charged_protocol_fee(
+                accepted_contract.offered_contract.total_collateral,
+                basis_points,
+            )?;
+            if protocol_fee != charged_fee {
+                return Err(Error::InvalidParameters(format!(
+                    "Protocol fee of {} sats does not match the {} sats charged for {} basis points",
+                    protocol_fee, charged_fee, basis_points
+                )));
+            }
+        }
+        self.config
+            .accept_policy
+            .check_protocol_fee(&accepted_contract)?;
+        info!(
+            "Accepting contract {} with a protocol fee of {} sats",
+            accepted_contract.get_contract_id_string(),
+            protocol_fee
+        );
 
         self.wallet.import_address(&Address::p2wsh(
             &accepted_contract.dlc_transactions.funding_script_pubkey,
             self.blockchain.get_network_async().await?,
         ))?;
 
-        let contract_id = accepted_contract.get_contract_id();
+        self.update_contract(
+            ContractState::Offered,
+            &Contract::Accepted(accepted_contract),
+        )
+        .await
+    }
 
-        self.store
-            .update_contract(&Contract::Accepted(accepted_contract))
-            .await?;
+    /// Function to call to reject an offer received from the counter party.
+    pub async fn reject_offer(&self, contract_id: &ContractId) -> Result<(), Error> {
+        let _lock = self.lock_contract(contract_id)?;
+        let offered_contract =
+            get_contract_in_state!(self, contract_id, Offered, None as Option<PublicKey>)?;
+        if offered_contract.is_offer_party {
+            return Err(Error::InvalidState(
+                "Cannot reject an offer we made, abandon it instead.".to_string(),
+            ));
+        }
 
-        Ok((contract_id, counter_party, accept_msg))
+        self.update_contract(
+            ContractState::Offered,
+            &Contract::Rejected(offered_contract),
+        )
+        .await
     }
 
-    /// Function to call to check the state of the currently executing DLCs and
-    /// update them if possible.
-    pub async fn periodic_check(&self) -> Result<Vec<(ContractId, String)>, Error> {
-        let mut affected_contracts = Vec::<(ContractId, String)>::new();
-        affected_contracts.extend_from_slice(&self.check_signed_contracts().await?);
-        affected_contracts.extend_from_slice(&self.check_confirmed_contracts().await?);
-        affected_contracts.extend_from_slice(&self.check_preclosed_contracts().await?);
+    /// Function to call to verify again the sign message of a contract that
+    /// failed with a transient error, such as a locked wallet. The
+    /// funding transaction is broadcast if it succeeds.
+    #[instrument(skip_all, fields(contract_id = field::Empty, event_id = field::Empty))]
+    pub async fn retry_failed_sign(&self, contract_id: &ContractId) -> Result<(), Error> {
+        record_contract_id(contract_id);
+        let _lock = self.lock_contract(contract_id)?;
+        let failed_contract =
+            get_contract_in_state!(self, contract_id, FailedSign, None as Option<PublicKey>)?;
+        record_event_id(
+            &failed_contract
+                .accepted_contract
+                .offered_contract
+                .contract_info,
+        );
+        let temporary_id = failed_contract.accepted_contract.offered_contract.id;
+        self.check_retryable(&temporary_id).await?;
 
-        Ok(affected_contracts)
+        self.process_sign_message(
+            ContractState::FailedSign,
+            failed_contract.accepted_contract,
+            &failed_contract.sign_message,
+        )
+        .await?;
+        self.clear_failure(&temporary_id).await
+    }
+
+    /// Function to call to accept a DLC channel for which an offer was
+    /// received.
+    pub async fn accept_channel(
+        &self,
+        channel_id: &ChannelId,
+    ) -> Result<(AcceptChannel, ChannelId, ContractId, PublicKey), Error> {
+        let _lock = self.lock_contract(channel_id)?;
+        let offered_channel =
+            get_channel_in_state!(self, channel_id, Offered, None as Option<PublicKey>)?;
+
+        if offered_channel.is_offer_party {
+            return Err(Error::InvalidState(
+                "Cannot accept channel initiated by us.".to_string(),
+            ));
+        }
+
+        let offered_contract = get_contract_in_state!(
+            self,
+            &offered_channel.offered_contract_id,
+            Offered,
+            None as Option<PublicKey>
+        )?;
+
+        let (accepted_channel, accepted_contract, accept_channel) =
+            channel_updater::accept_channel_offer(
+                &self.secp,
+                &offered_channel,
+                &offered_contract,
+                &self.wallet,
+            )?;
+
+        self.wallet.import_address(&Address::p2wsh(
+            &accepted_contract.dlc_transactions.funding_script_pubkey,
+            self.blockchain.get_network_async().await?,
+        ))?;
+
+        let channel_id = accepted_channel.channel_id;
+        let contract_id = accepted_contract.get_contract_id();
+        let counter_party = accepted_contract.offered_contract.counter_party;
+
+        self.update_contract(
+            ContractState::Offered,
+            &Contract::Accepted(accepted_contract),
+        )
+        .await?;
+        self.store
+            .upsert_channel(&Channel::Accepted(accepted_channel))
+            .await?;
+
+        Ok((accept_channel, channel_id, contract_id, counter_party))
+    }
+}
+
+impl<W: Deref, B: Deref, S: Deref, O: Deref, T: Deref, Role: ManagerRole, R, C>
+    Manager<W, B, S, O, T, Role, R, C>
+where
+    W::Target: Wallet + ReservingWallet,
+    B::Target: Blockchain + AsyncBlockchain,
+    S::Target: AsyncStorage,
+    O::Target: AsyncOracle,
+    T::Target: Time,
+    R: OracleResolver<O>,
+    C: AsyncClock,
+{
+    /// Register an observer notified of every contract state transition.
+    pub fn add_observer(&mut self, observer: Box<dyn ContractObserver + Send + Sync>) {
+        self.observers.push(observer);
+    }
+
+    /// Get the confirmation and timing parameters of the Manager.
+    pub fn get_config(&self) -> &ManagerConfig {
+        &self.config
+    }
+
+    /// Get the side record kept for the contract with the given id.
+    pub async fn get_contract_metadata(
+        &self,
+        contract_id: &ContractId,
+    ) -> Result<Option<ContractMetadata>, Error> {
+        self.store.get_contract_metadata(contract_id).await
+    }
+
+    /// Get the store from the Manager to access contracts.
+    pub fn get_store(&self) -> &S {
+        &self.store
+    }
+
+    #[doc(hidden)]
+    pub fn get_mut_store(&self) -> &S {
+        &self.store
+    }
+
+    /// Handles a DlcMessage once its sender was authenticated, see
+    /// [`Manager::on_signed_dlc_message`].
+    #[instrument(
+        skip_all,
+        fields(contract_id = %format_message_id(msg), event_id = field::Empty)
+    )]
+    async fn on_dlc_message(
+        &self,
+        msg: &DlcMessage,
+        counter_party: PublicKey,
+    ) -> Result<Option<DlcMessage>, Error> {
+        if !is_sent_to(msg, Role::IS_OFFER_PARTY) {
+            return Err(Error::InvalidState(format!(
+                "Message cannot be handled by the {} party.",
+                if Role::IS_OFFER_PARTY {
+                    "offer"
+                } else {
+                    "accept"
+                }
+            )));
+        }
+        match msg {
+            DlcMessage::Offer(o) => {
+                self.on_offer_message(o, counter_party).await?;
+                Ok(None)
+            }
+            DlcMessage::Accept(a) => Ok(Some(self.on_accept_message(a, &counter_party).await?)),
+            DlcMessage::Sign(s) => {
+                self.on_sign_message(s, &counter_party).await?;
+                Ok(None)
+            }
+            DlcMessage::OfferChannel(o) => {
+                self.on_offer_channel(o, counter_party).await?;
+                Ok(None)
+            }
+            DlcMessage::AcceptChannel(a) => Ok(Some(DlcMessage::SignChannel(
+                self.on_accept_channel(a, &counter_party).await?,
+            ))),
+            DlcMessage::SignChannel(s) => {
+                self.on_sign_channel(s, &counter_party).await?;
+                Ok(None)
+            }
+            DlcMessage::SettleOffer(s) => {
+                self.on_settle_offer(s, &counter_party).await?;
+                Ok(None)
+            }
+            DlcMessage::SettleAccept(s) => Ok(Some(DlcMessage::SettleConfirm(
+                self.on_settle_accept(s, &counter_party).await?,
+            ))),
+            DlcMessage::SettleConfirm(s) => Ok(Some(DlcMessage::SettleFinalize(
+                self.on_settle_confirm(s, &counter_party).await?,
+            ))),
+            DlcMessage::SettleFinalize(s) => {
+                self.on_settle_finalize(s, &counter_party).await?;
+                Ok(None)
+            }
+            DlcMessage::RenewOffer(r) => {
+                self.on_renew_offer(r, &counter_party).await?;
+                Ok(None)
+            }
+            DlcMessage::RenewAccept(r) => Ok(Some(DlcMessage::RenewConfirm(
+                self.on_renew_accept(r, &counter_party).await?,
+            ))),
+            DlcMessage::RenewConfirm(r) => Ok(Some(DlcMessage::RenewFinalize(
+                self.on_renew_confirm(r, &counter_party).await?,
+            ))),
+            DlcMessage::RenewFinalize(r) => {
+                self.on_renew_finalize(r, &counter_party).await?;
+                Ok(None)
+            }
+            DlcMessage::CollaborativeCloseOffer(c) => {
+                self.on_collaborative_close_offer(c, &counter_party).await?;
+                Ok(None)
+            }
+            _ => Err(Error::InvalidState("Invalid message type.".to_string())),
+        }
+    }
+
+    /// Function called to pass a DlcMessage to the Manager along with the
+    /// counter party's signature of it. The message is only processed if the
+    /// signature proves it comes from `counter_party`, whose identity key the
+    /// contract or channel then has to be bound to. The returned message, if
+    /// any, is to be signed with [`sign_dlc_message`] before being sent back.
+    pub async fn on_signed_dlc_message(
+        &self,
+        msg: &DlcMessage,
+        counter_party: PublicKey,
+        signature: &Signature,
+    ) -> Result<Option<DlcMessage>, Error> {
+        verify_dlc_message(&self.secp, msg, signature, &counter_party)?;
+        self.on_dlc_message(msg, counter_party).await
+    }
+
+    /// Function to call to propose closing a confirmed contract cooperatively,
+    /// paying `offer_payout` to the offer party and the rest of the total
+    /// collateral to the accept party. The returned message is to be sent to
+    /// the counter party. An offer that was dropped can be made again, and
+    /// replaced by one paying the counter party at least as much: it keeps our
+    /// signatures of the earlier offers, so none of them may suit it better.
+    pub async fn offer_mutual_close(
+        &self,
+        contract_id: &ContractId,
+        offer_payout: u64,
+    ) -> Result<CloseOffer, Error> {
+        let _lock = self.lock_contract(contract_id)?;
+        let contract =
+            get_contract_in_state!(self, contract_id, Confirmed, None as Option<PublicKey>)?;
+        let mut metadata = self
+            .store
+            .get_contract_metadata(contract_id)
+            .await?
+            .unwrap_or_default();
+        if let Some(outstanding_offer) = &metadata.close_offer {
+            let outstanding_payout =
+                get_counter_payout(&contract.accepted_contract, outstanding_offer.offer_payout);
+            let counter_payout = get_counter_payout(&contract.accepted_contract, offer_payout);
+            if counter_payout < outstanding_payout {
+                return Err(Error::InvalidParameters(format!(
+                    "Close offer pays the counter party {} sats, less than the {} sats of the outstanding offer",
+                    counter_payout, outstanding_payout
+                )));
+            }
+        }
+
+        let close_tx = build_close_transaction(&contract, offer_payout)?;
+        let close_signature = self.get_close_signature(&contract, &close_tx)?;
+        let close_offer = CloseOffer {
+            contract_id: *contract_id,
+            offer_payout,
+            close_signature,
+        };
+
+        // Persisted before our signature is handed out.
+        metadata.close_offer = Some(close_offer.clone());
+        self.store
+            .upsert_contract_metadata(contract_id, &metadata)
+            .await?;
+
+        Ok(close_offer)
+    }
+
+    /// Function to call to accept a close offer received from the counter
+    /// party if it pays us at least `min_payout`. The close transaction is
+    /// broadcast and the returned message is to be sent back to the counter
+    /// party.
+    pub async fn on_close_offer(
+        &self,
+        close_offer: &CloseOffer,
+        counter_party: PublicKey,
+        min_payout: u64,
+    ) -> Result<CloseAccept, Error> {
+        let _lock = self.lock_contract(&close_offer.contract_id)?;
+        let contract = get_contract_in_state!(
+            self,
+            &close_offer.contract_id,
+            Confirmed,
+            Some(counter_party)
+        )?;
+
+        let mut close_tx = build_close_transaction(&contract, close_offer.offer_payout)?;
+        let own_payout = get_own_payout(&contract.accepted_contract, &close_tx);
+        if own_payout < min_payout {
+            return Err(Error::InvalidParameters(format!(
+                "Close offer pays {} sats, less than the expected {} sats",
+                own_payout, min_payout
+            )));
+        }
+        let (own_fund_pubkey, counter_fund_pubkey) = get_fund_pubkeys(&contract);
+        verify_close_signature(
+            &self.secp,
+            &contract,
+            &close_tx,
+            &close_offer.close_signature,
+            &counter_fund_pubkey,
+        )?;
+        let close_signature = self.get_close_signature(&contract, &close_tx)?;
+        finalize_close_transaction(
+            &contract,
+            &mut close_tx,
+            [
+                (own_fund_pubkey, close_signature),
+                (counter_fund_pubkey, close_offer.close_signature),
+            ],
+        )?;
+
+        self.close_contract_mutually(&contract, close_tx).await?;
+
+        Ok(CloseAccept {
+            close_offer: close_offer.clone(),
+            close_signature,
+        })
+    }
+
+    /// Function to call when the counter party accepted our close offer. The
+    /// close transaction is broadcast if the counter party has not done so
+    /// already.
+    pub async fn on_close_accept(
+        &self,
+        close_accept: &CloseAccept,
+        counter_party: PublicKey,
+    ) -> Result<(), Error> {
+        let close_offer = &close_accept.close_offer;
+        let _lock = self.lock_contract(&close_offer.contract_id)?;
+        let contract = get_contract_in_state!(
+            self,
+            &close_offer.contract_id,
+            Confirmed,
+            Some(counter_party)
+        )?;
+        let outstanding_offer = self
+            .store
+            .get_contract_metadata(&close_offer.contract_id)
+            .await?
+            .and_then(|metadata| metadata.close_offer);
+        if outstanding_offer.is_none() {
+            return Err(Error::InvalidParameters(
+                "No close offer was made for this contract.".to_string(),
+            ));
+        }
+
+        let mut close_tx = build_close_transaction(&contract, close_offer.offer_payout)?;
+        let (own_fund_pubkey, counter_fund_pubkey) = get_fund_pubkeys(&contract);
+        // Our own signature proves the echoed offer is one we made. It may be
+        // one we replaced since, which pays us at least as much.
+        verify_close_signature(
+            &self.secp,
+            &contract,
+            &close_tx,
+            &close_offer.close_signature,
+            &own_fund_pubkey,
+        )?;
+        verify_close_signature(
+            &self.secp,
+            &contract,
+            &close_tx,
+            &close_accept.close_signature,
+            &counter_fund_pubkey,
+        )?;
+        finalize_close_transaction(
+            &contract,
+            &mut close_tx,
+            [
+                (own_fund_pubkey, close_offer.close_signature),
+                (counter_fund_pubkey, close_accept.close_signature),
+            ],
+        )?;
+
+        self.close_contract_mutually(&contract, close_tx).await?;
+
+        Ok(())
+    }
+
+    /// Get what each party receives for every outcome of the contract with the
+    /// given id. The contract must have been accepted, as the CET fees depend
+    /// on the contract transactions.
+    pub async fn get_payout_table(&self, contract_id: &ContractId) -> Result<PayoutTable, Error> {
+        let contract = self
+            .store
+            .get_contract(contract_id)
+            .await?
+            .ok_or(Error::InvalidParameters("Unknown Contract id.".to_string()))?;
+        let accepted_contract =
+            get_accepted_contract(&contract).ok_or(Error::InvalidState(format!(
+                "No payout table for a contract in state {:?}",
+                ContractState::from(&contract)
+            )))?;
+        PayoutTable::new(accepted_contract)
+    }
+
+    /// Get what each party receives if the contract with the given id closes
+    /// with the given outcome of the given oracle event.
+    pub async fn simulate_payout(
+        &self,
+        contract_id: &ContractId,
+        event_id: &str,
+        outcome: &Outcome,
+    ) -> Result<PayoutEntry, Error> {
+        let payout_table = self.get_payout_table(contract_id).await?;
+        payout_table
+            .get_entry(event_id, outcome)
+            .cloned()
+            .ok_or(Error::InvalidParameters(format!(
+                "Outcome {:?} of event {} is not covered by the contract",
+                outcome, event_id
+            )))
+    }
+
+    /// Get our possible profit and loss over every open contract. Contracts
+    /// that can still close through any CET count the worst and best of them,
+    /// over every contract info, and of the refund, while PreClosed contracts
+    /// count their closing transaction.
+    pub async fn get_exposure(&self) -> Result<ExposureSummary, Error> {
+        let mut summary = ExposureSummary::default();
+        for contract in self.store.get_contracts().await? {
+            match &contract {
+                Contract::Signed(c) => {
+                    summary.add(ContractExposure::new(
+                        &c.accepted_contract,
+                        ContractState::Signed,
+                    )?);
+                }
+                Contract::Confirmed(c) => {
+                    summary.add(ContractExposure::new(
+                        &c.accepted_contract,
+                        ContractState::Confirmed,
+                    )?);
+                }
+                Contract::PreClosed(c) => {
+                    summary.add(ContractExposure::new_closing(
+                        &c.signed_contract.accepted_contract,
+                        &c.signed_cet,
+                    ));
+                }
+                _ => {}
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Function to call to propose settling a channel off chain, paying
+    /// `counter_payout` to the counter party and the rest of the channel
+    /// collateral to us. The contract of the channel is closed once the
+    /// settlement is finalized.
+    pub async fn settle_offer(
+        &self,
+        channel_id: &ChannelId,
+        counter_payout: u64,
+    ) -> Result<(SettleOffer, PublicKey), Error> {
+        let _lock = self.lock_contract(channel_id)?;
+        let mut signed_channel =
+            get_channel_in_state!(self, channel_id, Signed, None as Option<PublicKey>)?;
+
+        let msg = channel_updater::settle_channel_offer(
+            &self.secp,
+            &mut signed_channel,
+            counter_payout,
+            PEER_TIMEOUT,
+            &self.wallet,
+            &self.time,
+        )?;
+
+        let counter_party = signed_channel.counter_party;
+
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await?;
+
+        Ok((msg, counter_party))
+    }
+
+    /// Function to call to accept a settle offer received from the counter
+    /// party.
+    pub async fn accept_settle_offer(
+        &self,
+        channel_id: &ChannelId,
+    ) -> Result<(SettleAccept, PublicKey), Error> {
+        let _lock = self.lock_contract(channel_id)?;
+        let mut signed_channel =
+            get_channel_in_state!(self, channel_id, Signed, None as Option<PublicKey>)?;
+
+        let msg = channel_updater::settle_channel_accept(
+            &self.secp,
+            &mut signed_channel,
+            CET_NSEQUENCE,
+            0,
+            PEER_TIMEOUT,
+            &self.wallet,
+            &self.time,
+        )?;
+
+        let counter_party = signed_channel.counter_party;
+
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await?;
+
+        Ok((msg, counter_party))
+    }
+
+    /// Function to call to propose replacing the contract of a channel with a
+    /// new one, settling the current one with `counter_payout` paid to the
+    /// counter party. No funding transaction is needed, as the new contract
+    /// spends the channel's funding output. Once the renewal is finalized, the
+    /// renewed contract is closed and its side record points to its successor.
+    ///
+    /// Only contracts of a channel can be renewed: the funding transaction of
+    /// a plain contract can only spend wallet inputs, not the funding output
+    /// of the contract it would replace.
+    pub async fn renew_offer(
+        &self,
+        channel_id: &ChannelId,
+        counter_payout: u64,
+        contract_input: &ContractInput,
+        refund_delay: u32,
+    ) -> Result<(RenewOffer, PublicKey), Error> {
+        let _lock = self.lock_contract(channel_id)?;
+        let mut signed_channel =
+            get_channel_in_state!(self, channel_id, Signed, None as Option<PublicKey>)?;
+
+        let oracle_announcements = self.get_oracle_announcements(contract_input).await?;
+
+        let (msg, offered_contract) = channel_updater::renew_offer(
+            &self.secp,
+            &mut signed_channel,
+            contract_input,
+            oracle_announcements,
+            counter_payout,
+            refund_delay,
+            PEER_TIMEOUT,
+            CET_NSEQUENCE,
+            &self.wallet,
+            &self.time,
+        )?;
+
+        let counter_party = offered_contract.counter_party;
+
+        self.create_contract(&offered_contract).await?;
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await?;
+
+        Ok((msg, counter_party))
+    }
+
+    /// Get the contract that renewed the contract with the given id, following
+    /// successive renewals to the latest one. Returns `None` if the contract
+    /// was not renewed.
+    pub async fn get_renewed_contract(
+        &self,
+        contract_id: &ContractId,
+    ) -> Result<Option<Contract>, Error> {
+        let mut visited = HashSet::from([*contract_id]);
+        let mut latest = None;
+        let mut current = *contract_id;
+        while let Some(successor) = self
+            .store
+            .get_contract_metadata(&current)
+            .await?
+            .and_then(|metadata| metadata.successor)
+        {
+            if !visited.insert(successor) {
+                return Err(Error::InvalidState(
+                    "Contract renewals form a cycle".to_string(),
+                ));
+            }
+            latest = Some(successor);
+            current = successor;
+        }
+        match latest {
+            Some(id) => self.store.get_contract(&id).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Function to call to accept a renew offer received from the counter
+    /// party.
+    pub async fn accept_renew_offer(
+        &self,
+        channel_id: &ChannelId,
+    ) -> Result<(RenewAccept, PublicKey), Error> {
+        let _lock = self.lock_contract(channel_id)?;
+        let mut signed_channel =
+            get_channel_in_state!(self, channel_id, Signed, None as Option<PublicKey>)?;
+        let offered_contract_id = signed_channel.get_contract_id().ok_or(Error::InvalidState(
+            "Channel has no pending renew offer.".to_string(),
+        ))?;
+        let offered_contract = get_contract_in_state!(
+            self,
+            &offered_contract_id,
+            Offered,
+            None as Option<PublicKey>
+        )?;
+
+        let (accepted_contract, msg) = channel_updater::accept_channel_renewal(
+            &self.secp,
+            &mut signed_channel,
+            &offered_contract,
+            CET_NSEQUENCE,
+            PEER_TIMEOUT,
+            &self.wallet,
+            &self.time,
+        )?;
+
+        let counter_party = signed_channel.counter_party;
+
+        self.update_contract(
+            ContractState::Offered,
+            &Contract::Accepted(accepted_contract),
+        )
+        .await?;
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await?;
+
+        Ok((msg, counter_party))
+    }
+
+    /// Function to call to propose closing a channel cooperatively, paying
+    /// `counter_payout` to the counter party and the rest of the channel
+    /// collateral to us. The offer cannot be withdrawn once sent: if the close
+    /// transaction is not confirmed within [`PEER_TIMEOUT`], the periodic check
+    /// closes the channel unilaterally instead.
+    pub async fn offer_collaborative_close(
+        &self,
+        channel_id: &ChannelId,
+        counter_payout: u64,
+    ) -> Result<(CollaborativeCloseOffer, PublicKey), Error> {
+        let _lock = self.lock_contract(channel_id)?;
+        let mut signed_channel =
+            get_channel_in_state!(self, channel_id, Signed, None as Option<PublicKey>)?;
+
+        let (msg, _) = channel_updater::offer_collaborative_close(
+            &self.secp,
+            &mut signed_channel,
+            counter_payout,
+            &self.wallet,
+            &self.time,
+        )?;
+
+        let counter_party = signed_channel.counter_party;
+
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await?;
+
+        Ok((msg, counter_party))
+    }
+
+    /// Function to call to accept a collaborative close offer received from the
+    /// counter party. The close transaction is broadcast and the contract of
+    /// the channel, if any, is closed.
+    pub async fn accept_collaborative_close(&self, channel_id: &ChannelId) -> Result<(), Error> {
+        let _lock = self.lock_contract(channel_id)?;
+        let mut signed_channel =
+            get_channel_in_state!(self, channel_id, Signed, None as Option<PublicKey>)?;
+        // The payout offered to the counter party of the offer is ours.
+        let own_payout = match signed_channel.state {
+            SignedChannelState::CollaborativeCloseOffered { counter_payout, .. } => counter_payout,
+            _ => {
+                return Err(unexpected_channel_state(
+                    "CollaborativeCloseOffered",
+                    &signed_channel,
+                ))
+            }
+        };
+
+        let close_tx = channel_updater::accept_collaborative_close_offer(
+            &self.secp,
+            &mut signed_channel,
+            &self.wallet,
+        )?;
+
+        self.broadcast(&close_tx, TransactionType::ChannelClose)
+            .await?;
+
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await?;
+        self.close_channel_contracts(channel_id, None, own_payout)
+            .await
+    }
+
+    /// Function to call to close a channel unilaterally, giving up on any
+    /// update it is waiting on. The buffer transaction is broadcast if the
+    /// contract of the channel is attested, the settle transaction if the
+    /// channel is settled. In the former case, the periodic check broadcasts
+    /// the CET and closes the contract once the buffer transaction got
+    /// [`CET_NSEQUENCE`] confirmations.
+    ///
+    /// Channels whose contract is not attested can only be closed
+    /// cooperatively, see [`Self::offer_collaborative_close`].
+    pub async fn force_close_channel(&self, channel_id: &ChannelId) -> Result<(), Error> {
+        let _lock = self.lock_contract(channel_id)?;
+        let mut signed_channel =
+            get_channel_in_state!(self, channel_id, Signed, None as Option<PublicKey>)?;
+        if get_channel_timeout(&signed_channel.state).is_some() {
+            roll_back_channel(&mut signed_channel)?;
+        }
+
+        if !self
+            .force_close_channel_internal(&mut signed_channel)
+            .await?
+        {
+            return Err(Error::InvalidState(
+                "The contract of the channel is not attested yet.".to_string(),
+            ));
+        }
+
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await
+    }
+
+    /// Function to call to check the state of the currently executing DLCs and
+    /// update them if possible.
+    #[instrument(skip_all)]
+    pub async fn periodic_check(&self) -> Result<PeriodicCheckReport, Error> {
+        let mut report = PeriodicCheckReport::default();
+        self.check_signed_contracts(&mut report).await?;
+        self.check_confirmed_contracts(&mut report).await?;
+        self.check_preclosed_contracts(&mut report).await?;
+        self.check_channels(&mut report).await?;
+        self.check_stale_contracts(&mut report).await?;
+
+        Ok(report)
+    }
+
+    /// Fetch the announcements of the oracles of each contract info of the
+    /// input, after validating it.
+    async fn get_oracle_announcements(
+        &self,
+        contract_input: &ContractInput,
+    ) -> Result<Vec<Vec<OracleAnnouncement>>, Error> {
+        let manager_oracles = &self.oracles;
+        contract_input.validate()?;
+
+        if contract_input.contract_infos.is_empty() {
+            return Err(Error::InvalidParameters(
+                "Contract Input Info missing".to_string(),
+            ));
+        }
+        debug!(
+            "manager_oracles keys {:?}",
+            manager_oracles
+                .keys()
+                .map(|k| k.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+        // Each contract info is built against the announcements of its own event.
+        let oracle_set: Vec<(&str, Vec<&O>)> = contract_input
+            .contract_infos
+            .iter()
+            .map(|x| {
+                debug!(
+                    "contract event id {}, public keys: {}",
+                    x.oracles.event_id,
+                    x.oracles
+                        .public_keys
+                        .iter()
+                        .map(|k| k.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                );
+                let oracles = x
+                    .oracles
+                    .public_keys
+                    .iter()
+                    .map(|pubkey| match manager_oracles.get(pubkey) {
+                        Some(x) => Ok(x),
+                        None => Err(Error::InvalidParameters(
+                            "Unknown oracle public key".to_string(),
+                        )),
+                    })
+                    .collect::<Result<Vec<&O>, Error>>()?;
+                Ok((x.oracles.event_id.as_str(), oracles))
+            })
+            .collect::<Result<Vec<(&str, Vec<&O>)>, Error>>()?;
+
+        let mut oracle_announcements = Vec::new();
+
+        for (event_id, oracles) in oracle_set {
+            let mut announcements = Vec::new();
+            for oracle in oracles {
+                let announcement = oracle.get_announcement(event_id).await?;
+                if announcement.oracle_event.event_id != event_id {
+                    return Err(Error::OracleError(format!(
+                        "Oracle returned announcement for event {} instead of {}",
+                        announcement.oracle_event.event_id, event_id
+                    )));
+                }
+                announcements.push(announcement);
+            }
+            oracle_announcements.push(announcements)
+        }
+
+        Ok(oracle_announcements)
     }
 
     async fn on_offer_message(
         &self,
-        offered_message: &OfferDlc,
+        offered_message: &OfferDlc,
+        counter_party: PublicKey,
+    ) -> Result<(), Error> {
+        offered_message.validate(
+            &self.secp,
+            self.config.min_refund_delay,
+            self.config.max_refund_delay,
+        )?;
+        let contract: OfferedContract =
+            OfferedContract::try_from_offer_dlc(offered_message, counter_party)?;
+        record_event_id(&contract.contract_info);
+        contract.validate()?;
+        self.config.accept_policy.check_offer(&contract)?;
+
+        if self.store.get_contract(&contract.id).await?.is_some() {
+            return Err(Error::InvalidParameters(
+                "Contract with identical id already exists".to_string(),
+            ));
+        }
+
+        self.create_contract(&contract).await?;
+
+        Ok(())
+    }
+
+    async fn on_accept_message(
+        &self,
+        accept_msg: &AcceptDlc,
+        counter_party: &PublicKey,
+    ) -> Result<DlcMessage, Error> {
+        let _lock = self.lock_contract(&accept_msg.temporary_contract_id)?;
+        let offered_contract = get_contract_in_state!(
+            self,
+            &accept_msg.temporary_contract_id,
+            Offered,
+            Some(*counter_party)
+        )?;
+        record_event_id(&offered_contract.contract_info);
+
+        let sign_msg = self
+            .process_accept_message(ContractState::Offered, offered_contract, accept_msg)
+            .await?;
+
+        Ok(DlcMessage::Sign(sign_msg))
+    }
+
+    /// Verify the accept message of our offer and sign the contract, or move it
+    /// to the FailedAccept state.
+    async fn process_accept_message(
+        &self,
+        previous_state: ContractState,
+        offered_contract: OfferedContract,
+        accept_msg: &AcceptDlc,
+    ) -> Result<SignDlc, Error> {
+        let (signed_contract, signed_msg) = match verify_accepted_and_sign_contract(
+            &self.secp,
+            &offered_contract,
+            accept_msg,
+            &self.wallet,
+        ) {
+            Ok(contract) => contract,
+            Err(e) => {
+                return self
+                    .accept_fail_on_error(previous_state, offered_contract, accept_msg.clone(), e)
+                    .await
+            }
+        };
+
+        self.wallet.import_address(&Address::p2wsh(
+            &signed_contract
+                .accepted_contract
+                .dlc_transactions
+                .funding_script_pubkey,
+            self.blockchain.get_network_async().await?,
+        ))?;
+
+        self.update_contract(previous_state, &Contract::Signed(signed_contract))
+            .await?;
+
+        Ok(signed_msg)
+    }
+
+    async fn on_sign_message(
+        &self,
+        sign_message: &SignDlc,
+        peer_id: &PublicKey,
+    ) -> Result<(), Error> {
+        let _lock = self.lock_contract(&sign_message.contract_id)?;
+        let accepted_contract =
+            get_contract_in_state!(self, &sign_message.contract_id, Accepted, Some(*peer_id))?;
+        record_event_id(&accepted_contract.offered_contract.contract_info);
+
+        self.process_sign_message(ContractState::Accepted, accepted_contract, sign_message)
+            .await
+    }
+
+    /// Verify the sign message of the contract we accepted and broadcast its
+    /// funding transaction, or move it to the FailedSign state.
+    async fn process_sign_message(
+        &self,
+        previous_state: ContractState,
+        accepted_contract: AcceptedContract,
+        sign_message: &SignDlc,
+    ) -> Result<(), Error> {
+        let (signed_contract, fund_tx) =
+            match crate::dlc_manager::contract_updater::verify_signed_contract(
+                &self.secp,
+                &accepted_contract,
+                sign_message,
+                &self.wallet,
+            ) {
+                Ok(contract) => contract,
+                Err(e) => {
+                    return self
+                        .sign_fail_on_error(
+                            previous_state,
+                            accepted_contract,
+                            sign_message.clone(),
+                            e,
+                        )
+                        .await
+                }
+            };
+
+        let contract_id = signed_contract.accepted_contract.get_contract_id();
+        self.update_contract(previous_state, &Contract::Signed(signed_contract))
+            .await?;
+
+        let mut metadata = self
+            .store
+            .get_contract_metadata(&contract_id)
+            .await?
+            .unwrap_or_default();
+        metadata.signed_fund_tx = Some(fund_tx.clone());
+        self.store
+            .upsert_contract_metadata(&contract_id, &metadata)
+            .await?;
+
+        self.broadcast(&fund_tx, TransactionType::Fund).await?;
+
+        Ok(())
+    }
+
+    async fn on_offer_channel(
+        &self,
+        offer_channel: &OfferChannel,
         counter_party: PublicKey,
     ) -> Result<(), Error> {
-        offered_message.validate(&self.secp, 0, FIFTY_YEARS)?;
-        let contract: OfferedContract =
-            OfferedContract::try_from_offer_dlc(offered_message, counter_party)?;
+        offer_channel.validate(
+            &self.secp,
+            self.config.min_refund_delay,
+            self.config.max_refund_delay,
+            CET_NSEQUENCE,
+            CET_NSEQUENCE * 10,
+        )?;
+
+        let (channel, contract) = OfferedChannel::from_offer_channel(offer_channel, counter_party)?;
         contract.validate()?;
+        self.config.accept_policy.check_offer(&contract)?;
 
-        if self.store.get_contract(&contract.id).await?.is_some() {
+        if self
+            .store
+            .get_channel(&channel.temporary_channel_id)
+            .await?
+            .is_some()
+        {
             return Err(Error::InvalidParameters(
-                "Contract with identical id already exists".to_string(),
+                "Channel with identical id already exists".to_string(),
             ));
         }
 
-        self.store.create_contract(&contract).await?;
+        self.create_contract(&contract).await?;
+        self.store
+            .upsert_channel(&Channel::Offered(channel))
+            .await?;
 
         Ok(())
     }
 
-    async fn on_accept_message(
+    async fn on_accept_channel(
         &self,
-        accept_msg: &AcceptDlc,
+        accept_channel: &AcceptChannel,
         counter_party: &PublicKey,
-    ) -> Result<DlcMessage, Error> {
+    ) -> Result<SignChannel, Error> {
+        let _lock = self.lock_contract(&accept_channel.temporary_channel_id)?;
+        let offered_channel = get_channel_in_state!(
+            self,
+            &accept_channel.temporary_channel_id,
+            Offered,
+            Some(*counter_party)
+        )?;
         let offered_contract = get_contract_in_state!(
             self,
-            &accept_msg.temporary_contract_id,
-            Offered,
+            &offered_channel.offered_contract_id,
+            Offered,
+            Some(*counter_party)
+        )?;
+
+        let (signed_channel, signed_contract, sign_channel) =
+            channel_updater::verify_and_sign_accepted_channel(
+                &self.secp,
+                &offered_channel,
+                &offered_contract,
+                accept_channel,
+                CET_NSEQUENCE,
+                &self.wallet,
+            )?;
+
+        self.wallet.import_address(&Address::p2wsh(
+            &signed_contract
+                .accepted_contract
+                .dlc_transactions
+                .funding_script_pubkey,
+            self.blockchain.get_network_async().await?,
+        ))?;
+
+        self.update_contract(ContractState::Offered, &Contract::Signed(signed_contract))
+            .await?;
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await?;
+
+        Ok(sign_channel)
+    }
+
+    async fn on_sign_channel(
+        &self,
+        sign_channel: &SignChannel,
+        counter_party: &PublicKey,
+    ) -> Result<(), Error> {
+        let _lock = self.lock_contract(&sign_channel.channel_id)?;
+        let accepted_channel = get_channel_in_state!(
+            self,
+            &sign_channel.channel_id,
+            Accepted,
+            Some(*counter_party)
+        )?;
+        let accepted_contract = get_contract_in_state!(
+            self,
+            &accepted_channel.accepted_contract_id,
+            Accepted,
+            Some(*counter_party)
+        )?;
+
+        let (signed_channel, signed_contract, fund_tx) = channel_updater::verify_signed_channel(
+            &self.secp,
+            &accepted_channel,
+            &accepted_contract,
+            sign_channel,
+            &self.wallet,
+        )?;
+
+        let contract_id = signed_contract.accepted_contract.get_contract_id();
+        self.update_contract(ContractState::Accepted, &Contract::Signed(signed_contract))
+            .await?;
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await?;
+
+        let mut metadata = self
+            .store
+            .get_contract_metadata(&contract_id)
+            .await?
+            .unwrap_or_default();
+        metadata.signed_fund_tx = Some(fund_tx.clone());
+        self.store
+            .upsert_contract_metadata(&contract_id, &metadata)
+            .await?;
+
+        self.broadcast(&fund_tx, TransactionType::Fund).await?;
+
+        Ok(())
+    }
+
+    async fn on_settle_offer(
+        &self,
+        settle_offer: &SettleOffer,
+        counter_party: &PublicKey,
+    ) -> Result<(), Error> {
+        let _lock = self.lock_contract(&settle_offer.channel_id)?;
+        let mut signed_channel =
+            get_channel_in_state!(self, &settle_offer.channel_id, Signed, Some(*counter_party))?;
+
+        channel_updater::on_settle_offer(&mut signed_channel, settle_offer)?;
+
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await
+    }
+
+    async fn on_settle_accept(
+        &self,
+        settle_accept: &SettleAccept,
+        counter_party: &PublicKey,
+    ) -> Result<SettleConfirm, Error> {
+        let _lock = self.lock_contract(&settle_accept.channel_id)?;
+        let mut signed_channel = get_channel_in_state!(
+            self,
+            &settle_accept.channel_id,
+            Signed,
+            Some(*counter_party)
+        )?;
+
+        let msg = channel_updater::settle_channel_confirm(
+            &self.secp,
+            &mut signed_channel,
+            settle_accept,
+            CET_NSEQUENCE,
+            0,
+            PEER_TIMEOUT,
+            &self.wallet,
+            &self.time,
+        )?;
+
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await?;
+
+        Ok(msg)
+    }
+
+    async fn on_settle_confirm(
+        &self,
+        settle_confirm: &SettleConfirm,
+        counter_party: &PublicKey,
+    ) -> Result<SettleFinalize, Error> {
+        let _lock = self.lock_contract(&settle_confirm.channel_id)?;
+        let mut signed_channel = get_channel_in_state!(
+            self,
+            &settle_confirm.channel_id,
+            Signed,
+            Some(*counter_party)
+        )?;
+        let own_payout = match signed_channel.state {
+            SignedChannelState::SettledAccepted { own_payout, .. } => own_payout,
+            _ => return Err(unexpected_channel_state("SettledAccepted", &signed_channel)),
+        };
+
+        let msg = channel_updater::settle_channel_finalize(
+            &self.secp,
+            &mut signed_channel,
+            settle_confirm,
+            &self.wallet,
+        )?;
+
+        let channel_id = signed_channel.channel_id;
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await?;
+        self.close_channel_contracts(&channel_id, None, own_payout)
+            .await?;
+
+        Ok(msg)
+    }
+
+    async fn on_settle_finalize(
+        &self,
+        settle_finalize: &SettleFinalize,
+        counter_party: &PublicKey,
+    ) -> Result<(), Error> {
+        let _lock = self.lock_contract(&settle_finalize.channel_id)?;
+        let mut signed_channel = get_channel_in_state!(
+            self,
+            &settle_finalize.channel_id,
+            Signed,
+            Some(*counter_party)
+        )?;
+        let own_payout = match signed_channel.state {
+            SignedChannelState::SettledConfirmed { own_payout, .. } => own_payout,
+            _ => {
+                return Err(unexpected_channel_state(
+                    "SettledConfirmed",
+                    &signed_channel,
+                ))
+            }
+        };
+
+        channel_updater::settle_channel_on_finalize(
+            &self.secp,
+            &mut signed_channel,
+            settle_finalize,
+        )?;
+
+        let channel_id = signed_channel.channel_id;
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await?;
+        self.close_channel_contracts(&channel_id, None, own_payout)
+            .await
+    }
+
+    async fn on_renew_offer(
+        &self,
+        renew_offer: &RenewOffer,
+        counter_party: &PublicKey,
+    ) -> Result<(), Error> {
+        let _lock = self.lock_contract(&renew_offer.channel_id)?;
+        let mut signed_channel =
+            get_channel_in_state!(self, &renew_offer.channel_id, Signed, Some(*counter_party))?;
+
+        let offered_contract = channel_updater::on_renew_offer(
+            &mut signed_channel,
+            renew_offer,
+            PEER_TIMEOUT,
+            &self.time,
+        )?;
+        offered_contract.validate()?;
+        self.config.accept_policy.check_offer(&offered_contract)?;
+
+        self.create_contract(&offered_contract).await?;
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await
+    }
+
+    async fn on_renew_accept(
+        &self,
+        renew_accept: &RenewAccept,
+        counter_party: &PublicKey,
+    ) -> Result<RenewConfirm, Error> {
+        let _lock = self.lock_contract(&renew_accept.channel_id)?;
+        let mut signed_channel =
+            get_channel_in_state!(self, &renew_accept.channel_id, Signed, Some(*counter_party))?;
+        let offered_contract_id = signed_channel.get_contract_id().ok_or(Error::InvalidState(
+            "Channel has no pending renew offer.".to_string(),
+        ))?;
+        let offered_contract =
+            get_contract_in_state!(self, &offered_contract_id, Offered, Some(*counter_party))?;
+
+        let (signed_contract, msg) = channel_updater::verify_renew_accept_and_confirm(
+            &self.secp,
+            renew_accept,
+            &mut signed_channel,
+            &offered_contract,
+            CET_NSEQUENCE,
+            PEER_TIMEOUT,
+            &self.wallet,
+            &self.time,
+        )?;
+
+        // The channel funding output is already confirmed, so is the new
+        // contract spending it.
+        self.update_contract(
+            ContractState::Offered,
+            &Contract::Confirmed(signed_contract),
+        )
+        .await?;
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await?;
+
+        Ok(msg)
+    }
+
+    async fn on_renew_confirm(
+        &self,
+        renew_confirm: &RenewConfirm,
+        counter_party: &PublicKey,
+    ) -> Result<RenewFinalize, Error> {
+        let _lock = self.lock_contract(&renew_confirm.channel_id)?;
+        let mut signed_channel = get_channel_in_state!(
+            self,
+            &renew_confirm.channel_id,
+            Signed,
+            Some(*counter_party)
+        )?;
+        let (contract_id, own_payout) = match signed_channel.state {
+            SignedChannelState::RenewAccepted {
+                contract_id,
+                own_payout,
+                ..
+            } => (contract_id, own_payout),
+            _ => return Err(unexpected_channel_state("RenewAccepted", &signed_channel)),
+        };
+        let accepted_contract =
+            get_contract_in_state!(self, &contract_id, Accepted, Some(*counter_party))?;
+
+        let (signed_contract, msg) = channel_updater::verify_renew_confirm_and_finalize(
+            &self.secp,
+            &mut signed_channel,
+            &accepted_contract,
+            renew_confirm,
+            PEER_TIMEOUT,
+            &self.time,
+            &self.wallet,
+        )?;
+
+        let channel_id = signed_channel.channel_id;
+        self.update_contract(
+            ContractState::Accepted,
+            &Contract::Confirmed(signed_contract),
+        )
+        .await?;
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await?;
+        self.close_channel_contracts(&channel_id, Some(contract_id), own_payout)
+            .await?;
+
+        Ok(msg)
+    }
+
+    async fn on_renew_finalize(
+        &self,
+        renew_finalize: &RenewFinalize,
+        counter_party: &PublicKey,
+    ) -> Result<(), Error> {
+        let _lock = self.lock_contract(&renew_finalize.channel_id)?;
+        let mut signed_channel = get_channel_in_state!(
+            self,
+            &renew_finalize.channel_id,
+            Signed,
             Some(*counter_party)
         )?;
-
-        let (signed_contract, signed_msg) = match verify_accepted_and_sign_contract(
-            &self.secp,
-            &offered_contract,
-            accept_msg,
-            &self.wallet,
-        ) {
-            Ok(contract) => contract,
-            Err(e) => {
-                return self
-                    .accept_fail_on_error(offered_contract, accept_msg.clone(), e)
-                    .await
-            }
+        let (contract_id, own_payout) = match signed_channel.state {
+            SignedChannelState::RenewConfirmed {
+                contract_id,
+                own_payout,
+                ..
+            } => (contract_id, own_payout),
+            _ => return Err(unexpected_channel_state("RenewConfirmed", &signed_channel)),
         };
 
-        self.wallet.import_address(&Address::p2wsh(
-            &signed_contract
-                .accepted_contract
-                .dlc_transactions
-                .funding_script_pubkey,
-            self.blockchain.get_network_async().await?,
-        ))?;
+        channel_updater::renew_channel_on_finalize(&mut signed_channel, renew_finalize)?;
 
+        let channel_id = signed_channel.channel_id;
         self.store
-            .update_contract(&Contract::Signed(signed_contract))
+            .upsert_channel(&Channel::Signed(signed_channel))
             .await?;
-
-        Ok(DlcMessage::Sign(signed_msg))
+        self.close_channel_contracts(&channel_id, Some(contract_id), own_payout)
+            .await
     }
 
-    async fn on_sign_message(
+    async fn on_collaborative_close_offer(
         &self,
-        sign_message: &SignDlc,
-        peer_id: &PublicKey,
+        close_offer: &CollaborativeCloseOffer,
+        counter_party: &PublicKey,
     ) -> Result<(), Error> {
-        let accepted_contract =
-            get_contract_in_state!(self, &sign_message.contract_id, Accepted, Some(*peer_id))?;
+        let _lock = self.lock_contract(&close_offer.channel_id)?;
+        let mut signed_channel =
+            get_channel_in_state!(self, &close_offer.channel_id, Signed, Some(*counter_party))?;
+
+        channel_updater::on_collaborative_close_offer(
+            &mut signed_channel,
+            close_offer,
+            PEER_TIMEOUT,
+            &self.time,
+        )?;
 
-        let (signed_contract, fund_tx) =
-            match crate::dlc_manager::contract_updater::verify_signed_contract(
-                &self.secp,
-                &accepted_contract,
-                sign_message,
-                &self.wallet,
-            ) {
-                Ok(contract) => contract,
-                Err(e) => {
-                    return self
-                        .sign_fail_on_error(accepted_contract, sign_message.clone(), e)
-                        .await
-                }
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await
+    }
+
+    /// Broadcast the transaction closing the channel unilaterally: the buffer
+    /// transaction of its contract once attested, moving the channel to the
+    /// closing state, or its settle transaction. Returns `false`, leaving the
+    /// channel untouched, if its contract is not confirmed or not attested
+    /// yet. The caller persists the channel.
+    async fn force_close_channel_internal(
+        &self,
+        signed_channel: &mut SignedChannel,
+    ) -> Result<bool, Error> {
+        match signed_channel.state {
+            SignedChannelState::Established {
+                signed_contract_id, ..
+            } => {
+                let contract = match self.store.get_contract(&signed_contract_id).await? {
+                    Some(Contract::Confirmed(c)) => c,
+                    _ => return Ok(false),
+                };
+                let (contract_info, adaptor_info, attestations) =
+                    match self.get_closable_contract_info(&contract).await?.0 {
+                        Some(info) => info,
+                        None => return Ok(false),
+                    };
+                channel_updater::initiate_unilateral_close_established_channel(
+                    &self.secp,
+                    signed_channel,
+                    &contract,
+                    contract_info,
+                    &attestations,
+                    adaptor_info,
+                    &self.wallet,
+                )?;
+                let buffer_transaction = match &signed_channel.state {
+                    SignedChannelState::Closing {
+                        buffer_transaction, ..
+                    } => buffer_transaction,
+                    _ => return Err(unexpected_channel_state("Closing", signed_channel)),
+                };
+                self.broadcast(buffer_transaction, TransactionType::Buffer)
+                    .await?;
+            }
+            SignedChannelState::Settled { .. } => {
+                let settle_tx = channel_updater::initiate_unilateral_close_settled_channel(
+                    &self.secp,
+                    signed_channel,
+                    &self.wallet,
+                )?;
+                self.broadcast(&settle_tx, TransactionType::Settle).await?;
+            }
+            _ => {
+                return Err(unexpected_channel_state(
+                    "Established or Settled",
+                    signed_channel,
+                ))
+            }
+        }
+        info!("Force closed channel {:02x?}", signed_channel.channel_id);
+        Ok(true)
+    }
+
+    /// Close the confirmed contracts of a channel other than `successor` once
+    /// the channel got settled or renewed, `own_payout` being what we were
+    /// left with in the channel. The closed contracts are linked to the
+    /// contract renewing them, if any.
+    async fn close_channel_contracts(
+        &self,
+        channel_id: &ChannelId,
+        successor: Option<ContractId>,
+        own_payout: u64,
+    ) -> Result<(), Error> {
+        let contracts = self
+            .store
+            .get_confirmed_contracts()
+            .await?
+            .into_iter()
+            .filter(|c| {
+                c.channel_id == Some(*channel_id)
+                    && Some(c.accepted_contract.get_contract_id()) != successor
+            });
+        for contract in contracts {
+            let accepted_contract = &contract.accepted_contract;
+            let offered_contract = &accepted_contract.offered_contract;
+            let own_collateral = if offered_contract.is_offer_party {
+                offered_contract.offer_params.collateral
+            } else {
+                accepted_contract.accept_params.collateral
             };
+            let closed_contract = Contract::Closed(ClosedContract {
+                attestations: None,
+                pnl: own_payout as i64 - own_collateral as i64,
+                signed_cet: None,
+                contract_id: accepted_contract.get_contract_id(),
+                temporary_contract_id: offered_contract.id,
+                counter_party_id: offered_contract.counter_party,
+            });
+            self.update_contract(ContractState::Confirmed, &closed_contract)
+                .await?;
+            if let Some(successor) = successor {
+                self.link_renewal(&closed_contract.get_id(), &successor)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
 
+    /// Record that `successor` replaced `predecessor` in both their side
+    /// records.
+    async fn link_renewal(
+        &self,
+        predecessor: &ContractId,
+        successor: &ContractId,
+    ) -> Result<(), Error> {
+        let mut metadata = self
+            .store
+            .get_contract_metadata(predecessor)
+            .await?
+            .unwrap_or_default();
+        metadata.successor = Some(*successor);
         self.store
-            .update_contract(&Contract::Signed(signed_contract))
+            .upsert_contract_metadata(predecessor, &metadata)
             .await?;
 
-        self.blockchain.send_transaction_async(&fund_tx).await?;
-
-        Ok(())
+        let mut metadata = self
+            .store
+            .get_contract_metadata(successor)
+            .await?
+            .unwrap_or_default();
+        metadata.predecessor = Some(*predecessor);
+        self.store
+            .upsert_contract_metadata(successor, &metadata)
+            .await
     }
 
     async fn sign_fail_on_error<R>(
         &self,
+        previous_state: ContractState,
         accepted_contract: AcceptedContract,
         sign_message: SignDlc,
         e: Error,
     ) -> Result<R, Error> {
         error!("Error in on_sign {}", e);
-        self.store
-            .update_contract(&Contract::FailedSign(FailedSignContract {
+        let temporary_id = accepted_contract.offered_contract.id;
+        self.update_contract(
+            previous_state,
+            &Contract::FailedSign(FailedSignContract {
                 accepted_contract,
                 sign_message,
                 error_message: e.to_string(),
-            }))
-            .await?;
+            }),
+        )
+        .await?;
+        self.record_failure(&temporary_id, &e).await?;
         Err(e)
     }
 
     async fn accept_fail_on_error<R>(
         &self,
+        previous_state: ContractState,
         offered_contract: OfferedContract,
         accept_message: AcceptDlc,
         e: Error,
     ) -> Result<R, Error> {
         error!("Error in on_accept {}", e);
-        self.store
-            .update_contract(&Contract::FailedAccept(FailedAcceptContract {
+        let temporary_id = offered_contract.id;
+        self.update_contract(
+            previous_state,
+            &Contract::FailedAccept(FailedAcceptContract {
                 offered_contract,
                 accept_message,
                 error_message: e.to_string(),
-            }))
-            .await?;
+            }),
+        )
+        .await?;
+        self.record_failure(&temporary_id, &e).await?;
         Err(e)
     }
 
-    async fn check_signed_contract(&self, contract: &SignedContract) -> Result<bool, Error> {
+    /// Record in the side record of a failed contract whether its error can be
+    /// retried.
+    async fn record_failure(&self, temporary_id: &ContractId, error: &Error) -> Result<(), Error> {
+        let mut metadata = self
+            .store
+            .get_contract_metadata(temporary_id)
+            .await?
+            .unwrap_or_default();
+        let attempts = metadata.failure.as_ref().map_or(0, |f| f.attempts);
+        metadata.failure = Some(ContractFailure {
+            kind: FailureKind::classify(error),
+            failed_at: self.time.unix_time_now(),
+            attempts: attempts + 1,
+        });
+        self.store
+            .upsert_contract_metadata(temporary_id, &metadata)
+            .await
+    }
+
+    /// Fails unless the latest failure of the contract was classified as
+    /// transient.
+    async fn check_retryable(&self, temporary_id: &ContractId) -> Result<(), Error> {
+        let failure = self
+            .store
+            .get_contract_metadata(temporary_id)
+            .await?
+            .and_then(|metadata| metadata.failure);
+        match failure {
+            Some(failure) if failure.kind == FailureKind::Transient => Ok(()),
+            Some(_) => Err(Error::InvalidState(
+                "The contract failed with a permanent error and cannot be retried.".to_string(),
+            )),
+            None => Err(Error::InvalidState(
+                "The failure of the contract was not classified, it cannot be retried.".to_string(),
+            )),
+        }
+    }
+
+    /// Forget the failure of a contract that was retried successfully.
+    async fn clear_failure(&self, temporary_id: &ContractId) -> Result<(), Error> {
+        if let Some(mut metadata) = self.store.get_contract_metadata(temporary_id).await? {
+            metadata.failure = None;
+            self.store
+                .upsert_contract_metadata(temporary_id, &metadata)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn check_signed_contract(
+        &self,
+        contract: &SignedContract,
+    ) -> Result<CheckOutcome, Error> {
+        let fund_txid = contract.accepted_contract.dlc_transactions.fund.txid();
+        if !self
+            .blockchain
+            .is_transaction_known_async(&fund_txid)
+            .await?
+        {
+            let contract_id = contract.accepted_contract.get_contract_id();
+            // Only the party that broadcast the funding transaction holds all
+            // its signatures.
+            let signed_fund_tx = self
+                .store
+                .get_contract_metadata(&contract_id)
+                .await?
+                .and_then(|metadata| metadata.signed_fund_tx);
+            if let Some(fund_tx) = signed_fund_tx {
+                self.rebroadcast(&contract_id, &fund_tx).await?;
+            }
+            return Ok(CheckOutcome::Unchanged);
+        }
         let confirmations = self
             .blockchain
-            .get_transaction_confirmations_async(
-                &contract.accepted_contract.dlc_transactions.fund.txid(),
+            .get_transaction_confirmations_async(&fund_txid)
+            .await?;
+        if confirmations == 0 {
+            let accepted_contract = &contract.accepted_contract;
+            // Our change output pays for the bump, if we have one.
+            self.bump_fee_if_stuck(
+                contract,
+                &accepted_contract.dlc_transactions.fund,
+                get_fund_fee(accepted_contract),
+                get_own_change_script(accepted_contract),
             )
             .await?;
-        if confirmations >= NB_CONFIRMATIONS {
-            self.store
-                .update_contract(&Contract::Confirmed(contract.clone()))
+        }
+        let required_confirmations = self.config.get_funding_confirmations(
+            contract.accepted_contract.offered_contract.total_collateral,
+        );
+        if confirmations >= required_confirmations {
+            let confirmed_contract = Contract::Confirmed(contract.clone());
+            self.update_contract(ContractState::Signed, &confirmed_contract)
                 .await?;
-            return Ok(true);
-        }
-        Ok(false)
-    }
-
-    async fn check_signed_contracts(&self) -> Result<Vec<(ContractId, String)>, Error> {
-        let mut contracts_to_confirm = Vec::new();
-        for c in self.store.get_signed_contracts().await? {
-            match self.check_signed_contract(&c).await {
-                Ok(true) => {
-                    let contract_id = c.accepted_contract.get_contract_id();
-                    let oracle_event_id = c
-                        .accepted_contract
-                        .offered_contract
-                        .contract_info
-                        .first()
-                        .and_then(|info| info.oracle_announcements.first())
-                        .map(|announcement| announcement.oracle_event.event_id.clone())
-                        .ok_or(Error::InvalidState("Missing oracle event ID".to_string()))?;
-
-                    contracts_to_confirm.push((contract_id, oracle_event_id));
+            return Ok(CheckOutcome::Updated(confirmed_contract));
+        }
+        Ok(CheckOutcome::Unchanged)
+    }
+
+    #[instrument(skip_all)]
+    async fn check_signed_contracts(&self, report: &mut PeriodicCheckReport) -> Result<(), Error> {
+        let contracts = self.store.get_signed_contracts().await?;
+        record_contract_count(ContractState::Signed, contracts.len());
+        let outcomes: Vec<_> = stream::iter(contracts)
+            .map(|c| {
+                let contract_id = c.accepted_contract.get_contract_id();
+                let span = contract_span(
+                    &contract_id,
+                    get_event_id(&c.accepted_contract.offered_contract.contract_info),
+                );
+                async move {
+                    let outcome = match self
+                        .lock_contract_in_state(&contract_id, ContractState::Signed)
+                        .await
+                    {
+                        Ok(Some((_lock, Contract::Signed(c)))) => {
+                            self.check_signed_contract(&c).await
+                        }
+                        Ok(_) => Ok(CheckOutcome::Unchanged),
+                        Err(e) => Err(e),
+                    };
+                    (c, outcome)
                 }
-                Ok(false) => (),
-                Err(e) => error!(
-                    "Error checking signed contract {}: {}",
-                    c.accepted_contract.get_contract_id_string(),
-                    e
-                ),
-            }
+                .instrument(span)
+            })
+            .buffer_unordered(self.config.max_concurrent_checks.max(1))
+            .collect()
+            .await;
+
+        for (c, outcome) in outcomes {
+            report.add_outcome(&c, ContractState::Signed, outcome);
         }
 
-        Ok(contracts_to_confirm)
+        Ok(())
     }
 
-    async fn check_confirmed_contracts(&self) -> Result<Vec<(ContractId, String)>, Error> {
-        let mut contracts_to_close = Vec::new();
-        for c in self.store.get_confirmed_contracts().await? {
-            // Confirmed contracts from channel are processed in channel specific methods.
-            if c.channel_id.is_some() {
-                continue;
+    #[instrument(skip_all)]
+    async fn check_confirmed_contracts(
+        &self,
+        report: &mut PeriodicCheckReport,
+    ) -> Result<(), Error> {
+        let contracts = self.store.get_confirmed_contracts().await?;
+        record_contract_count(ContractState::Confirmed, contracts.len());
+        // Confirmed contracts of a channel are closed through the channel, see
+        // `check_channels`.
+        let contracts: Vec<_> = contracts
+            .into_iter()
+            .filter(|c| c.channel_id.is_none())
+            .collect();
+        let outcomes: Vec<_> = stream::iter(contracts)
+            .map(|c| {
+                let contract_id = c.accepted_contract.get_contract_id();
+                let span = contract_span(
+                    &contract_id,
+                    get_event_id(&c.accepted_contract.offered_contract.contract_info),
+                );
+                async move {
+                    let mut attestation_status = None;
+                    let outcome = match self
+                        .lock_contract_in_state(&contract_id, ContractState::Confirmed)
+                        .await
+                    {
+                        Ok(Some((_lock, Contract::Confirmed(c)))) => {
+                            self.check_confirmed_contract(&c, &mut attestation_status)
+                                .await
+                        }
+                        Ok(_) => Ok(CheckOutcome::Unchanged),
+                        Err(e) => Err(e),
+                    };
+                    if let Ok(CheckOutcome::Updated(Contract::Closed(_))) = &outcome {
+                        record_close_latency(&c, self.time.unix_time_now());
+                    }
+                    (c, outcome, attestation_status)
+                }
+                .instrument(span)
+            })
+            .buffer_unordered(self.config.max_concurrent_checks.max(1))
+            .collect()
+            .await;
+
+        for (c, outcome, attestation_status) in outcomes {
+            report.add_outcome(&c, ContractState::Confirmed, outcome);
+            if let Some(status) = attestation_status {
+                report.add_attestation_status(status);
             }
-            match self.check_confirmed_contract(&c).await {
-                Err(e) => {
-                    error!(
-                        "Error checking confirmed contract {}: {}",
-                        c.accepted_contract.get_contract_id_string(),
-                        e
-                    )
+        }
+
+        Ok(())
+    }
+
+    /// Check the signed channels: follow the transactions closing them, offer
+    /// to close cooperatively those whose contract got attested and give up on
+    /// the updates and close offers the counter party did not complete within
+    /// [`PEER_TIMEOUT`], closing the channel unilaterally.
+    ///
+    /// Channel contracts are never refunded: their refund transaction spends
+    /// the buffer transaction, which is only published along with an attested
+    /// CET. Channels whose oracles never attest have to be closed
+    /// cooperatively.
+    #[instrument(skip_all)]
+    async fn check_channels(&self, report: &mut PeriodicCheckReport) -> Result<(), Error> {
+        let channels = self.store.get_signed_channels(None).await?;
+        for channel in channels {
+            let channel_id = channel.channel_id;
+            let outcome = match self.contract_locks.try_lock(&channel_id) {
+                Some(_lock) => self.check_channel(&channel_id, report).await,
+                None => {
+                    debug!(
+                        "Skipping channel {:02x?}, already being processed",
+                        channel_id
+                    );
+                    Ok(ChannelCheckOutcome::Unchanged)
+                }
+            };
+            report.add_channel_outcome(channel_id, outcome);
+        }
+
+        Ok(())
+    }
+
+    async fn check_channel(
+        &self,
+        channel_id: &ChannelId,
+        report: &mut PeriodicCheckReport,
+    ) -> Result<ChannelCheckOutcome, Error> {
+        let mut signed_channel =
+            get_channel_in_state!(self, channel_id, Signed, None as Option<PublicKey>)?;
+        match &signed_channel.state {
+            SignedChannelState::Closing {
+                buffer_transaction, ..
+            } => {
+                let confirmations = self
+                    .blockchain
+                    .get_transaction_confirmations_async(&buffer_transaction.txid())
+                    .await?;
+                if confirmations < CET_NSEQUENCE {
+                    return Ok(ChannelCheckOutcome::Unchanged);
                 }
-                Ok(true) => {
-                    let contract_id = c.accepted_contract.get_contract_id();
-                    let oracle_event_id = c
-                        .accepted_contract
-                        .offered_contract
-                        .contract_info
-                        .first()
-                        .and_then(|info| info.oracle_announcements.first())
-                        .map(|announcement| announcement.oracle_event.event_id.clone())
-                        .ok_or(Error::InvalidState("Missing oracle event ID".to_string()))?;
-
-                    contracts_to_close.push((contract_id, oracle_event_id));
+                return self.close_closing_channel(signed_channel, report).await;
+            }
+            SignedChannelState::CollaborativeCloseOffered { close_tx, .. } => {
+                let confirmations = self
+                    .blockchain
+                    .get_transaction_confirmations_async(&close_tx.txid())
+                    .await?;
+                if confirmations >= 1 {
+                    return self.on_collaborative_close_confirmed(signed_channel).await;
                 }
-                Ok(false) => (),
             }
+            SignedChannelState::Established {
+                signed_contract_id, ..
+            } => {
+                let contract_id = *signed_contract_id;
+                return self
+                    .check_established_channel(signed_channel, &contract_id)
+                    .await;
+            }
+            _ => {}
         }
 
-        Ok(contracts_to_close)
+        match get_channel_timeout(&signed_channel.state) {
+            Some(timeout) if timeout < self.time.unix_time_now() => {
+                warn!(
+                    "Channel {:02x?} timed out in state {:?}, closing it",
+                    channel_id, signed_channel.state
+                );
+                // The roll back is persisted even if the channel cannot be
+                // closed yet, so that it is force closed once its contract
+                // gets attested.
+                roll_back_channel(&mut signed_channel)?;
+                let force_closed = self
+                    .force_close_channel_internal(&mut signed_channel)
+                    .await?;
+                self.store
+                    .upsert_channel(&Channel::Signed(signed_channel))
+                    .await?;
+                Ok(if force_closed {
+                    ChannelCheckOutcome::ForceClosed
+                } else {
+                    ChannelCheckOutcome::Unchanged
+                })
+            }
+            _ => Ok(ChannelCheckOutcome::Unchanged),
+        }
     }
 
-    async fn get_closable_contract_info<'a>(
-        &'a self,
-        contract: &'a SignedContract,
-    ) -> Result<ClosableContractInfo<'a>, Error> {
-        let manager_oracles = match &self.oracles {
-            // Oracles is now an optional field, so check here before continuing.
-            Some(oracles) => oracles,
+    /// Close an established channel once its contract got attested. The offer
+    /// party offers a collaborative close paying each party what the attested
+    /// CET would, and closes the channel unilaterally if the offer times out.
+    /// The accept party waits [`PEER_TIMEOUT`] for that offer before closing
+    /// the channel unilaterally itself.
+    async fn check_established_channel(
+        &self,
+        mut signed_channel: SignedChannel,
+        contract_id: &ContractId,
+    ) -> Result<ChannelCheckOutcome, Error> {
+        let contract = match self.store.get_contract(contract_id).await? {
+            Some(Contract::Confirmed(c)) => c,
+            _ => return Ok(ChannelCheckOutcome::Unchanged),
+        };
+        let (contract_info, adaptor_info, attestations) =
+            match self.get_closable_contract_info(&contract).await?.0 {
+                Some(info) => info,
+                None => return Ok(ChannelCheckOutcome::Unchanged),
+            };
+
+        if Role::IS_OFFER_PARTY {
+            let offered_contract = &contract.accepted_contract.offered_contract;
+            let payout = get_attested_payout(
+                contract_info,
+                adaptor_info,
+                &attestations,
+                offered_contract.total_collateral,
+            )?;
+            let counter_payout = if offered_contract.is_offer_party {
+                payout.accept
+            } else {
+                payout.offer
+            };
+            let (close_offer, _) = channel_updater::offer_collaborative_close(
+                &self.secp,
+                &mut signed_channel,
+                counter_payout,
+                &self.wallet,
+                &self.time,
+            )?;
+            info!(
+                "Offered to close channel {:02x?} paying {} sats to the counter party",
+                signed_channel.channel_id, counter_payout
+            );
+            let counter_party = signed_channel.counter_party;
+            self.store
+                .upsert_channel(&Channel::Signed(signed_channel))
+                .await?;
+            return Ok(ChannelCheckOutcome::CloseOffered(
+                close_offer,
+                counter_party,
+            ));
+        }
+
+        let mut metadata = self
+            .store
+            .get_contract_metadata(contract_id)
+            .await?
+            .unwrap_or_default();
+        let now = self.time.unix_time_now();
+        match metadata.attested_at {
             None => {
-                return Err(Error::InvalidParameters(
-                    "Manager instantiated without oracles, get_closable_contract_info function not supported"
-                        .to_string(),
-                ));
+                metadata.attested_at = Some(now);
+                self.store
+                    .upsert_contract_metadata(contract_id, &metadata)
+                    .await?;
+                return Ok(ChannelCheckOutcome::Unchanged);
+            }
+            Some(attested_at) if now <= attested_at + PEER_TIMEOUT => {
+                return Ok(ChannelCheckOutcome::Unchanged)
+            }
+            Some(_) => {}
+        }
+        warn!(
+            "No close offer for channel {:02x?} since its contract got attested, closing it",
+            signed_channel.channel_id
+        );
+        if !self
+            .force_close_channel_internal(&mut signed_channel)
+            .await?
+        {
+            return Ok(ChannelCheckOutcome::Unchanged);
+        }
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await?;
+        Ok(ChannelCheckOutcome::ForceClosed)
+    }
+
+    /// Close the channel whose collaborative close we offered once its close
+    /// transaction got confirmed, along with its contract if any.
+    async fn on_collaborative_close_confirmed(
+        &self,
+        mut signed_channel: SignedChannel,
+    ) -> Result<ChannelCheckOutcome, Error> {
+        let close_tx = match &signed_channel.state {
+            SignedChannelState::CollaborativeCloseOffered { close_tx, .. } => close_tx.clone(),
+            _ => {
+                return Err(unexpected_channel_state(
+                    "CollaborativeCloseOffered",
+                    &signed_channel,
+                ))
             }
         };
+        let channel_id = signed_channel.channel_id;
+        // Settled channels have no contract left to close, so their payout
+        // does not matter.
+        let own_payout = self
+            .store
+            .get_confirmed_contracts()
+            .await?
+            .iter()
+            .find(|c| c.channel_id == Some(channel_id))
+            .map(|c| get_own_payout(&c.accepted_contract, &close_tx))
+            .unwrap_or_default();
+
+        signed_channel.state = SignedChannelState::CollaborativelyClosed;
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await?;
+        self.close_channel_contracts(&channel_id, None, own_payout)
+            .await?;
+
+        Ok(ChannelCheckOutcome::Closed)
+    }
+
+    /// Broadcast the CET of a channel whose buffer transaction got enough
+    /// confirmations for it to be valid, closing the channel and its contract.
+    async fn close_closing_channel(
+        &self,
+        mut signed_channel: SignedChannel,
+        report: &mut PeriodicCheckReport,
+    ) -> Result<ChannelCheckOutcome, Error> {
+        let (signed_cet, contract_id, attestations) = match &signed_channel.state {
+            SignedChannelState::Closing {
+                signed_cet,
+                contract_id,
+                attestations,
+                ..
+            } => (signed_cet.clone(), *contract_id, attestations.clone()),
+            _ => return Err(unexpected_channel_state("Closing", &signed_channel)),
+        };
+        let contract =
+            get_contract_in_state!(self, &contract_id, Confirmed, None as Option<PublicKey>)?;
+
+        let closed_contract = self
+            .close_contract(&contract, signed_cet, Some(attestations))
+            .await?;
+        self.update_contract(ContractState::Confirmed, &closed_contract)
+            .await?;
+        signed_channel.state = SignedChannelState::Closed;
+        self.store
+            .upsert_channel(&Channel::Signed(signed_channel))
+            .await?;
+
+        report.add_outcome(
+            &contract,
+            ContractState::Confirmed,
+            Ok(CheckOutcome::Updated(closed_contract)),
+        );
+        Ok(ChannelCheckOutcome::Closed)
+    }
+
+    async fn get_closable_contract_info<'a>(
+        &'a self,
+        contract: &'a SignedContract,
+    ) -> Result<(ClosableContractInfo<'a>, Option<AttestationStatus>), Error> {
+        let contract_id = contract.accepted_contract.get_contract_id();
         let contract_infos = &contract.accepted_contract.offered_contract.contract_info;
         let adaptor_infos = &contract.accepted_contract.adaptor_infos;
+        let mut status = None;
         for (contract_info, adaptor_info) in contract_infos.iter().zip(adaptor_infos.iter()) {
-            let announcements: Vec<(usize, &OracleAnnouncement)> = contract_info
-                .oracle_announcements
+            let announcements = &contract_info.oracle_announcements;
+            if announcements.len() < contract_info.threshold {
+                continue;
+            }
+
+            // Attestations are requested from every oracle at once and used as
+            // they arrive, so that slow or unreachable oracles do not delay the
+            // closing once enough of them agree.
+            let mut requests: FuturesUnordered<_> = announcements
                 .iter()
                 .enumerate()
+                .map(|(i, announcement)| async move {
+                    let attestation = self.get_valid_attestation(announcement).await;
+                    (i, attestation)
+                })
                 .collect();
-
-            if announcements.len() >= contract_info.threshold {
-                let attestations: Vec<_> = futures::future::join_all(announcements.iter().map(
-                    |(i, announcement)| async move {
-                        let oracle = match manager_oracles.get(&announcement.oracle_public_key) {
-                            Some(x) => x,
-                            None => {
-                                return Err(Error::InvalidParameters(
-                                    "Unknown oracle public key".to_string(),
-                                ));
-                            }
-                        };
-
-                        let attestation = oracle
-                            .get_attestation(&announcement.oracle_event.event_id)
-                            .await
-                            .map_err(|err| Error::OracleError(err.to_string()))?;
-
-                        Ok((*i, attestation))
-                    },
-                ))
-                .await;
-                let attestations: Vec<_> = attestations
-                    .iter()
-                    .filter(|&pair| pair.is_ok())
-                    .flat_map(|pair| {
-                        pair.as_ref()
-                            .map(|(i, attestation)| (*i, attestation.clone()))
-                    })
-                    .collect();
-
+            let mut info_status = AttestationStatus::new(contract_id, contract_info.threshold);
+            let mut attestations = Vec::new();
+            let mut agreed_outcomes = None;
+            while let Some((i, attestation)) = requests.next().await {
+                match attestation {
+                    Ok(attestation) => attestations.push((i, attestation)),
+                    Err(e) => {
+                        info_status.missing.push(MissingAttestation {
+                            oracle_public_key: announcements[i].oracle_public_key,
+                            reason: e.to_string(),
+                        });
+                        continue;
+                    }
+                }
                 if attestations.len() >= contract_info.threshold {
-                    return Ok(Some((contract_info, adaptor_info, attestations)));
+                    agreed_outcomes =
+                        get_agreed_outcomes(contract_info, adaptor_info, &attestations)?;
+                    if agreed_outcomes.is_some() {
+                        break;
+                    }
                 }
             }
+
+            info_status.complete(announcements, &attestations, agreed_outcomes.as_ref());
+            if info_status.state == AttestationState::Attested {
+                return Ok((
+                    Some((contract_info, adaptor_info, attestations)),
+                    Some(info_status),
+                ));
+            }
+            status = Some(info_status);
+        }
+
+        Ok((None, status))
+    }
+
+    /// Fetches the attestation of the announced event from its oracle and
+    /// checks it against the announcement. Oracles missing from the Manager's
+    /// oracles are looked up with its resolver. The time to wait for an answer
+    /// is bounded by the oracle client.
+    async fn get_valid_attestation(
+        &self,
+        announcement: &OracleAnnouncement,
+    ) -> Result<OracleAttestation, Error> {
+        let public_key = &announcement.oracle_public_key;
+        let event_id = &announcement.oracle_event.event_id;
+        let attestation = match self.oracles.get(public_key) {
+            Some(oracle) => oracle.get_attestation(event_id).await,
+            None => match self.oracle_resolver.resolve_oracle(public_key).await? {
+                Some(oracle) => oracle.get_attestation(event_id).await,
+                None => {
+                    return Err(Error::InvalidParameters(
+                        "Unknown oracle public key".to_string(),
+                    ))
+                }
+            },
         }
+        .map_err(|err| Error::OracleError(err.to_string()))?;
+        validate_attestation(&self.secp, announcement, &attestation)?;
+        Ok(attestation)
+    }
 
-        Ok(None)
+    /// Persists the conflicting attestations of a contract, notifying the
+    /// observers the first time they are seen.
+    async fn record_oracle_disagreement(&self, status: &AttestationStatus) -> Result<(), Error> {
+        let contract_id = &status.contract_id;
+        let mut metadata = self
+            .store
+            .get_contract_metadata(contract_id)
+            .await?
+            .unwrap_or_default();
+        let detected_at = match &metadata.oracle_disagreement {
+            Some(disagreement) if disagreement.attestations == status.attestations => return Ok(()),
+            Some(disagreement) => disagreement.detected_at,
+            None => self.time.unix_time_now(),
+        };
+        let disagreement = OracleDisagreement {
+            detected_at,
+            attestations: status.attestations.clone(),
+        };
+        metadata.oracle_disagreement = Some(disagreement.clone());
+        self.store
+            .upsert_contract_metadata(contract_id, &metadata)
+            .await?;
+        for observer in &self.observers {
+            observer.on_oracle_disagreement(contract_id, &disagreement);
+        }
+        Ok(())
     }
 
-    async fn check_confirmed_contract(&self, contract: &SignedContract) -> Result<bool, Error> {
-        let closable_contract_info = self.get_closable_contract_info(contract).await;
-        if let Ok(Some((contract_info, adaptor_info, attestations))) = closable_contract_info {
+    async fn check_confirmed_contract(
+        &self,
+        contract: &SignedContract,
+        attestation_status: &mut Option<AttestationStatus>,
+    ) -> Result<CheckOutcome, Error> {
+        match self.get_funding_spend(contract).await? {
+            FundingSpend::Unspent => {
+                if let Some(close_tx) = self.get_mutual_close_tx(contract).await? {
+                    // The mutual close we signed is not known to the
+                    // blockchain, it is broadcast again rather than closing
+                    // with a CET unless we give up on it.
+                    let contract_id = contract.accepted_contract.get_contract_id();
+                    match self.rebroadcast(&contract_id, &close_tx).await {
+                        Ok(()) => return Ok(CheckOutcome::Unchanged),
+                        Err(e) => warn!(
+                            "Giving up on mutual close {} of contract {}: {}",
+                            close_tx.txid(),
+                            contract.accepted_contract.get_contract_id_string(),
+                            e
+                        ),
+                    }
+                }
+            }
+            FundingSpend::Cet(index, cet) => {
+                info!(
+                    "Funding output of contract {} already spent by CET #{} ({})",
+                    contract.accepted_contract.get_contract_id_string(),
+                    index,
+                    cet.txid()
+                );
+                let confirmations = self
+                    .blockchain
+                    .get_transaction_confirmations_async(&cet.txid())
+                    .await?;
+                let closed_contract = get_closed_or_preclosed_contract(
+                    contract,
+                    cet,
+                    None,
+                    confirmations,
+                    self.get_closing_confirmations(contract),
+                );
+                self.update_contract(ContractState::Confirmed, &closed_contract)
+                    .await?;
+                return Ok(CheckOutcome::Updated(closed_contract));
+            }
+            FundingSpend::Refund(refund) => {
+                let confirmations = self
+                    .blockchain
+                    .get_transaction_confirmations_async(&refund.txid())
+                    .await?;
+                // Contracts stay Confirmed until their refund is, so that its
+                // fee can be bumped.
+                if confirmations == 0 {
+                    self.bump_spending_fee_if_stuck(contract, &refund).await?;
+                    return Ok(CheckOutcome::Unchanged);
+                }
+                info!(
+                    "Funding output of contract {} spent by refund {}",
+                    contract.accepted_contract.get_contract_id_string(),
+                    refund.txid()
+                );
+                let refunded_contract = Contract::Refunded(contract.clone());
+                self.update_contract(ContractState::Confirmed, &refunded_contract)
+                    .await?;
+                return Ok(CheckOutcome::Updated(refunded_contract));
+            }
+            FundingSpend::MutualClose(close_tx) => {
+                debug!(
+                    "Funding output of contract {} spent by mutual close {}",
+                    contract.accepted_contract.get_contract_id_string(),
+                    close_tx.txid()
+                );
+                return self
+                    .check_mutual_close(ContractState::Confirmed, contract, close_tx)
+                    .await;
+            }
+            FundingSpend::Unexpected(txid) => return Ok(CheckOutcome::UnexpectedSpend(txid)),
+        }
+
+        let closable_contract_info = match self.get_closable_contract_info(contract).await {
+            Ok((closable_contract_info, status)) => {
+                if let Some(status) = &status {
+                    if status.state == AttestationState::Disagreement {
+                        self.record_oracle_disagreement(status).await?;
+                    }
+                }
+                *attestation_status = status;
+                closable_contract_info
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to get attestations of contract {}: {}",
+                    contract.accepted_contract.get_contract_id_string(),
+                    e
+                );
+                None
+            }
+        };
+        if let Some((contract_info, adaptor_info, attestations)) = closable_contract_info {
             let cet = crate::dlc_manager::contract_updater::get_signed_cet(
                 &self.secp,
                 contract,
@@ -635,13 +3097,14 @@ where
                 .close_contract(
                     contract,
                     cet,
-                    attestations.iter().map(|x| x.1.clone()).collect(),
+                    Some(attestations.iter().map(|x| x.1.clone()).collect()),
                 )
                 .await
             {
                 Ok(closed_contract) => {
-                    self.store.update_contract(&closed_contract).await?;
-                    return Ok(true);
+                    self.update_contract(ContractState::Confirmed, &closed_contract)
+                        .await?;
+                    return Ok(CheckOutcome::Updated(closed_contract));
                 }
                 Err(e) => {
                     warn!(
@@ -654,160 +3117,819 @@ where
             }
         }
         self.check_refund(contract).await?;
+        Ok(CheckOutcome::Unchanged)
+    }
+
+    /// Looks up the transaction spending the funding output of the given
+    /// contract and identifies it as one of its CETs, its refund, a mutual
+    /// close transaction, or an unexpected spend.
+    async fn get_funding_spend(&self, contract: &SignedContract) -> Result<FundingSpend, Error> {
+        let dlc_transactions = &contract.accepted_contract.dlc_transactions;
+        let (fund_outpoint, _, _) = get_fund_output(&contract.accepted_contract)?;
+
+        let spending_txid = match self
+            .blockchain
+            .get_spending_txid_async(&fund_outpoint)
+            .await?
+        {
+            Some(txid) => txid,
+            None => return Ok(FundingSpend::Unspent),
+        };
+
+        if spending_txid == dlc_transactions.refund.txid() {
+            let refund = self
+                .blockchain
+                .get_transaction_async(&spending_txid)
+                .await?;
+            return Ok(FundingSpend::Refund(refund));
+        }
 
-        Ok(false)
-    }
-
-    async fn check_preclosed_contracts(&self) -> Result<Vec<(ContractId, String)>, Error> {
-        let mut contracts_to_close = Vec::new();
-        for c in self.store.get_preclosed_contracts().await? {
-            match self.check_preclosed_contract(&c).await {
-                Ok(true) => {
-                    let contract_id = c.signed_contract.accepted_contract.get_contract_id();
-                    let oracle_event_id = c
-                        .signed_contract
-                        .accepted_contract
-                        .offered_contract
-                        .contract_info
-                        .first()
-                        .and_then(|info| info.oracle_announcements.first())
-                        .map(|announcement| announcement.oracle_event.event_id.clone())
-                        .ok_or(Error::InvalidState("Missing oracle event ID".to_string()))?;
-
-                    contracts_to_close.push((contract_id, oracle_event_id));
+        match dlc_transactions
+            .cets
+            .iter()
+            .position(|cet| cet.txid() == spending_txid)
+        {
+            Some(index) => {
+                let cet = self
+                    .blockchain
+                    .get_transaction_async(&spending_txid)
+                    .await?;
+                Ok(FundingSpend::Cet(index, cet))
+            }
+            None => {
+                let spending_tx = self
+                    .blockchain
+                    .get_transaction_async(&spending_txid)
+                    .await?;
+                if is_mutual_close_transaction(&self.secp, contract, &spending_tx) {
+                    Ok(FundingSpend::MutualClose(spending_tx))
+                } else {
+                    Ok(FundingSpend::Unexpected(spending_txid))
                 }
-                Ok(false) => (),
-                Err(e) => error!(
-                    "Error checking pre-closed contract {}: {}",
-                    c.signed_contract.accepted_contract.get_contract_id_string(),
-                    e
-                ),
             }
         }
+    }
+
+    #[instrument(skip_all)]
+    async fn check_preclosed_contracts(
+        &self,
+        report: &mut PeriodicCheckReport,
+    ) -> Result<(), Error> {
+        let contracts = self.store.get_preclosed_contracts().await?;
+        record_contract_count(ContractState::PreClosed, contracts.len());
+        let outcomes: Vec<_> = stream::iter(contracts)
+            .map(|c| {
+                let accepted_contract = &c.signed_contract.accepted_contract;
+                let contract_id = accepted_contract.get_contract_id();
+                let span = contract_span(
+                    &contract_id,
+                    get_event_id(&accepted_contract.offered_contract.contract_info),
+                );
+                async move {
+                    let outcome = match self
+                        .lock_contract_in_state(&contract_id, ContractState::PreClosed)
+                        .await
+                    {
+                        Ok(Some((_lock, Contract::PreClosed(c)))) => {
+                            self.check_preclosed_contract(&c).await
+                        }
+                        Ok(_) => Ok(CheckOutcome::Unchanged),
+                        Err(e) => Err(e),
+                    };
+                    if let Ok(CheckOutcome::Updated(Contract::Closed(_))) = &outcome {
+                        record_close_latency(&c.signed_contract, self.time.unix_time_now());
+                    }
+                    (c, outcome)
+                }
+                .instrument(span)
+            })
+            .buffer_unordered(self.config.max_concurrent_checks.max(1))
+            .collect()
+            .await;
+
+        for (c, outcome) in outcomes {
+            report.add_outcome(&c.signed_contract, ContractState::PreClosed, outcome);
+        }
 
-        Ok(contracts_to_close)
+        Ok(())
     }
 
-    async fn check_preclosed_contract(&self, contract: &PreClosedContract) -> Result<bool, Error> {
+    async fn check_preclosed_contract(
+        &self,
+        contract: &PreClosedContract,
+    ) -> Result<CheckOutcome, Error> {
         let broadcasted_txid = contract.signed_cet.txid();
-        let confirmations = self
+        let is_known = self
             .blockchain
-            .get_transaction_confirmations_async(&broadcasted_txid)
-            .await?;
-        if confirmations >= NB_CONFIRMATIONS {
-            let closed_contract = ClosedContract {
-                attestations: contract.attestations.clone(),
-                signed_cet: Some(contract.signed_cet.clone()),
-                contract_id: contract.signed_contract.accepted_contract.get_contract_id(),
-                temporary_contract_id: contract
-                    .signed_contract
-                    .accepted_contract
-                    .offered_contract
-                    .id,
-                counter_party_id: contract
-                    .signed_contract
-                    .accepted_contract
-                    .offered_contract
-                    .counter_party,
-                pnl: contract
-                    .signed_contract
-                    .accepted_contract
-                    .compute_pnl(&contract.signed_cet),
-            };
-            self.store
-                .update_contract(&Contract::Closed(closed_contract.clone()))
+            .is_transaction_known_async(&broadcasted_txid)
+            .await?;
+        let confirmations = if is_known {
+            self.blockchain
+                .get_transaction_confirmations_async(&broadcasted_txid)
+                .await?
+        } else {
+            0
+        };
+        if confirmations == 0 {
+            // The CET we broadcast may have lost against another spend of the
+            // funding output, in which case we follow what actually happened.
+            match self.get_funding_spend(&contract.signed_contract).await? {
+                FundingSpend::Cet(index, cet) if cet.txid() != broadcasted_txid => {
+                    warn!(
+                        "Contract {} was closed by CET #{} ({}) instead of {}",
+                        contract
+                            .signed_contract
+                            .accepted_contract
+                            .get_contract_id_string(),
+                        index,
+                        cet.txid(),
+                        broadcasted_txid
+                    );
+                    let confirmations = self
+                        .blockchain
+                        .get_transaction_confirmations_async(&cet.txid())
+                        .await?;
+                    let closed_contract = get_closed_or_preclosed_contract(
+                        &contract.signed_contract,
+                        cet,
+                        contract.attestations.clone(),
+                        confirmations,
+                        self.get_closing_confirmations(&contract.signed_contract),
+                    );
+                    self.update_contract(ContractState::PreClosed, &closed_contract)
+                        .await?;
+                    return Ok(CheckOutcome::Updated(closed_contract));
+                }
+                FundingSpend::Refund(refund) => {
+                    warn!(
+                        "Contract {} was refunded by {} instead of closed by {}",
+                        contract
+                            .signed_contract
+                            .accepted_contract
+                            .get_contract_id_string(),
+                        refund.txid(),
+                        broadcasted_txid
+                    );
+                    let refunded_contract = Contract::Refunded(contract.signed_contract.clone());
+                    self.update_contract(ContractState::PreClosed, &refunded_contract)
+                        .await?;
+                    return Ok(CheckOutcome::Updated(refunded_contract));
+                }
+                FundingSpend::MutualClose(close_tx) => {
+                    warn!(
+                        "Contract {} was closed by mutual close {} instead of {}",
+                        contract
+                            .signed_contract
+                            .accepted_contract
+                            .get_contract_id_string(),
+                        close_tx.txid(),
+                        broadcasted_txid
+                    );
+                    return self
+                        .check_mutual_close(
+                            ContractState::PreClosed,
+                            &contract.signed_contract,
+                            close_tx,
+                        )
+                        .await;
+                }
+                FundingSpend::Unexpected(txid) => return Ok(CheckOutcome::UnexpectedSpend(txid)),
+                _ => (),
+            }
+            if is_known {
+                self.bump_spending_fee_if_stuck(&contract.signed_contract, &contract.signed_cet)
+                    .await?;
+            } else {
+                let contract_id = contract.signed_contract.accepted_contract.get_contract_id();
+                self.rebroadcast(&contract_id, &contract.signed_cet).await?;
+            }
+        }
+        let required_confirmations = self.get_closing_confirmations(&contract.signed_contract);
+        if confirmations >= required_confirmations {
+            let closed_contract = get_closed_or_preclosed_contract(
+                &contract.signed_contract,
+                contract.signed_cet.clone(),
+                contract.attestations.clone(),
+                confirmations,
+                required_confirmations,
+            );
+            self.update_contract(ContractState::PreClosed, &closed_contract)
                 .await?;
-            return Ok(true);
+            return Ok(CheckOutcome::Updated(closed_contract));
+        }
+
+        Ok(CheckOutcome::Unchanged)
+    }
+
+    #[instrument(skip_all)]
+    async fn check_stale_contracts(&self, report: &mut PeriodicCheckReport) -> Result<(), Error> {
+        if !self.cleans_up_stale_contracts() {
+            return Ok(());
+        }
+        let now = self.time.unix_time_now();
+        let mut states = Vec::new();
+        if self.config.offer_expiry.is_some() {
+            states.extend([ContractState::Offered, ContractState::Accepted]);
+        }
+        if self.config.failed_contract_policy != FailedContractPolicy::Keep {
+            states.extend([ContractState::FailedAccept, ContractState::FailedSign]);
+        }
+        let mut contracts = Vec::new();
+        for state in states {
+            contracts.extend(self.store.get_contracts_in_state(state).await?);
+        }
+        let outcomes: Vec<_> = stream::iter(contracts)
+            .map(|c| {
+                let contract_id = c.get_id();
+                let span = contract_span(&contract_id, get_contract_event_id(&c).as_deref());
+                async move {
+                    let state = ContractState::from(&c);
+                    let outcome = match self.lock_contract_in_state(&contract_id, state).await {
+                        Ok(Some((_lock, c))) => self.check_stale_contract(&c, now).await,
+                        Ok(None) => Ok(CleanupOutcome::Unchanged),
+                        Err(e) => Err(e),
+                    };
+                    (contract_id, state, outcome)
+                }
+                .instrument(span)
+            })
+            .buffer_unordered(self.config.max_concurrent_checks.max(1))
+            .collect()
+            .await;
+
+        for (contract_id, state, outcome) in outcomes {
+            report.add_cleanup_outcome(contract_id, state, outcome);
+        }
+
+        Ok(())
+    }
+
+    /// Expire offers and delete failed records that are older than the
+    /// configured limits. Contracts created before their creation time was
+    /// recorded are aged from the first time they are checked.
+    async fn check_stale_contract(
+        &self,
+        contract: &Contract,
+        now: u64,
+    ) -> Result<CleanupOutcome, Error> {
+        let max_age = match contract {
+            Contract::Offered(_) | Contract::Accepted(_) => self.config.offer_expiry,
+            Contract::FailedAccept(_) | Contract::FailedSign(_) => {
+                match self.config.failed_contract_policy {
+                    FailedContractPolicy::Keep => None,
+                    FailedContractPolicy::Delete { after } => Some(after),
+                }
+            }
+            _ => None,
+        };
+        let max_age = match max_age {
+            Some(max_age) => max_age,
+            None => return Ok(CleanupOutcome::Unchanged),
+        };
+
+        let temporary_id = contract.get_temporary_id();
+        let mut metadata = self
+            .store
+            .get_contract_metadata(&temporary_id)
+            .await?
+            .unwrap_or_default();
+        let created_at = match metadata.created_at {
+            Some(created_at) => created_at,
+            None => {
+                metadata.created_at = Some(now);
+                self.store
+                    .upsert_contract_metadata(&temporary_id, &metadata)
+                    .await?;
+                return Ok(CleanupOutcome::Unchanged);
+            }
+        };
+        if now < created_at.saturating_add(max_age) {
+            return Ok(CleanupOutcome::Unchanged);
         }
 
-        Ok(false)
+        let outcome = match contract {
+            Contract::Offered(offered_contract) => {
+                self.update_contract(
+                    ContractState::Offered,
+                    &Contract::Rejected(offered_contract.clone()),
+                )
+                .await?;
+                CleanupOutcome::Expired
+            }
+            Contract::Accepted(accepted_contract) => {
+                // Accepted contracts are stored under their final id while
+                // rejected ones are stored under their temporary id.
+                self.replace_contract(
+                    &accepted_contract.get_contract_id(),
+                    ContractState::Accepted,
+                    &Contract::Rejected(accepted_contract.offered_contract.clone()),
+                )
+                .await?;
+                CleanupOutcome::Expired
+            }
+            _ => {
+                self.delete_contract(contract).await?;
+                CleanupOutcome::Deleted
+            }
+        };
+        // None of these contracts had its funding transaction signed, so the
+        // inputs reserved for it can fund other contracts.
+        self.wallet
+            .unreserve_utxos(&get_own_funding_outpoints(contract))?;
+        Ok(outcome)
     }
 
     async fn close_contract(
         &self,
         contract: &SignedContract,
         signed_cet: Transaction,
-        attestations: Vec<OracleAttestation>,
+        attestations: Option<Vec<OracleAttestation>>,
     ) -> Result<Contract, Error> {
         let confirmations = self
             .blockchain
             .get_transaction_confirmations_async(&signed_cet.txid())
             .await?;
 
-        // Put it here for post-close, and here's the btc txid too.
-        // But perhaps we'd rather have it in the final close place, and
-        // only set it after 6 confirmations
-
         if confirmations < 1 {
-            // TODO(tibo): if this fails because another tx is already in
-            // mempool or blockchain, we might have been cheated. There is
-            // not much to be done apart from possibly extracting a fraud
-            // proof but ideally it should be handled.
-            self.blockchain.send_transaction_async(&signed_cet).await?;
-
-            let preclosed_contract = PreClosedContract {
-                signed_contract: contract.clone(),
-                attestations: Some(attestations),
-                signed_cet,
-            };
+            // Spends of the funding output by another transaction are caught
+            // by `get_funding_spend` before we get here, so a failure at this
+            // point means we raced with one and the next check will pick it up.
+            self.broadcast(&signed_cet, TransactionType::Cet).await?;
+        }
 
-            return Ok(Contract::PreClosed(preclosed_contract));
-        } else if confirmations < NB_CONFIRMATIONS {
-            let preclosed_contract = PreClosedContract {
-                signed_contract: contract.clone(),
-                attestations: Some(attestations),
-                signed_cet,
-            };
+        Ok(get_closed_or_preclosed_contract(
+            contract,
+            signed_cet,
+            attestations,
+            confirmations,
+            self.get_closing_confirmations(contract),
+        ))
+    }
+
+    /// Record the fully signed close transaction of a confirmed contract and
+    /// broadcast it, unless it already spends the funding output. A failed
+    /// broadcast is retried by the periodic check.
+    async fn close_contract_mutually(
+        &self,
+        contract: &SignedContract,
+        close_tx: Transaction,
+    ) -> Result<(), Error> {
+        let contract_id = contract.accepted_contract.get_contract_id();
+        let mut metadata = self
+            .store
+            .get_contract_metadata(&contract_id)
+            .await?
+            .unwrap_or_default();
+        metadata.mutual_close_tx = Some(close_tx.clone());
+        self.store
+            .upsert_contract_metadata(&contract_id, &metadata)
+            .await?;
+
+        let (fund_outpoint, _, _) = get_fund_output(&contract.accepted_contract)?;
+        let close_txid = close_tx.txid();
+        if let Err(e) = self.blockchain.send_transaction_async(&close_tx).await {
+            // Both parties broadcast the close transaction, the counter
+            // party's copy may have reached the network first.
+            let spending_txid = self
+                .blockchain
+                .get_spending_txid_async(&fund_outpoint)
+                .await?;
+            if spending_txid != Some(close_txid) {
+                record_broadcast_failure(TransactionType::MutualClose);
+                warn!(
+                    "Failed to broadcast mutual close {} of contract {}, will retry: {}",
+                    close_txid,
+                    contract.accepted_contract.get_contract_id_string(),
+                    e
+                );
+                return Ok(());
+            }
+        }
+
+        self.check_mutual_close(ContractState::Confirmed, contract, close_tx)
+            .await?;
+        Ok(())
+    }
 
-            return Ok(Contract::PreClosed(preclosed_contract));
+    /// Follow a mutual close transaction spending the funding output of the
+    /// contract. The contract is Closed once the transaction has the required
+    /// confirmations and waits in the Confirmed state until then.
+    async fn check_mutual_close(
+        &self,
+        previous_state: ContractState,
+        contract: &SignedContract,
+        close_tx: Transaction,
+    ) -> Result<CheckOutcome, Error> {
+        let accepted_contract = &contract.accepted_contract;
+        let contract_id = accepted_contract.get_contract_id();
+        let close_txid = close_tx.txid();
+        let mut metadata = self
+            .store
+            .get_contract_metadata(&contract_id)
+            .await?
+            .unwrap_or_default();
+        // The counter party may have broadcast a close transaction we never
+        // received, or one of our offers it accepted.
+        if metadata.mutual_close_tx.as_ref().map(|tx| tx.txid()) != Some(close_txid) {
+            metadata.mutual_close_tx = Some(close_tx.clone());
+            self.store
+                .upsert_contract_metadata(&contract_id, &metadata)
+                .await?;
         }
 
-        let closed_contract = ClosedContract {
-            attestations: Some(attestations.to_vec()),
-            pnl: contract.accepted_contract.compute_pnl(&signed_cet),
-            signed_cet: Some(signed_cet),
-            contract_id: contract.accepted_contract.get_contract_id(),
-            temporary_contract_id: contract.accepted_contract.offered_contract.id,
-            counter_party_id: contract.accepted_contract.offered_contract.counter_party,
-        };
+        let confirmations = self
+            .blockchain
+            .get_transaction_confirmations_async(&close_txid)
+            .await?;
+        if confirmations == 0
+            && self
+                .blockchain
+                .is_transaction_known_async(&close_txid)
+                .await?
+        {
+            self.bump_spending_fee_if_stuck(contract, &close_tx).await?;
+        }
+        if confirmations < self.get_closing_confirmations(contract) {
+            if previous_state != ContractState::Confirmed {
+                // Our CET lost against the mutual close, which the contract
+                // now waits for. This is not reported as a new confirmation.
+                self.update_contract(previous_state, &Contract::Confirmed(contract.clone()))
+                    .await?;
+            }
+            return Ok(CheckOutcome::Unchanged);
+        }
+
+        let closed_contract = Contract::Closed(ClosedContract {
+            attestations: None,
+            pnl: accepted_contract.compute_pnl(&close_tx),
+            signed_cet: None,
+            contract_id,
+            temporary_contract_id: accepted_contract.offered_contract.id,
+            counter_party_id: accepted_contract.offered_contract.counter_party,
+        });
+        self.update_contract(previous_state, &closed_contract)
+            .await?;
+        Ok(CheckOutcome::MutuallyClosed(closed_contract, close_txid))
+    }
+
+    /// Returns the mutual close transaction signed for the contract, if any.
+    async fn get_mutual_close_tx(
+        &self,
+        contract: &SignedContract,
+    ) -> Result<Option<Transaction>, Error> {
+        Ok(self
+            .store
+            .get_contract_metadata(&contract.accepted_contract.get_contract_id())
+            .await?
+            .and_then(|metadata| metadata.mutual_close_tx))
+    }
 
-        Ok(Contract::Closed(closed_contract))
+    fn get_close_signature(
+        &self,
+        contract: &SignedContract,
+        close_tx: &Transaction,
+    ) -> Result<Signature, Error> {
+        let (own_fund_pubkey, _) = get_fund_pubkeys(contract);
+        let fund_secret_key = self.wallet.get_secret_key_for_pubkey(&own_fund_pubkey)?;
+        sign_close_transaction(&self.secp, contract, close_tx, &fund_secret_key)
     }
 
-    async fn get_json(&self, path: &str) -> Result<serde_json::Value, Error> {
-        reqwest::get(path)
+    /// Spend our output of an unconfirmed transaction of the contract with a
+    /// higher fee if it stayed unconfirmed for `fee_bump_delay` blocks, or for
+    /// as long since the previous bump. `own_script` is the script of our
+    /// output and `tx_fee` the fee paid by the transaction itself.
+    async fn bump_fee_if_stuck(
+        &self,
+        contract: &SignedContract,
+        tx: &Transaction,
+        tx_fee: u64,
+        own_script: &Script,
+    ) -> Result<(), Error> {
+        let fee_bump_delay = match self.config.fee_bump_delay {
+            Some(delay) => delay as u64,
+            None => return Ok(()),
+        };
+        let contract_id = contract.accepted_contract.get_contract_id();
+        let txid = tx.txid();
+
+        let height = self.blockchain.get_blockchain_height_async().await?;
+        let mut metadata = self
+            .store
+            .get_contract_metadata(&contract_id)
+            .await?
+            .unwrap_or_default();
+        let since_height = match &metadata.unconfirmed_tx {
+            Some(unconfirmed_tx) if unconfirmed_tx.txid == txid => unconfirmed_tx.since_height,
+            _ => {
+                metadata.unconfirmed_tx = Some(UnconfirmedTransaction {
+                    txid,
+                    since_height: height,
+                });
+                return self
+                    .store
+                    .upsert_contract_metadata(&contract_id, &metadata)
+                    .await;
+            }
+        };
+        let previous_bump = metadata.get_last_fee_bump(&txid).cloned();
+        let waiting_since = previous_bump
+            .as_ref()
+            .map_or(since_height, |bump| bump.height);
+        if height < waiting_since + fee_bump_delay {
+            return Ok(());
+        }
+
+        let vout = match tx
+            .output
+            .iter()
+            .position(|output| output.script_pubkey == *own_script)
+        {
+            Some(vout) => vout,
+            None => {
+                debug!(
+                    "No output of {} to bump the fee of contract {} with",
+                    txid,
+                    contract.accepted_contract.get_contract_id_string()
+                );
+                return Ok(());
+            }
+        };
+        let fee_rate = self
+            .blockchain
+            .get_fee_rate_async(self.config.fee_bump_confirmation_target)
+            .await?;
+        let destination = self.wallet.get_new_address()?.script_pubkey();
+
+        // The child is signed once to learn its size, then again once its fee
+        // is known.
+        let draft = self.sign_child_transaction(tx, vout, destination.clone(), 0)?;
+        let child_fee = get_child_fee(
+            tx,
+            tx_fee,
+            get_vsize(&draft),
+            fee_rate,
+            previous_bump.map(|bump| bump.fee),
+        );
+        let child = self.sign_child_transaction(tx, vout, destination, child_fee)?;
+        self.broadcast(&child, TransactionType::FeeBump).await?;
+        info!(
+            "Bumped the fee of {} for contract {} with {} paying {} sats",
+            txid,
+            contract.accepted_contract.get_contract_id_string(),
+            child.txid(),
+            child_fee
+        );
+
+        metadata.fee_bumps.push(FeeBump {
+            parent_txid: txid,
+            txid: child.txid(),
+            fee: child_fee,
+            height,
+        });
+        self.store
+            .upsert_contract_metadata(&contract_id, &metadata)
             .await
-            .map_err(|x| Error::WalletError(Box::new(x)))?
-            .json::<serde_json::Value>()
+    }
+
+    /// Bump the fee of a transaction spending the funding output of the
+    /// contract, paying us to our payout script.
+    async fn bump_spending_fee_if_stuck(
+        &self,
+        contract: &SignedContract,
+        tx: &Transaction,
+    ) -> Result<(), Error> {
+        let tx_fee = get_spending_fee(&contract.accepted_contract, tx)?;
+        let own_script = get_own_payout_script(&contract.accepted_contract);
+        self.bump_fee_if_stuck(contract, tx, tx_fee, own_script)
             .await
-            .map_err(|x| Error::WalletError(Box::new(x)))
     }
 
-    async fn get_unixtime(&self) -> Result<u64, Error> {
-        let path = "https://worldtimeapi.org/api/timezone/Etc/UTC";
-        let v = match self.get_json(path).await {
-            Ok(v) => v,
-            Err(e) => {
-                return Err(Error::WalletError(
-                    format!("Error getting unixtime: {e}").into(),
-                ))
+    /// Broadcast the transaction, counting failures in the broadcast failures
+    /// metric.
+    async fn broadcast(&self, tx: &Transaction, tx_type: TransactionType) -> Result<(), Error> {
+        let result = self.blockchain.send_transaction_async(tx).await;
+        if result.is_err() {
+            record_broadcast_failure(tx_type);
+        }
+        result
+    }
+
+    /// Broadcast again a transaction of the contract that the blockchain does
+    /// not know about, waiting longer after each attempt. Fails without
+    /// broadcasting once `max_rebroadcast_attempts` were made.
+    async fn rebroadcast(&self, contract_id: &ContractId, tx: &Transaction) -> Result<(), Error> {
+        let txid = tx.txid();
+        let now = self.time.unix_time_now();
+        let mut metadata = self
+            .store
+            .get_contract_metadata(contract_id)
+            .await?
+            .unwrap_or_default();
+        let (attempts, last_attempt) = match &metadata.rebroadcast {
+            Some(rebroadcast) if rebroadcast.txid == txid => {
+                (rebroadcast.attempts, rebroadcast.last_attempt)
             }
+            _ => (0, 0),
         };
 
-        let unixtime = match v["unixtime"].as_u64() {
-            //call to_string instead of as_str and watch your world crumble to pieces
-            None => return Err(Error::WalletError("unable to get unixtime".into())),
-            Some(s) => s,
+        if attempts >= self.config.max_rebroadcast_attempts {
+            return Err(Error::InvalidState(format!(
+                "Transaction {} is still unknown after {} broadcast attempts",
+                txid, attempts
+            )));
+        }
+        if attempts > 0 && now < last_attempt + self.config.get_rebroadcast_delay(attempts) {
+            return Ok(());
+        }
+
+        info!(
+            "Broadcasting missing transaction {} of contract {:02x?}, attempt #{}",
+            txid,
+            contract_id,
+            attempts + 1
+        );
+        if let Err(e) = self.broadcast(tx, TransactionType::Rebroadcast).await {
+            warn!("Failed to broadcast transaction {}: {}", txid, e);
+        }
+
+        metadata.rebroadcast = Some(Rebroadcast {
+            txid,
+            attempts: attempts + 1,
+            last_attempt: now,
+        });
+        self.store
+            .upsert_contract_metadata(contract_id, &metadata)
+            .await
+    }
+
+    fn sign_child_transaction(
+        &self,
+        parent: &Transaction,
+        vout: usize,
+        destination: Script,
+        fee: u64,
+    ) -> Result<Transaction, Error> {
+        let mut child = build_child_transaction(parent, vout as u32, destination, fee)?;
+        self.wallet
+            .sign_tx_input(&mut child, 0, &parent.output[vout], None)?;
+        Ok(child)
+    }
+
+    /// Returns true if offers expire or failed contracts get deleted.
+    fn cleans_up_stale_contracts(&self) -> bool {
+        self.config.offer_expiry.is_some()
+            || self.config.failed_contract_policy != FailedContractPolicy::Keep
+    }
+
+    fn get_closing_confirmations(&self, contract: &SignedContract) -> u32 {
+        self.config
+            .get_closing_confirmations(contract.accepted_contract.offered_contract.total_collateral)
+    }
+
+    /// Take the lock on the given contract, failing if it is already being
+    /// processed.
+    fn lock_contract(&self, contract_id: &ContractId) -> Result<ContractLock<'_>, Error> {
+        self.contract_locks
+            .try_lock(contract_id)
+            .ok_or(Error::InvalidState(format!(
+                "Contract {:02x?} is already being processed.",
+                contract_id
+            )))
+    }
+
+    /// Take the lock on the given contract and read it back from the store.
+    /// Returns `None` if the contract is being processed elsewhere or has left
+    /// the given state since it was listed.
+    async fn lock_contract_in_state(
+        &self,
+        contract_id: &ContractId,
+        state: ContractState,
+    ) -> Result<Option<(ContractLock<'_>, Contract)>, Error> {
+        let lock = match self.contract_locks.try_lock(contract_id) {
+            Some(lock) => lock,
+            None => {
+                debug!(
+                    "Skipping contract {:02x?}, already being processed",
+                    contract_id
+                );
+                return Ok(None);
+            }
         };
+        match self.store.get_contract(contract_id).await? {
+            Some(contract) if ContractState::from(&contract) == state => Ok(Some((lock, contract))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Persist a newly created contract and notify the observers. Its creation
+    /// time is only recorded when stale contracts are cleaned up.
+    async fn create_contract(&self, contract: &OfferedContract) -> Result<(), Error> {
+        self.store.create_contract(contract).await?;
+        if self.cleans_up_stale_contracts() {
+            let metadata = ContractMetadata {
+                created_at: Some(self.time.unix_time_now()),
+                ..Default::default()
+            };
+            self.store
+                .upsert_contract_metadata(&contract.id, &metadata)
+                .await?;
+        }
+        record_transition(ContractState::Offered);
+        self.notify_observers(&ContractTransition::new(
+            None,
+            &Contract::Offered(contract.clone()),
+        ));
+        Ok(())
+    }
 
-        Ok(unixtime)
+    /// Persist the new state of a contract and notify the observers of the
+    /// transition from `previous_state`.
+    async fn update_contract(
+        &self,
+        previous_state: ContractState,
+        contract: &Contract,
+    ) -> Result<(), Error> {
+        let mut transition = ContractTransition::new(Some(previous_state), contract);
+        if let Contract::Closed(closed_contract) = contract {
+            if !self.observers.is_empty() {
+                self.complete_closed_transition(&mut transition, closed_contract)
+                    .await?;
+            }
+        }
+        self.store.update_contract(contract).await?;
+        record_transition(contract.into());
+        self.notify_observers(&transition);
+        Ok(())
     }
 
-    async fn check_refund(&self, contract: &SignedContract) -> Result<(), Error> {
-        // TODO(tibo): should check for confirmation of refund before updating state
-        // use reqwest to fetch the current time
+    /// Persist the new state of a contract in place of the record kept under
+    /// `previous_id` and notify the observers of the transition from
+    /// `previous_state`.
+    async fn replace_contract(
+        &self,
+        previous_id: &ContractId,
+        previous_state: ContractState,
+        contract: &Contract,
+    ) -> Result<(), Error> {
+        self.store.replace_contract(previous_id, contract).await?;
+        record_transition(contract.into());
+        self.notify_observers(&ContractTransition::new(Some(previous_state), contract));
+        Ok(())
+    }
+
+    /// Closed contracts no longer hold their oracle event, which is taken from
+    /// the record they replace, and mutually closed ones do not hold their
+    /// close transaction, which is kept in their side record.
+    async fn complete_closed_transition(
+        &self,
+        transition: &mut ContractTransition,
+        contract: &ClosedContract,
+    ) -> Result<(), Error> {
+        transition.event_id = self
+            .store
+            .get_contract(&contract.contract_id)
+            .await?
+            .as_ref()
+            .and_then(get_contract_event_id);
+        if contract.signed_cet.is_none() {
+            let mutual_close_tx = self
+                .store
+                .get_contract_metadata(&contract.contract_id)
+                .await?
+                .and_then(|metadata| metadata.mutual_close_tx);
+            transition
+                .txids
+                .extend(mutual_close_tx.map(|close_tx| close_tx.txid()));
+        }
+        Ok(())
+    }
+
+    /// Let the observers archive the contract, then delete its record and the
+    /// side records kept under its temporary id and under its final id.
+    async fn delete_contract(&self, contract: &Contract) -> Result<(), Error> {
+        for observer in &self.observers {
+            observer.on_delete(contract);
+        }
+        let id = contract.get_id();
+        let temporary_id = contract.get_temporary_id();
+        self.store.delete_contract(&id).await?;
+        self.store.delete_contract_metadata(&temporary_id).await?;
+        if id != temporary_id {
+            self.store.delete_contract_metadata(&id).await?;
+        }
+        Ok(())
+    }
+
+    fn notify_observers(&self, transition: &ContractTransition) {
+        for observer in &self.observers {
+            observer.on_transition(transition);
+        }
+    }
 
-        let unixtime = self.get_unixtime().await?;
+    /// Broadcast the refund transaction of the contract once its lock time
+    /// has passed. The contract moves to Refunded once the refund confirms.
+    async fn check_refund(&self, contract: &SignedContract) -> Result<(), Error> {
+        let unixtime = self.clock.get_unix_time().await?;
         if contract
             .accepted_contract
             .dlc_transactions
@@ -816,24 +3938,12 @@ where
             .0 as u64
             <= unixtime
         {
-            let accepted_contract = &contract.accepted_contract;
-            let refund = accepted_contract.dlc_transactions.refund.clone();
-            let confirmations = self
-                .blockchain
-                .get_transaction_confirmations_async(&refund.txid())
-                .await?;
-            if confirmations == 0 {
-                let refund = crate::dlc_manager::contract_updater::get_signed_refund(
-                    &self.secp,
-                    contract,
-                    &self.wallet,
-                )?;
-                self.blockchain.send_transaction_async(&refund).await?;
-            }
-
-            self.store
-                .update_contract(&Contract::Refunded(contract.clone()))
-                .await?;
+            let refund = crate::dlc_manager::contract_updater::get_signed_refund(
+                &self.secp,
+                contract,
+                &self.wallet,
+            )?;
+            self.broadcast(&refund, TransactionType::Refund).await?;
         }
 
         Ok(())
@@ -842,30 +3952,86 @@ where
 
 #[cfg(test)]
 mod test {
+    const ONE_BTC: u64 = 100_000_000;
+
+    #[test]
+    fn test_get_protocol_fee() {
+        assert_eq!(super::get_protocol_fee(ONE_BTC, 100).ok(), Some(1_000_000));
+        assert_eq!(super::get_protocol_fee(ONE_BTC, 3).ok(), Some(30_000));
+        assert_eq!(super::get_protocol_fee(ONE_BTC, 0).ok(), Some(0));
+        assert_eq!(super::get_protocol_fee(9_999, 1).ok(), Some(0));
+        assert_eq!(
+            super::get_protocol_fee(u64::MAX, 10_000).ok(),
+            Some(u64::MAX)
+        );
+        assert!(super::get_protocol_fee(ONE_BTC, 10_001).is_err());
+    }
+
+    #[test]
+    fn test_get_fee_denominator() {
+        let denominator = super::get_fee_denominator(ONE_BTC, 1_000_000).ok();
+        assert_eq!(denominator, Some(100));
+
+        let denominator = super::get_fee_denominator(ONE_BTC, 100_000).ok();
+        assert_eq!(denominator, Some(1000));
+
+        let denominator = super::get_fee_denominator(ONE_BTC, 500_000).ok();
+        assert_eq!(denominator, Some(200));
+
+        let denominator = super::get_fee_denominator(ONE_BTC, 2_000_000).ok();
+        assert_eq!(denominator, Some(50));
+
+        let denominator = super::get_fee_denominator(ONE_BTC, 10_000_000).ok();
+        assert_eq!(denominator, Some(10));
+
+        let denominator = super::get_fee_denominator(ONE_BTC, ONE_BTC).ok();
+        assert_eq!(denominator, Some(1));
+
+        let denominator = super::get_fee_denominator(ONE_BTC, 0).ok();
+        assert_eq!(denominator, Some(0));
+
+        // 3 basis points of 10 000 sats can be charged exactly.
+        let denominator = super::get_fee_denominator(10_000, 3).ok();
+        assert_eq!(denominator, Some(3333));
+
+        // 3 basis points of 1 BTC cannot: 3333 charges 30,003 sats and 3334
+        // charges 29,994 sats.
+        assert!(super::get_fee_denominator(ONE_BTC, 30_000).is_err());
+    }
+
     #[test]
-    fn test_calculate_denominator_from_basis_points() {
-        let basis_points = 100;
-        let denominator = super::calculate_denominator_from_basis_points(basis_points);
-        assert_eq!(denominator, 100);
-
-        let basis_points = 10;
-        let denominator = super::calculate_denominator_from_basis_points(basis_points);
-        assert_eq!(denominator, 1000);
-
-        let basis_points = 50;
-        let denominator = super::calculate_denominator_from_basis_points(basis_points);
-        assert_eq!(denominator, 200);
-
-        let basis_points = 200;
-        let denominator = super::calculate_denominator_from_basis_points(basis_points);
-        assert_eq!(denominator, 50);
-
-        let basis_points = 1000;
-        let denominator = super::calculate_denominator_from_basis_points(basis_points);
-        assert_eq!(denominator, 10);
-
-        let basis_points = 0;
-        let denominator = super::calculate_denominator_from_basis_points(basis_points);
-        assert_eq!(denominator, 0);
+    fn test_get_charged_protocol_fee() {
+        assert_eq!(
+            super::get_charged_protocol_fee(ONE_BTC, 100).ok(),
+            Some(1_000_000)
+        );
+        assert_eq!(super::get_charged_protocol_fee(10_000, 3).ok(), Some(3));
+        assert!(super::get_charged_protocol_fee(ONE_BTC, 3).is_err());
+        assert_eq!(super::get_charged_protocol_fee(ONE_BTC, 0).ok(), Some(0));
+        assert!(super::get_charged_protocol_fee(ONE_BTC, 10_001).is_err());
+
+        for total_collateral in [10_000, 123_457, ONE_BTC, 21 * ONE_BTC + 1] {
+            // Basis points dividing 10,000 are always charged exactly.
+            for basis_points in [1, 2, 5, 8, 25, 80, 250, 625, 10_000] {
+                let fee =
+                    super::get_protocol_fee(total_collateral, basis_points).expect("a valid fee");
+                assert_eq!(
+                    super::get_charged_protocol_fee(total_collateral, basis_points).ok(),
+                    Some(fee)
+                );
+            }
+            // Others are charged exactly or refused.
+            for basis_points in [3, 7, 33, 99, 9_999] {
+                let fee =
+                    super::get_protocol_fee(total_collateral, basis_points).expect("a valid fee");
+                if let Ok(charged) = super::get_charged_protocol_fee(total_collateral, basis_points)
+                {
+                    assert_eq!(charged, fee);
+                    let denominator = super::get_fee_denominator(total_collateral, fee)
+                        .expect("a denominator for a charged fee");
+                    assert_eq!(total_collateral / denominator, fee);
+                }
+            }
+        }
     }
 }
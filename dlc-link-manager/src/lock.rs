@@ -0,0 +1,63 @@
+//! #ContractLocks per-contract locks preventing concurrent updates of a contract.
+
+use std::collections::HashSet;
+use std::sync::{Mutex, PoisonError};
+
+use dlc_manager::ContractId;
+
+/// The set of contracts currently being processed by the Manager.
+#[derive(Default)]
+pub(crate) struct ContractLocks {
+    locked: Mutex<HashSet<ContractId>>,
+}
+
+impl ContractLocks {
+    /// Returns a guard holding the lock on the given contract, or `None` if the
+    /// contract is already being processed.
+    pub(crate) fn try_lock(&self, contract_id: &ContractId) -> Option<ContractLock<'_>> {
+        let mut locked = self.locked.lock().unwrap_or_else(PoisonError::into_inner);
+        if !locked.insert(*contract_id) {
+            return None;
+        }
+        Some(ContractLock {
+            locks: self,
+            contract_id: *contract_id,
+        })
+    }
+}
+
+/// Releases the lock on a contract when dropped.
+pub(crate) struct ContractLock<'a> {
+    locks: &'a ContractLocks,
+    contract_id: ContractId,
+}
+
+impl Drop for ContractLock<'_> {
+    fn drop(&mut self) {
+        self.locks
+            .locked
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.contract_id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_contract_lock_is_exclusive_until_dropped() {
+        let locks = ContractLocks::default();
+        let first_id = [1u8; 32];
+        let second_id = [2u8; 32];
+
+        let lock = locks.try_lock(&first_id);
+        assert!(lock.is_some());
+        assert!(locks.try_lock(&first_id).is_none());
+        assert!(locks.try_lock(&second_id).is_some());
+
+        drop(lock);
+        assert!(locks.try_lock(&first_id).is_some());
+    }
+}
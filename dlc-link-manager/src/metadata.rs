@@ -0,0 +1,113 @@
+//! #ContractMetadata side record kept by the Manager for each contract.
+
+use bitcoin::{Transaction, Txid};
+use dlc_manager::ContractId;
+use dlc_messages::oracle_msgs::OracleAttestation;
+
+use crate::{CloseOffer, ContractFailure};
+
+/// Information the Manager tracks about a contract that does not fit in the
+/// contract record itself. Missing fields default when reading older records.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct ContractMetadata {
+    /// The funding, closing or refund transaction of the contract waiting for
+    /// its first confirmation.
+    pub unconfirmed_tx: Option<UnconfirmedTransaction>,
+    /// The child transactions broadcast to bump the fee of the transactions
+    /// of the contract, oldest first.
+    pub fee_bumps: Vec<FeeBump>,
+    /// The fully signed funding transaction, kept by the party that broadcasts
+    /// it so that it can be broadcast again.
+    pub signed_fund_tx: Option<Transaction>,
+    /// The broadcast attempts of a transaction the blockchain did not know
+    /// about.
+    pub rebroadcast: Option<Rebroadcast>,
+    /// The unix time at which the offer was created or received. Kept under
+    /// the temporary contract id, as it is set before the contract id is known.
+    pub created_at: Option<u64>,
+    /// The contract that replaced this one when it was renewed.
+    pub successor: Option<ContractId>,
+    /// The contract this one replaced when it was renewed.
+    pub predecessor: Option<ContractId>,
+    /// The conflicting attestations that prevent closing the contract.
+    pub oracle_disagreement: Option<OracleDisagreement>,
+    /// The latest failure of a FailedAccept or FailedSign contract.
+    pub failure: Option<ContractFailure>,
+    /// The latest close offer we made for the contract. The counter party
+    /// holds our signatures of the earlier ones and can accept any of them,
+    /// so a new offer never pays it less than this one.
+    pub close_offer: Option<CloseOffer>,
+    /// The fully signed mutual close transaction of the contract. Contracts
+    /// stay Confirmed until it gets the required confirmations, and keep it
+    /// here once Closed as it is not a CET.
+    pub mutual_close_tx: Option<Transaction>,
+    /// The unix time at which the accept party of a channel first found its
+    /// contract attested. The offer party then has [`PEER_TIMEOUT`] to offer
+    /// closing the channel cooperatively before it is closed unilaterally.
+    ///
+    /// [`PEER_TIMEOUT`]: crate::PEER_TIMEOUT
+    pub attested_at: Option<u64>,
+}
+
+/// A transaction of a contract that has not been confirmed yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnconfirmedTransaction {
+    /// The id of the transaction.
+    pub txid: Txid,
+    /// The block height at which the transaction was first seen unconfirmed.
+    pub since_height: u64,
+}
+
+/// A child-pays-for-parent transaction spending our output of a transaction of
+/// the contract.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FeeBump {
+    /// The id of the transaction whose fee is bumped.
+    pub parent_txid: Txid,
+    /// The id of the child transaction.
+    pub txid: Txid,
+    /// The fee paid by the child transaction, in satoshis.
+    pub fee: u64,
+    /// The block height at which the child transaction was broadcast.
+    pub height: u64,
+}
+
+/// Broadcast attempts of a transaction missing from the blockchain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rebroadcast {
+    /// The id of the transaction.
+    pub txid: Txid,
+    /// The number of broadcast attempts made so far.
+    pub attempts: u32,
+    /// The unix time of the latest attempt.
+    pub last_attempt: u64,
+}
+
+/// Valid attestations whose outcomes do not match, so that no CET of the
+/// contract can be signed with them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OracleDisagreement {
+    /// The unix time at which the disagreement was first detected.
+    pub detected_at: u64,
+    /// The attestations received when the disagreement was last checked.
+    pub attestations: Vec<OracleAttestation>,
+}
+
+impl ContractMetadata {
+    /// Returns the latest fee bump of the given transaction.
+    pub fn get_last_fee_bump(&self, parent_txid: &Txid) -> Option<&FeeBump> {
+        self.fee_bumps
+            .iter()
+            .rev()
+            .find(|bump| bump.parent_txid == *parent_txid)
+    }
+}
@@ -0,0 +1,247 @@
+//! #CloseOffer messages and transactions to close a contract cooperatively.
+//!
+//! The messages are not part of the DLC message set: applications exchange
+//! them with the counter party themselves and pass them to the
+//! [`Manager`](crate::Manager).
+
+use bitcoin::{
+    EcdsaSighashType, OutPoint, PackedLockTime, Script, Sequence, Transaction, TxIn, TxOut, Witness,
+};
+use dlc_manager::contract::accepted_contract::AcceptedContract;
+use dlc_manager::contract::signed_contract::SignedContract;
+use dlc_manager::error::Error;
+use dlc_manager::ContractId;
+use secp256k1_zkp::ecdsa::Signature;
+use secp256k1_zkp::{PublicKey, Secp256k1, SecretKey, Signing, Verification};
+
+use crate::DUST_LIMIT;
+
+/// Proposal to close a confirmed contract cooperatively, without waiting for
+/// the oracle attestation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CloseOffer {
+    /// The id of the contract to close.
+    pub contract_id: ContractId,
+    /// The amount paid to the offer party of the contract. The accept party
+    /// receives the rest of the total collateral.
+    pub offer_payout: u64,
+    /// The signature of the close transaction by the proposing party.
+    pub close_signature: Signature,
+}
+
+/// Acceptance of a [`CloseOffer`], carrying the original offer so that the
+/// proposing party can check it against its own signature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CloseAccept {
+    /// The accepted close offer.
+    pub close_offer: CloseOffer,
+    /// The signature of the close transaction by the accepting party.
+    pub close_signature: Signature,
+}
+
+/// Returns the funding outpoint of the contract, the value locked in it and
+/// its witness script.
+pub(crate) fn get_fund_output(
+    contract: &AcceptedContract,
+) -> Result<(OutPoint, u64, &Script), Error> {
+    let dlc_transactions = &contract.dlc_transactions;
+    let fund_outpoint = dlc_transactions
+        .refund
+        .input
+        .first()
+        .map(|input| input.previous_output)
+        .ok_or(Error::InvalidState(
+            "Refund transaction has no input".to_string(),
+        ))?;
+    let fund_value = dlc_transactions
+        .fund
+        .output
+        .get(fund_outpoint.vout as usize)
+        .map(|output| output.value)
+        .ok_or(Error::InvalidState("Funding output not found".to_string()))?;
+    Ok((
+        fund_outpoint,
+        fund_value,
+        &dlc_transactions.funding_script_pubkey,
+    ))
+}
+
+/// Returns the funding public keys of the local party and of the counter party.
+pub(crate) fn get_fund_pubkeys(contract: &SignedContract) -> (PublicKey, PublicKey) {
+    let accepted_contract = &contract.accepted_contract;
+    let offer_fund_pubkey = accepted_contract.offered_contract.offer_params.fund_pubkey;
+    let accept_fund_pubkey = accepted_contract.accept_params.fund_pubkey;
+    if accepted_contract.offered_contract.is_offer_party {
+        (offer_fund_pubkey, accept_fund_pubkey)
+    } else {
+        (accept_fund_pubkey, offer_fund_pubkey)
+    }
+}
+
+/// Returns the script the local party is paid to by the transactions of the
+/// contract.
+pub(crate) fn get_own_payout_script(contract: &AcceptedContract) -> &Script {
+    if contract.offered_contract.is_offer_party {
+        &contract.offered_contract.offer_params.payout_script_pubkey
+    } else {
+        &contract.accept_params.payout_script_pubkey
+    }
+}
+
+/// Returns what the transaction pays to the local party.
+pub(crate) fn get_own_payout(contract: &AcceptedContract, tx: &Transaction) -> u64 {
+    let own_payout_script = get_own_payout_script(contract);
+    tx.output
+        .iter()
+        .filter(|output| output.script_pubkey == *own_payout_script)
+        .map(|output| output.value)
+        .sum()
+}
+
+/// Returns what a close offer paying `offer_payout` to the offer party pays to
+/// the counter party of the local party.
+pub(crate) fn get_counter_payout(contract: &AcceptedContract, offer_payout: u64) -> u64 {
+    let offered_contract = &contract.offered_contract;
+    if offered_contract.is_offer_party {
+        offered_contract
+            .total_collateral
+            .saturating_sub(offer_payout)
+    } else {
+        offer_payout
+    }
+}
+
+/// Builds the unsigned transaction splitting the funding output of the contract,
+/// paying `offer_payout` to the offer party and the rest of the total
+/// collateral to the accept party. As with CETs, the difference between the
+/// funding output and the total collateral pays for the fees.
+pub(crate) fn build_close_transaction(
+    contract: &SignedContract,
+    offer_payout: u64,
+) -> Result<Transaction, Error> {
+    let accepted_contract = &contract.accepted_contract;
+    let offered_contract = &accepted_contract.offered_contract;
+    let accept_payout = offered_contract
+        .total_collateral
+        .checked_sub(offer_payout)
+        .ok_or(Error::InvalidParameters(
+            "Offer payout exceeds the total collateral".to_string(),
+        ))?;
+    let (fund_outpoint, _, _) = get_fund_output(&contract.accepted_contract)?;
+
+    let mut outputs = vec![
+        (
+            offered_contract.offer_params.payout_serial_id,
+            TxOut {
+                value: offer_payout,
+                script_pubkey: offered_contract.offer_params.payout_script_pubkey.clone(),
+            },
+        ),
+        (
+            accepted_contract.accept_params.payout_serial_id,
+            TxOut {
+                value: accept_payout,
+                script_pubkey: accepted_contract.accept_params.payout_script_pubkey.clone(),
+            },
+        ),
+    ];
+    outputs.retain(|(_, output)| output.value >= DUST_LIMIT);
+    outputs.sort_by_key(|(serial_id, _)| *serial_id);
+
+    Ok(Transaction {
+        version: 2,
+        lock_time: PackedLockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: fund_outpoint,
+            script_sig: Script::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::default(),
+        }],
+        output: outputs.into_iter().map(|(_, output)| output).collect(),
+    })
+}
+
+/// Signs the funding input of the close transaction.
+pub(crate) fn sign_close_transaction<C: Signing>(
+    secp: &Secp256k1<C>,
+    contract: &SignedContract,
+    close_tx: &Transaction,
+    fund_secret_key: &SecretKey,
+) -> Result<Signature, Error> {
+    let (_, fund_value, funding_script) = get_fund_output(&contract.accepted_contract)?;
+    Ok(dlc::util::get_raw_sig_for_tx_input(
+        secp,
+        close_tx,
+        0,
+        funding_script,
+        fund_value,
+        fund_secret_key,
+    )?)
+}
+
+/// Verifies a signature of the funding input of the close transaction.
+pub(crate) fn verify_close_signature<C: Verification>(
+    secp: &Secp256k1<C>,
+    contract: &SignedContract,
+    close_tx: &Transaction,
+    signature: &Signature,
+    fund_pubkey: &PublicKey,
+) -> Result<(), Error> {
+    let (_, fund_value, funding_script) = get_fund_output(&contract.accepted_contract)?;
+    dlc::verify_tx_input_sig(
+        secp,
+        signature,
+        close_tx,
+        0,
+        funding_script,
+        fund_value,
+        fund_pubkey,
+    )
+    .map_err(|_| Error::InvalidParameters("Invalid close transaction signature".to_string()))
+}
+
+/// Adds the witness spending the 2-of-2 funding output to the close transaction.
+pub(crate) fn finalize_close_transaction(
+    contract: &SignedContract,
+    close_tx: &mut Transaction,
+    signatures: [(PublicKey, Signature); 2],
+) -> Result<(), Error> {
+    let (_, _, funding_script) = get_fund_output(&contract.accepted_contract)?;
+    let mut signatures = signatures;
+    // The funding script lists the public keys in ascending order, and
+    // OP_CHECKMULTISIG expects the signatures in the same order.
+    signatures.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let mut witness = vec![Vec::new()];
+    for (_, signature) in signatures.iter() {
+        let mut serialized = signature.serialize_der().to_vec();
+        serialized.push(EcdsaSighashType::All as u8);
+        witness.push(serialized);
+    }
+    witness.push(funding_script.to_bytes());
+    close_tx.input[0].witness = Witness::from_vec(witness);
+    Ok(())
+}
+
+/// Returns true if the given transaction spends the funding output of the
+/// contract with a signature of the local party, which it only gives for
+/// mutual closes besides CETs and the refund transaction.
+pub(crate) fn is_mutual_close_transaction<C: Verification>(
+    secp: &Secp256k1<C>,
+    contract: &SignedContract,
+    tx: &Transaction,
+) -> bool {
+    let (own_fund_pubkey, _) = get_fund_pubkeys(contract);
+    let input = match tx.input.as_slice() {
+        [input] => input,
+        _ => return false,
+    };
+    input.witness.iter().any(|item| {
+        item.split_last()
+            .and_then(|(_, der)| Signature::from_der(der).ok())
+            .map_or(false, |signature| {
+                verify_close_signature(secp, contract, tx, &signature, &own_fund_pubkey).is_ok()
+            })
+    })
+}
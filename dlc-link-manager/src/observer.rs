@@ -0,0 +1,138 @@
+//! #ContractObserver hooks notified of contract state transitions.
+
+use bitcoin::Txid;
+use dlc_manager::contract::Contract;
+use dlc_manager::ContractId;
+
+use crate::{get_event_id, OracleDisagreement};
+
+/// The state of a contract, without its content.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ContractState {
+    Offered,
+    Accepted,
+    Signed,
+    Confirmed,
+    PreClosed,
+    Closed,
+    Refunded,
+    FailedAccept,
+    FailedSign,
+    Rejected,
+}
+
+impl From<&Contract> for ContractState {
+    fn from(contract: &Contract) -> Self {
+        match contract {
+            Contract::Offered(_) => ContractState::Offered,
+            Contract::Accepted(_) => ContractState::Accepted,
+            Contract::Signed(_) => ContractState::Signed,
+            Contract::Confirmed(_) => ContractState::Confirmed,
+            Contract::PreClosed(_) => ContractState::PreClosed,
+            Contract::Closed(_) => ContractState::Closed,
+            Contract::Refunded(_) => ContractState::Refunded,
+            Contract::FailedAccept(_) => ContractState::FailedAccept,
+            Contract::FailedSign(_) => ContractState::FailedSign,
+            Contract::Rejected(_) => ContractState::Rejected,
+        }
+    }
+}
+
+/// A change of state of a contract.
+#[derive(Clone, Debug)]
+pub struct ContractTransition {
+    /// The id of the contract, which is the temporary id for offered contracts.
+    pub contract_id: ContractId,
+    /// The oracle event id of the contract. Closed contracts report the event
+    /// id of the contract they replace.
+    pub event_id: Option<String>,
+    /// The state before the transition, `None` for newly created contracts.
+    pub previous_state: Option<ContractState>,
+    /// The state after the transition.
+    pub new_state: ContractState,
+    /// The transactions relevant to the new state: the funding transaction
+    /// from the Accepted state on, followed by the CET or refund transaction
+    /// once closing. Closed contracts only report their CET or mutual close
+    /// transaction.
+    pub txids: Vec<Txid>,
+}
+
+impl ContractTransition {
+    pub(crate) fn new(previous_state: Option<ContractState>, contract: &Contract) -> Self {
+        ContractTransition {
+            contract_id: contract.get_id(),
+            event_id: get_contract_event_id(contract),
+            previous_state,
+            new_state: contract.into(),
+            txids: get_txids(contract),
+        }
+    }
+}
+
+/// Observer notified by the [`Manager`](crate::Manager) every time it moves a
+/// contract to a new state.
+pub trait ContractObserver {
+    /// Called after the new state of the contract has been persisted.
+    fn on_transition(&self, transition: &ContractTransition);
+
+    /// Called before a contract record is deleted, so that it can be archived.
+    fn on_delete(&self, _contract: &Contract) {}
+
+    /// Called when the oracles of a confirmed contract attest outcomes that do
+    /// not match, or attest again differently, after the disagreement has been
+    /// persisted in the contract metadata.
+    fn on_oracle_disagreement(
+        &self,
+        _contract_id: &ContractId,
+        _disagreement: &OracleDisagreement,
+    ) {
+    }
+}
+
+/// Returns the event id of the first oracle of the contract, `None` for closed
+/// contracts which only keep their ids and closing transaction.
+pub(crate) fn get_contract_event_id(contract: &Contract) -> Option<String> {
+    let contract_info = match contract {
+        Contract::Offered(c) | Contract::Rejected(c) => &c.contract_info,
+        Contract::Accepted(c) => &c.offered_contract.contract_info,
+        Contract::Signed(c) | Contract::Confirmed(c) | Contract::Refunded(c) => {
+            &c.accepted_contract.offered_contract.contract_info
+        }
+        Contract::PreClosed(c) => {
+            &c.signed_contract
+                .accepted_contract
+                .offered_contract
+                .contract_info
+        }
+        Contract::FailedAccept(c) => &c.offered_contract.contract_info,
+        Contract::FailedSign(c) => &c.accepted_contract.offered_contract.contract_info,
+        Contract::Closed(_) => return None,
+    };
+    get_event_id(contract_info).map(ToString::to_string)
+}
+
+fn get_txids(contract: &Contract) -> Vec<Txid> {
+    match contract {
+        Contract::Accepted(c) => vec![c.dlc_transactions.fund.txid()],
+        Contract::Signed(c) | Contract::Confirmed(c) => {
+            vec![c.accepted_contract.dlc_transactions.fund.txid()]
+        }
+        Contract::Refunded(c) => vec![
+            c.accepted_contract.dlc_transactions.fund.txid(),
+            c.accepted_contract.dlc_transactions.refund.txid(),
+        ],
+        Contract::PreClosed(c) => vec![
+            c.signed_contract
+                .accepted_contract
+                .dlc_transactions
+                .fund
+                .txid(),
+            c.signed_cet.txid(),
+        ],
+        Contract::Closed(c) => c.signed_cet.iter().map(|cet| cet.txid()).collect(),
+        Contract::Offered(_)
+        | Contract::Rejected(_)
+        | Contract::FailedAccept(_)
+        | Contract::FailedSign(_) => Vec::new(),
+    }
+}
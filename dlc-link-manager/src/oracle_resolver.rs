@@ -0,0 +1,24 @@
+//! #OracleResolver lookup of the oracles announced in contracts.
+
+use dlc_manager::error::Error;
+use secp256k1_zkp::XOnlyPublicKey;
+
+/// Provides a client for an oracle announced in a contract when the Manager's
+/// own oracles do not include it, for instance because the oracle was removed
+/// from the configured set after the contract was offered.
+pub trait OracleResolver<O> {
+    /// Returns a client for the oracle with the given public key, or `None` if
+    /// the oracle cannot be found.
+    async fn resolve_oracle(&self, public_key: &XOnlyPublicKey) -> Result<Option<O>, Error>;
+}
+
+/// An [`OracleResolver`] that never finds any oracle, so that only the
+/// Manager's own oracles are used.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoOracleResolver;
+
+impl<O> OracleResolver<O> for NoOracleResolver {
+    async fn resolve_oracle(&self, _public_key: &XOnlyPublicKey) -> Result<Option<O>, Error> {
+        Ok(None)
+    }
+}
@@ -0,0 +1,312 @@
+//! #PayoutTable what each party receives for the outcomes of a contract.
+
+use dlc::Payout;
+use dlc_manager::contract::accepted_contract::AcceptedContract;
+use dlc_manager::contract::contract_info::ContractInfo;
+use dlc_manager::contract::ContractDescriptor;
+use dlc_manager::error::Error;
+use dlc_manager::ContractId;
+
+use crate::mutual_close::get_fund_output;
+use crate::policy::get_paid_protocol_fee;
+use crate::{ContractState, DUST_LIMIT};
+
+/// An outcome of the oracle event of a contract.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// An outcome of an enumerated contract.
+    Enumerated(String),
+    /// An outcome of a numerical contract.
+    Numerical(u64),
+}
+
+/// The outcomes a payout applies to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PayoutOutcomes {
+    /// A single outcome of an enumerated contract.
+    Enumerated(String),
+    /// The outcomes from `start` to `end`, both included, of a numerical
+    /// contract.
+    Range { start: u64, end: u64 },
+}
+
+impl PayoutOutcomes {
+    /// Returns true if the given outcome is covered.
+    pub fn contains(&self, outcome: &Outcome) -> bool {
+        match (self, outcome) {
+            (PayoutOutcomes::Enumerated(a), Outcome::Enumerated(b)) => a == b,
+            (PayoutOutcomes::Range { start, end }, Outcome::Numerical(value)) => {
+                start <= value && value <= end
+            }
+            _ => false,
+        }
+    }
+}
+
+/// What each party receives from the CET of some outcomes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PayoutEntry {
+    /// The outcomes paying out this way.
+    pub outcomes: PayoutOutcomes,
+    /// The amount received by the offer party, in satoshis.
+    pub offer_payout: u64,
+    /// The amount received by the accept party, in satoshis.
+    pub accept_payout: u64,
+    /// The fee paid by the CET, including the payouts below the dust limit
+    /// that are not created.
+    pub cet_fee: u64,
+}
+
+/// The payouts of every outcome of an oracle event of a contract.
+#[derive(Clone, Debug)]
+pub struct ContractInfoPayouts {
+    /// The id of the oracle event the payouts depend on.
+    pub event_id: String,
+    /// The payout entries, in outcome order for numerical contracts.
+    pub entries: Vec<PayoutEntry>,
+}
+
+/// The payouts of every outcome of a contract, with one set of entries per
+/// contract info as the contract has CETs for each of them.
+#[derive(Clone, Debug)]
+pub struct PayoutTable {
+    /// The id of the contract.
+    pub contract_id: ContractId,
+    /// The collateral of both parties, in satoshis.
+    pub total_collateral: u64,
+    /// The value locked in the funding output, covering the collateral and
+    /// the CET fees.
+    pub fund_value: u64,
+    /// The protocol fee paid by the funding transaction, in satoshis.
+    pub protocol_fee: u64,
+    /// The payouts of each contract info, in the order of the offer.
+    pub contract_infos: Vec<ContractInfoPayouts>,
+}
+
+impl PayoutTable {
+    /// Builds the payout table of a contract.
+    pub(crate) fn new(contract: &AcceptedContract) -> Result<Self, Error> {
+        let offered_contract = &contract.offered_contract;
+        let total_collateral = offered_contract.total_collateral;
+        let (_, fund_value, _) = get_fund_output(contract)?;
+        if offered_contract.contract_info.is_empty() {
+            return Err(Error::InvalidState(
+                "Contract has no contract info".to_string(),
+            ));
+        }
+
+        let contract_infos = offered_contract
+            .contract_info
+            .iter()
+            .map(|contract_info| {
+                build_contract_info_payouts(contract_info, total_collateral, fund_value)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(PayoutTable {
+            contract_id: contract.get_contract_id(),
+            total_collateral,
+            fund_value,
+            protocol_fee: get_paid_protocol_fee(contract),
+            contract_infos,
+        })
+    }
+
+    /// Returns the payout entry of the given outcome of the given event.
+    pub fn get_entry(&self, event_id: &str, outcome: &Outcome) -> Option<&PayoutEntry> {
+        self.contract_infos
+            .iter()
+            .filter(|contract_info| contract_info.event_id == event_id)
+            .flat_map(|contract_info| contract_info.entries.iter())
+            .find(|entry| entry.outcomes.contains(outcome))
+    }
+
+    /// Returns the number of payout entries over every contract info, which
+    /// is the number of CETs of the contract.
+    fn get_entry_count(&self) -> usize {
+        self.contract_infos
+            .iter()
+            .map(|contract_info| contract_info.entries.len())
+            .sum()
+    }
+}
+
+/// The possible profit and loss of a party in an open contract.
+#[derive(Clone, Debug)]
+pub struct ContractExposure {
+    /// The id of the contract.
+    pub contract_id: ContractId,
+    /// The state of the contract.
+    pub state: ContractState,
+    /// Our collateral in the contract, in satoshis.
+    pub collateral: u64,
+    /// Our lowest profit and loss over the CETs and the refund transaction.
+    pub worst_pnl: i64,
+    /// Our highest profit and loss over the CETs and the refund transaction.
+    pub best_pnl: i64,
+}
+
+impl ContractExposure {
+    /// Computes the exposure of a contract that can still close through any of
+    /// its CETs or its refund transaction. Fails if the CETs do not match the
+    /// payouts of every contract info, as the exposure would miss outcomes.
+    pub(crate) fn new(contract: &AcceptedContract, state: ContractState) -> Result<Self, Error> {
+        let dlc_transactions = &contract.dlc_transactions;
+        let payout_count = PayoutTable::new(contract)?.get_entry_count();
+        if dlc_transactions.cets.len() != payout_count {
+            return Err(Error::InvalidState(format!(
+                "Contract {} has {} CETs for {} payouts",
+                contract.get_contract_id_string(),
+                dlc_transactions.cets.len(),
+                payout_count
+            )));
+        }
+        let (worst_pnl, best_pnl) = dlc_transactions
+            .cets
+            .iter()
+            .chain(std::iter::once(&dlc_transactions.refund))
+            .map(|tx| contract.compute_pnl(tx))
+            .fold((i64::MAX, i64::MIN), |(worst, best), pnl| {
+                (worst.min(pnl), best.max(pnl))
+            });
+        Ok(ContractExposure {
+            contract_id: contract.get_contract_id(),
+            state,
+            collateral: get_own_collateral(contract),
+            worst_pnl,
+            best_pnl,
+        })
+    }
+
+    /// Computes the exposure of a contract whose closing transaction was
+    /// already broadcast.
+    pub(crate) fn new_closing(
+        contract: &AcceptedContract,
+        closing_tx: &bitcoin::Transaction,
+    ) -> Self {
+        let pnl = contract.compute_pnl(closing_tx);
+        ContractExposure {
+            contract_id: contract.get_contract_id(),
+            state: ContractState::PreClosed,
+            collateral: get_own_collateral(contract),
+            worst_pnl: pnl,
+            best_pnl: pnl,
+        }
+    }
+}
+
+/// The aggregate exposure over all open contracts.
+#[derive(Clone, Debug, Default)]
+pub struct ExposureSummary {
+    /// The exposure of each open contract.
+    pub contracts: Vec<ContractExposure>,
+    /// Our collateral locked in open contracts, in satoshis.
+    pub total_collateral: u64,
+    /// The sum of the lowest profit and loss of each contract.
+    pub worst_pnl: i64,
+    /// The sum of the highest profit and loss of each contract.
+    pub best_pnl: i64,
+}
+
+impl ExposureSummary {
+    pub(crate) fn add(&mut self, exposure: ContractExposure) {
+        self.total_collateral += exposure.collateral;
+        self.worst_pnl = self.worst_pnl.saturating_add(exposure.worst_pnl);
+        self.best_pnl = self.best_pnl.saturating_add(exposure.best_pnl);
+        self.contracts.push(exposure);
+    }
+}
+
+fn get_own_collateral(contract: &AcceptedContract) -> u64 {
+    if contract.offered_contract.is_offer_party {
+        contract.offered_contract.offer_params.collateral
+    } else {
+        contract.accept_params.collateral
+    }
+}
+
+/// Returns the payout entries of a contract info.
+fn build_contract_info_payouts(
+    contract_info: &ContractInfo,
+    total_collateral: u64,
+    fund_value: u64,
+) -> Result<ContractInfoPayouts, Error> {
+    let event_id = contract_info
+        .oracle_announcements
+        .first()
+        .map(|announcement| announcement.oracle_event.event_id.clone())
+        .ok_or(Error::InvalidState(
+            "Contract info has no oracle announcement".to_string(),
+        ))?;
+
+    let entries = match &contract_info.contract_descriptor {
+        ContractDescriptor::Enum(descriptor) => descriptor
+            .outcome_payouts
+            .iter()
+            .map(|outcome_payout| {
+                build_entry(
+                    PayoutOutcomes::Enumerated(outcome_payout.outcome.clone()),
+                    &outcome_payout.payout,
+                    fund_value,
+                )
+            })
+            .collect(),
+        ContractDescriptor::Numerical(descriptor) => descriptor
+            .get_range_payouts(total_collateral)?
+            .iter()
+            .map(|range_payout| {
+                let end = range_payout.start + range_payout.count.saturating_sub(1);
+                build_entry(
+                    PayoutOutcomes::Range {
+                        start: range_payout.start as u64,
+                        end: end as u64,
+                    },
+                    &range_payout.payout,
+                    fund_value,
+                )
+            })
+            .collect(),
+    };
+
+    Ok(ContractInfoPayouts { event_id, entries })
+}
+
+/// Returns the entry for a payout, dropping the outputs below the dust limit
+/// as CETs do.
+fn build_entry(outcomes: PayoutOutcomes, payout: &Payout, fund_value: u64) -> PayoutEntry {
+    let above_dust = |value: u64| if value >= DUST_LIMIT { value } else { 0 };
+    let offer_payout = above_dust(payout.offer);
+    let accept_payout = above_dust(payout.accept);
+    PayoutEntry {
+        outcomes,
+        offer_payout,
+        accept_payout,
+        cet_fee: fund_value.saturating_sub(offer_payout + accept_payout),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_entry_drops_dust() {
+        let outcomes = PayoutOutcomes::Range { start: 0, end: 9 };
+        let entry = build_entry(
+            outcomes.clone(),
+            &Payout {
+                offer: 99_500,
+                accept: 500,
+            },
+            100_300,
+        );
+        assert_eq!(entry.offer_payout, 99_500);
+        assert_eq!(entry.accept_payout, 0);
+        assert_eq!(entry.cet_fee, 800);
+        assert!(entry.outcomes.contains(&Outcome::Numerical(9)));
+        assert!(!entry.outcomes.contains(&Outcome::Numerical(10)));
+        assert!(!entry
+            .outcomes
+            .contains(&Outcome::Enumerated("9".to_string())));
+    }
+}
@@ -0,0 +1,195 @@
+//! #AcceptPolicy limits on the offers the Manager accepts.
+
+use bitcoin::{Script, Transaction};
+use dlc_manager::contract::accepted_contract::AcceptedContract;
+use dlc_manager::contract::offered_contract::OfferedContract;
+use dlc_manager::contract::ContractDescriptor;
+use dlc_manager::error::Error;
+use dlc_manager::payout_curve::PayoutFunctionPiece;
+use secp256k1_zkp::XOnlyPublicKey;
+
+/// The kind of payout curve an offer must use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PayoutCurveShape {
+    /// Contracts over enumerated outcomes.
+    Enumerated,
+    /// Numerical contracts with any payout function.
+    Numerical,
+    /// Numerical contracts whose payout function is only made of polynomial
+    /// pieces.
+    Polynomial,
+}
+
+/// Limits on received offers, checked when an offer is received and again
+/// when accepting it. Every limit left to `None` is not enforced. The refund
+/// delay is bounded by [`ManagerConfig::min_refund_delay`] and
+/// [`ManagerConfig::max_refund_delay`] instead, which the offer validation
+/// checks.
+///
+/// [`ManagerConfig::min_refund_delay`]: crate::ManagerConfig::min_refund_delay
+/// [`ManagerConfig::max_refund_delay`]: crate::ManagerConfig::max_refund_delay
+#[derive(Clone, Debug, Default)]
+pub struct AcceptPolicy {
+    /// The oracles whose announcements offers may use.
+    pub allowed_oracle_pubkeys: Option<Vec<XOnlyPublicKey>>,
+    /// The maximum collateral, in satoshis, we put in a contract.
+    pub max_collateral: Option<u64>,
+    /// The maximum fee rate, in sats per vbyte, of the contract transactions.
+    pub max_fee_rate: Option<u64>,
+    /// The payout curve shape offers must use.
+    pub payout_curve_shape: Option<PayoutCurveShape>,
+    /// The maximum protocol fee paid by the funding transaction, in basis
+    /// points of the total collateral.
+    pub max_protocol_fee_basis_points: Option<u64>,
+}
+
+impl AcceptPolicy {
+    /// Checks the offer against the policy, listing every violated limit in
+    /// the returned error.
+    pub fn check_offer(&self, offered_contract: &OfferedContract) -> Result<(), Error> {
+        let mut violations = Vec::new();
+
+        if let Some(allowed) = &self.allowed_oracle_pubkeys {
+            for announcement in offered_contract
+                .contract_info
+                .iter()
+                .flat_map(|info| info.oracle_announcements.iter())
+            {
+                if !allowed.contains(&announcement.oracle_public_key) {
+                    violations.push(format!(
+                        "oracle {} is not allowed",
+                        announcement.oracle_public_key
+                    ));
+                }
+            }
+        }
+
+        let accept_collateral = offered_contract
+            .total_collateral
+            .saturating_sub(offered_contract.offer_params.collateral);
+        if let Some(max_collateral) = self.max_collateral {
+            if accept_collateral > max_collateral {
+                violations.push(format!(
+                    "collateral of {} sats exceeds the maximum of {} sats",
+                    accept_collateral, max_collateral
+                ));
+            }
+        }
+
+        if let Some(max_fee_rate) = self.max_fee_rate {
+            if offered_contract.fee_rate_per_vb > max_fee_rate {
+                violations.push(format!(
+                    "fee rate of {} sats/vbyte exceeds the maximum of {} sats/vbyte",
+                    offered_contract.fee_rate_per_vb, max_fee_rate
+                ));
+            }
+        }
+
+        if let Some(shape) = self.payout_curve_shape {
+            for info in &offered_contract.contract_info {
+                if !has_shape(&info.contract_descriptor, shape) {
+                    violations.push(format!("payout curve is not {:?}", shape));
+                }
+            }
+        }
+
+        into_result(violations)
+    }
+
+    /// Checks the protocol fee paid by the funding transaction of the accepted
+    /// contract against the policy. The funding transaction is only built
+    /// when accepting, after the wallet selected our funding inputs, so this
+    /// is the only limit not checked on the offer.
+    pub fn check_protocol_fee(&self, accepted_contract: &AcceptedContract) -> Result<(), Error> {
+        if let Some(max_basis_points) = self.max_protocol_fee_basis_points {
+            let total_collateral = accepted_contract.offered_contract.total_collateral;
+            let protocol_fee = get_paid_protocol_fee(accepted_contract);
+            if exceeds_basis_points(protocol_fee, total_collateral, max_basis_points) {
+                return into_result(vec![format!(
+                    "protocol fee of {} sats exceeds {} basis points of the {} sats collateral",
+                    protocol_fee, max_basis_points, total_collateral
+                )]);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the value paid by the funding transaction to outputs other than
+/// the funding output and the change outputs of both parties.
+pub(crate) fn get_paid_protocol_fee(accepted_contract: &AcceptedContract) -> u64 {
+    let dlc_transactions = &accepted_contract.dlc_transactions;
+    let known_scripts = [
+        dlc_transactions.funding_script_pubkey.to_v0_p2wsh(),
+        accepted_contract
+            .offered_contract
+            .offer_params
+            .change_script_pubkey
+            .clone(),
+        accepted_contract.accept_params.change_script_pubkey.clone(),
+    ];
+    sum_outputs_except(&dlc_transactions.fund, &known_scripts)
+}
+
+fn sum_outputs_except(tx: &Transaction, scripts: &[Script]) -> u64 {
+    tx.output
+        .iter()
+        .filter(|output| !scripts.contains(&output.script_pubkey))
+        .map(|output| output.value)
+        .sum()
+}
+
+/// Returns true if `amount` is more than `basis_points` of `total`, without
+/// rounding.
+fn exceeds_basis_points(amount: u64, total: u64, basis_points: u64) -> bool {
+    amount as u128 * 10_000 > total as u128 * basis_points as u128
+}
+
+fn has_shape(contract_descriptor: &ContractDescriptor, shape: PayoutCurveShape) -> bool {
+    match (contract_descriptor, shape) {
+        (ContractDescriptor::Enum(_), PayoutCurveShape::Enumerated) => true,
+        (ContractDescriptor::Numerical(_), PayoutCurveShape::Numerical) => true,
+        (ContractDescriptor::Numerical(descriptor), PayoutCurveShape::Polynomial) => descriptor
+            .payout_function
+            .payout_function_pieces
+            .iter()
+            .all(|piece| matches!(piece, PayoutFunctionPiece::PolynomialPayoutCurvePiece(_))),
+        _ => false,
+    }
+}
+
+fn into_result(violations: Vec<String>) -> Result<(), Error> {
+    if violations.is_empty() {
+        return Ok(());
+    }
+    Err(Error::InvalidParameters(format!(
+        "Offer rejected by accept policy: {}",
+        violations.join("; ")
+    )))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_exceeds_basis_points() {
+        assert!(!exceeds_basis_points(100, 10_000, 100));
+        assert!(exceeds_basis_points(101, 10_000, 100));
+        assert!(!exceeds_basis_points(0, 0, 0));
+        assert!(exceeds_basis_points(1, 100_000, 0));
+        assert!(!exceeds_basis_points(u64::MAX / 2, u64::MAX, 10_000));
+    }
+
+    #[test]
+    fn test_policy_violations_are_listed() {
+        assert!(into_result(Vec::new()).is_ok());
+        match into_result(vec!["a".to_string(), "b".to_string()]) {
+            Err(Error::InvalidParameters(message)) => {
+                assert_eq!(message, "Offer rejected by accept policy: a; b")
+            }
+            _ => panic!("expected an invalid parameters error"),
+        }
+    }
+}
@@ -0,0 +1,315 @@
+//! #PeriodicCheckReport the outcome of a periodic check of the contracts.
+
+use bitcoin::Txid;
+use dlc_manager::contract::{signed_contract::SignedContract, Contract};
+use dlc_manager::error::Error;
+use dlc_manager::{ChannelId, ContractId};
+use dlc_messages::channel::CollaborativeCloseOffer;
+use secp256k1_zkp::PublicKey;
+use tracing::{error, warn};
+
+use crate::{get_event_id, AttestationState, AttestationStatus, ContractState};
+
+/// A contract moved to a new state during a periodic check.
+#[derive(Clone, Debug)]
+pub struct ContractUpdate {
+    /// The id of the contract.
+    pub contract_id: ContractId,
+    /// The oracle event id of the contract.
+    pub event_id: String,
+    /// The transaction that triggered the update: the funding transaction for
+    /// confirmed contracts, the CET for pre-closed contracts, the CET or mutual
+    /// close transaction for closed contracts and the refund transaction for
+    /// refunded contracts.
+    pub txid: Txid,
+}
+
+/// A funding output spent by a transaction that is neither a CET, the refund
+/// transaction nor a mutual close transaction of its contract.
+#[derive(Clone, Debug)]
+pub struct UnexpectedSpend {
+    /// The id of the contract.
+    pub contract_id: ContractId,
+    /// The id of the transaction spending the funding output.
+    pub txid: Txid,
+}
+
+/// An error encountered while checking a single contract.
+#[derive(Debug)]
+pub struct ContractCheckError {
+    /// The id of the contract.
+    pub contract_id: ContractId,
+    /// The state of the contract when the check failed.
+    pub state: ContractState,
+    /// The cause of the failure.
+    pub cause: Error,
+}
+
+/// An error encountered while checking a single channel.
+#[derive(Debug)]
+pub struct ChannelCheckError {
+    /// The id of the channel.
+    pub channel_id: ChannelId,
+    /// The cause of the failure.
+    pub cause: Error,
+}
+
+/// A collaborative close offered by a periodic check because the contract of
+/// the channel got attested. The application sends it to the counter party.
+#[derive(Clone, Debug)]
+pub struct ChannelCloseOffer {
+    /// The id of the channel.
+    pub channel_id: ChannelId,
+    /// The counter party of the channel.
+    pub counter_party: PublicKey,
+    /// The message to send to the counter party.
+    pub close_offer: CollaborativeCloseOffer,
+}
+
+/// The contracts updated by a periodic check, grouped by the kind of
+/// transition, together with the contracts that could not be checked.
+#[derive(Debug, Default)]
+pub struct PeriodicCheckReport {
+    /// Contracts whose funding transaction reached the required confirmations.
+    pub confirmed: Vec<ContractUpdate>,
+    /// Contracts whose CET was broadcast but is not yet deeply confirmed.
+    /// Mutually closed contracts stay Confirmed until their close transaction
+    /// is.
+    pub pre_closed: Vec<ContractUpdate>,
+    /// Contracts whose CET or mutual close transaction reached the required
+    /// confirmations.
+    pub closed: Vec<ContractUpdate>,
+    /// Contracts that were refunded.
+    pub refunded: Vec<ContractUpdate>,
+    /// Contracts whose funding output was spent by an unknown transaction.
+    pub unexpected_spends: Vec<UnexpectedSpend>,
+    /// Offers that were never signed and moved to the Rejected state.
+    pub expired_offers: Vec<ContractId>,
+    /// Failed contracts whose record was deleted.
+    pub deleted: Vec<ContractId>,
+    /// The attestations gathered for confirmed contracts whose event was
+    /// attested by at least one oracle, listing the oracles that were missing
+    /// or disagreed.
+    pub attestations: Vec<AttestationStatus>,
+    /// Confirmed contracts whose oracles attested outcomes that do not match,
+    /// so that they cannot close before their refund.
+    pub oracle_disagreements: Vec<ContractId>,
+    /// Contracts that could not be checked.
+    pub errors: Vec<ContractCheckError>,
+    /// Channels whose contract got attested, offered to be closed
+    /// cooperatively according to the attested outcome.
+    pub close_offers: Vec<ChannelCloseOffer>,
+    /// Channels closed unilaterally, because the counter party did not answer
+    /// an update or a close offer within [`PEER_TIMEOUT`](crate::PEER_TIMEOUT).
+    pub force_closed_channels: Vec<ChannelId>,
+    /// Channels whose cooperative close transaction, or whose CET following
+    /// the buffer transaction, got confirmed.
+    pub closed_channels: Vec<ChannelId>,
+    /// Channels that could not be checked.
+    pub channel_errors: Vec<ChannelCheckError>,
+}
+
+/// The outcome of checking a single contract.
+pub(crate) enum CheckOutcome {
+    Unchanged,
+    Updated(Contract),
+    /// The contract was closed by the given mutual close transaction, which
+    /// the Closed contract does not hold.
+    MutuallyClosed(Contract, Txid),
+    UnexpectedSpend(Txid),
+}
+
+/// The outcome of checking a single channel.
+pub(crate) enum ChannelCheckOutcome {
+    Unchanged,
+    /// A collaborative close was offered to the given counter party.
+    CloseOffered(CollaborativeCloseOffer, PublicKey),
+    ForceClosed,
+    Closed,
+}
+
+/// The outcome of checking whether a stale or failed contract should be
+/// cleaned up.
+pub(crate) enum CleanupOutcome {
+    Unchanged,
+    Expired,
+    Deleted,
+}
+
+impl PeriodicCheckReport {
+    /// Returns true if the periodic check neither updated a contract nor
+    /// encountered any issue.
+    pub fn is_empty(&self) -> bool {
+        self.confirmed.is_empty()
+            && self.pre_closed.is_empty()
+            && self.closed.is_empty()
+            && self.refunded.is_empty()
+            && self.unexpected_spends.is_empty()
+            && self.expired_offers.is_empty()
+            && self.deleted.is_empty()
+            && self.attestations.is_empty()
+            && self.oracle_disagreements.is_empty()
+            && self.errors.is_empty()
+            && self.close_offers.is_empty()
+            && self.force_closed_channels.is_empty()
+            && self.closed_channels.is_empty()
+            && self.channel_errors.is_empty()
+    }
+
+    pub(crate) fn add_update(&mut self, event_id: String, contract: &Contract) {
+        let (list, txid) = match contract {
+            Contract::Confirmed(c) => (
+                &mut self.confirmed,
+                c.accepted_contract.dlc_transactions.fund.txid(),
+            ),
+            Contract::PreClosed(c) => (&mut self.pre_closed, c.signed_cet.txid()),
+            Contract::Closed(c) => match &c.signed_cet {
+                Some(cet) => (&mut self.closed, cet.txid()),
+                None => return,
+            },
+            Contract::Refunded(c) => (
+                &mut self.refunded,
+                c.accepted_contract.dlc_transactions.refund.txid(),
+            ),
+            _ => return,
+        };
+        list.push(ContractUpdate {
+            contract_id: contract.get_id(),
+            event_id,
+            txid,
+        });
+    }
+
+    pub(crate) fn add_error(
+        &mut self,
+        contract_id: ContractId,
+        state: ContractState,
+        cause: Error,
+    ) {
+        self.errors.push(ContractCheckError {
+            contract_id,
+            state,
+            cause,
+        });
+    }
+
+    pub(crate) fn add_channel_outcome(
+        &mut self,
+        channel_id: ChannelId,
+        outcome: Result<ChannelCheckOutcome, Error>,
+    ) {
+        match outcome {
+            Ok(ChannelCheckOutcome::Unchanged) => (),
+            Ok(ChannelCheckOutcome::CloseOffered(close_offer, counter_party)) => {
+                self.close_offers.push(ChannelCloseOffer {
+                    channel_id,
+                    counter_party,
+                    close_offer,
+                })
+            }
+            Ok(ChannelCheckOutcome::ForceClosed) => self.force_closed_channels.push(channel_id),
+            Ok(ChannelCheckOutcome::Closed) => self.closed_channels.push(channel_id),
+            Err(e) => {
+                error!("Error checking channel {:02x?}: {}", channel_id, e);
+                self.channel_errors.push(ChannelCheckError {
+                    channel_id,
+                    cause: e,
+                });
+            }
+        }
+    }
+
+    pub(crate) fn add_attestation_status(&mut self, status: AttestationStatus) {
+        if status.state == AttestationState::NotAttested {
+            return;
+        }
+        if status.state == AttestationState::Disagreement {
+            error!(
+                "ALERT: Oracles disagree on the outcome of contract {:02x?}, disagreeing: {:?}",
+                status.contract_id, status.disagreeing
+            );
+            self.oracle_disagreements.push(status.contract_id);
+        } else if !status.missing.is_empty() || !status.disagreeing.is_empty() {
+            warn!(
+                "Contract {:02x?} has {} attestations for a threshold of {}, missing: {:?}, disagreeing: {:?}",
+                status.contract_id,
+                status.attested.len(),
+                status.threshold,
+                status.missing,
+                status.disagreeing
+            );
+        }
+        self.attestations.push(status);
+    }
+
+    pub(crate) fn add_cleanup_outcome(
+        &mut self,
+        contract_id: ContractId,
+        state: ContractState,
+        outcome: Result<CleanupOutcome, Error>,
+    ) {
+        match outcome {
+            Ok(CleanupOutcome::Unchanged) => (),
+            Ok(CleanupOutcome::Expired) => self.expired_offers.push(contract_id),
+            Ok(CleanupOutcome::Deleted) => self.deleted.push(contract_id),
+            Err(e) => {
+                error!(
+                    "Error cleaning up {:?} contract {:02x?}: {}",
+                    state, contract_id, e
+                );
+                self.add_error(contract_id, state, e);
+            }
+        }
+    }
+
+    pub(crate) fn add_outcome(
+        &mut self,
+        contract: &SignedContract,
+        state: ContractState,
+        outcome: Result<CheckOutcome, Error>,
+    ) {
+        let contract_id = contract.accepted_contract.get_contract_id();
+        let event_id = get_event_id(&contract.accepted_contract.offered_contract.contract_info);
+        match outcome {
+            Ok(CheckOutcome::Unchanged) => (),
+            Ok(CheckOutcome::Updated(updated)) => match event_id {
+                Some(event_id) => self.add_update(event_id.to_string(), &updated),
+                None => self.add_error(
+                    contract_id,
+                    state,
+                    Error::InvalidState("Missing oracle event ID".to_string()),
+                ),
+            },
+            Ok(CheckOutcome::MutuallyClosed(updated, txid)) => match event_id {
+                Some(event_id) => self.closed.push(ContractUpdate {
+                    contract_id: updated.get_id(),
+                    event_id: event_id.to_string(),
+                    txid,
+                }),
+                None => self.add_error(
+                    contract_id,
+                    state,
+                    Error::InvalidState("Missing oracle event ID".to_string()),
+                ),
+            },
+            Ok(CheckOutcome::UnexpectedSpend(txid)) => {
+                error!(
+                    "ALERT: Funding output of contract {} spent by unexpected transaction {}",
+                    contract.accepted_contract.get_contract_id_string(),
+                    txid
+                );
+                self.unexpected_spends
+                    .push(UnexpectedSpend { contract_id, txid });
+            }
+            Err(e) => {
+                error!(
+                    "Error checking {:?} contract {}: {}",
+                    state,
+                    contract.accepted_contract.get_contract_id_string(),
+                    e
+                );
+                self.add_error(contract_id, state, e);
+            }
+        }
+    }
+}
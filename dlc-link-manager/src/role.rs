@@ -0,0 +1,50 @@
+//! #ManagerRole the side of the DLC protocol a Manager takes.
+
+use dlc_messages::Message as DlcMessage;
+
+/// The side of the DLC protocol a [`Manager`](crate::Manager) takes. It decides
+/// which offer or accept APIs the Manager exposes and which messages it
+/// handles.
+pub trait ManagerRole: private::Sealed {
+    /// Whether the Manager makes the offers.
+    const IS_OFFER_PARTY: bool;
+}
+
+/// A Manager offering contracts and channels, and signing them once the
+/// counter party accepts.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Offerer;
+
+/// A Manager accepting the contracts and channels offered by its counter
+/// parties.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Acceptor;
+
+impl ManagerRole for Offerer {
+    const IS_OFFER_PARTY: bool = true;
+}
+
+impl ManagerRole for Acceptor {
+    const IS_OFFER_PARTY: bool = false;
+}
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for super::Offerer {}
+    impl Sealed for super::Acceptor {}
+}
+
+/// Returns true if the message is sent to the offer party when `to_offer_party`
+/// is set, or to the accept party otherwise. Messages of settled and renewed
+/// channels can be sent by either party.
+pub(crate) fn is_sent_to(msg: &DlcMessage, to_offer_party: bool) -> bool {
+    match msg {
+        DlcMessage::Offer(_)
+        | DlcMessage::Sign(_)
+        | DlcMessage::OfferChannel(_)
+        | DlcMessage::SignChannel(_) => !to_offer_party,
+        DlcMessage::Accept(_) | DlcMessage::AcceptChannel(_) => to_offer_party,
+        _ => true,
+    }
+}
@@ -0,0 +1,180 @@
+//! #Telemetry metrics recorded by the Manager through the `metrics` facade, and
+//! the fields of its tracing spans.
+//!
+//! The metrics are only exported if the application installs a recorder.
+
+use bitcoin::hashes::hex::ToHex;
+use dlc_manager::contract::contract_info::ContractInfo;
+use dlc_manager::contract::contract_input::ContractInput;
+use dlc_manager::contract::signed_contract::SignedContract;
+use dlc_manager::ContractId;
+use dlc_messages::Message as DlcMessage;
+use metrics::{describe_counter, describe_gauge, describe_histogram, Unit};
+use tracing::{info_span, Span};
+
+use crate::{get_event_id, ContractState};
+
+const CONTRACTS: &str = "dlc_manager_contracts";
+const CONTRACT_TRANSITIONS: &str = "dlc_manager_contract_transitions_total";
+const CLOSE_LATENCY: &str = "dlc_manager_close_latency_seconds";
+const BROADCAST_FAILURES: &str = "dlc_manager_broadcast_failures_total";
+
+/// Register the descriptions of the metrics recorded by the Manager with the
+/// installed recorder.
+pub fn describe_metrics() {
+    describe_gauge!(
+        CONTRACTS,
+        Unit::Count,
+        "Signed, Confirmed and PreClosed contracts, as listed by the latest periodic check"
+    );
+    describe_counter!(
+        CONTRACT_TRANSITIONS,
+        Unit::Count,
+        "Contracts moved to each state"
+    );
+    describe_histogram!(
+        CLOSE_LATENCY,
+        Unit::Seconds,
+        "Time from the maturity of the oracle event to the closing of a contract"
+    );
+    describe_counter!(
+        BROADCAST_FAILURES,
+        Unit::Count,
+        "Failed broadcasts per transaction type"
+    );
+}
+
+/// The transactions broadcast by the Manager, labelling broadcast failures.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum TransactionType {
+    Fund,
+    Cet,
+    MutualClose,
+    Refund,
+    FeeBump,
+    Rebroadcast,
+    Buffer,
+    Settle,
+    ChannelClose,
+}
+
+impl TransactionType {
+    fn label(self) -> &'static str {
+        match self {
+            TransactionType::Fund => "fund",
+            TransactionType::Cet => "cet",
+            TransactionType::MutualClose => "mutual_close",
+            TransactionType::Refund => "refund",
+            TransactionType::FeeBump => "fee_bump",
+            TransactionType::Rebroadcast => "rebroadcast",
+            TransactionType::Buffer => "buffer",
+            TransactionType::Settle => "settle",
+            TransactionType::ChannelClose => "channel_close",
+        }
+    }
+}
+
+fn state_label(state: ContractState) -> &'static str {
+    match state {
+        ContractState::Offered => "offered",
+        ContractState::Accepted => "accepted",
+        ContractState::Signed => "signed",
+        ContractState::Confirmed => "confirmed",
+        ContractState::PreClosed => "pre_closed",
+        ContractState::Closed => "closed",
+        ContractState::Refunded => "refunded",
+        ContractState::FailedAccept => "failed_accept",
+        ContractState::FailedSign => "failed_sign",
+        ContractState::Rejected => "rejected",
+    }
+}
+
+/// Record the number of contracts in the given state. Only the states every
+/// periodic check lists are recorded, as counting the others would mean
+/// reading every record on each check.
+pub(crate) fn record_contract_count(state: ContractState, count: usize) {
+    metrics::gauge!(CONTRACTS, count as f64, "state" => state_label(state));
+}
+
+pub(crate) fn record_transition(state: ContractState) {
+    metrics::counter!(CONTRACT_TRANSITIONS, 1, "state" => state_label(state));
+}
+
+/// Record the time elapsed since the latest maturity of the oracle events of
+/// the contract, which was just closed.
+pub(crate) fn record_close_latency(contract: &SignedContract, now: u64) {
+    let maturity = contract
+        .accepted_contract
+        .offered_contract
+        .contract_info
+        .iter()
+        .flat_map(|info| info.oracle_announcements.iter())
+        .map(|announcement| announcement.oracle_event.event_maturity_epoch)
+        .max();
+    if let Some(maturity) = maturity {
+        metrics::histogram!(CLOSE_LATENCY, now.saturating_sub(maturity as u64) as f64);
+    }
+}
+
+pub(crate) fn record_broadcast_failure(tx_type: TransactionType) {
+    metrics::counter!(BROADCAST_FAILURES, 1, "transaction" => tx_type.label());
+}
+
+/// A span for the processing of a single contract by a periodic check step.
+pub(crate) fn contract_span(contract_id: &ContractId, event_id: Option<&str>) -> Span {
+    info_span!(
+        "contract",
+        contract_id = %contract_id.to_hex(),
+        event_id = event_id.unwrap_or_default()
+    )
+}
+
+/// Record the contract id in the `contract_id` field of the current span, once
+/// it is known.
+pub(crate) fn record_contract_id(contract_id: &ContractId) {
+    Span::current().record("contract_id", contract_id.to_hex().as_str());
+}
+
+/// Record the event id of the contract in the `event_id` field of the current
+/// span, once the contract is known.
+pub(crate) fn record_event_id(contract_info: &[ContractInfo]) {
+    if let Some(event_id) = get_event_id(contract_info) {
+        Span::current().record("event_id", event_id);
+    }
+}
+
+/// Returns the event id of the first oracle of the contract input.
+pub(crate) fn get_input_event_id(contract_input: &ContractInput) -> &str {
+    contract_input
+        .contract_infos
+        .first()
+        .map_or("", |info| info.oracles.event_id.as_str())
+}
+
+/// Returns the id of the contract or channel the message is about, which is
+/// the temporary id until the contract or channel is signed, formatted for
+/// span fields.
+pub(crate) fn format_message_id(msg: &DlcMessage) -> String {
+    get_message_id(msg).map_or_else(String::new, |id| id.to_hex())
+}
+
+fn get_message_id(msg: &DlcMessage) -> Option<ContractId> {
+    match msg {
+        DlcMessage::Offer(o) => Some(o.temporary_contract_id),
+        DlcMessage::Accept(a) => Some(a.temporary_contract_id),
+        DlcMessage::Sign(s) => Some(s.contract_id),
+        DlcMessage::OfferChannel(o) => Some(o.temporary_channel_id),
+        DlcMessage::AcceptChannel(a) => Some(a.temporary_channel_id),
+        DlcMessage::SignChannel(s) => Some(s.channel_id),
+        DlcMessage::SettleOffer(s) => Some(s.channel_id),
+        DlcMessage::SettleAccept(s) => Some(s.channel_id),
+        DlcMessage::SettleConfirm(s) => Some(s.channel_id),
+        DlcMessage::SettleFinalize(s) => Some(s.channel_id),
+        DlcMessage::RenewOffer(r) => Some(r.channel_id),
+        DlcMessage::RenewAccept(r) => Some(r.channel_id),
+        DlcMessage::RenewConfirm(r) => Some(r.channel_id),
+        DlcMessage::RenewFinalize(r) => Some(r.channel_id),
+        DlcMessage::CollaborativeCloseOffer(c) => Some(c.channel_id),
+        _ => None,
+    }
+}
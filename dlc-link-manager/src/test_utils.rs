@@ -0,0 +1,985 @@
+//! #test_utils in-memory implementations of the traits the Manager depends on,
+//! so that whole contract lifecycles can run in-process.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use bitcoin::{
+    Address, Block, Network, OutPoint, PackedLockTime, Script, Sequence, Transaction, TxIn, TxOut,
+    Txid, Witness,
+};
+use dlc::secp_utils::schnorrsig_sign_with_nonce;
+use dlc_manager::channel::signed_channel::{SignedChannel, SignedChannelStateType};
+use dlc_manager::channel::{offered_channel::OfferedChannel, Channel};
+use dlc_manager::contract::offered_contract::OfferedContract;
+use dlc_manager::contract::signed_contract::SignedContract;
+use dlc_manager::contract::{Contract, PreClosedContract};
+use dlc_manager::error::Error;
+use dlc_manager::{Blockchain, ChannelId, ContractId, Signer, Time, Utxo, Wallet};
+use dlc_messages::oracle_msgs::{
+    EventDescriptor, OracleAnnouncement, OracleAttestation, OracleEvent,
+};
+use lightning::chain::chaininterface::{ConfirmationTarget, FeeEstimator};
+use lightning::util::ser::Writeable;
+use secp256k1_zkp::hashes::sha256;
+use secp256k1_zkp::rand::thread_rng;
+use secp256k1_zkp::{All, KeyPair, Message, PublicKey, Secp256k1, SecretKey, XOnlyPublicKey};
+
+use crate::{
+    derive_identity_key, AsyncBlockchain, AsyncClock, AsyncOracle, AsyncStorage, ContractMetadata,
+    ContractState, ReservingWallet,
+};
+
+/// Lock timestamps start from this value, lower lock times are block heights.
+const LOCK_TIME_THRESHOLD: u32 = 500_000_000;
+
+/// An [`AsyncStorage`] keeping contracts, channels and their side records in
+/// memory.
+#[derive(Default)]
+pub struct MemoryStorage {
+    contracts: Mutex<HashMap<ContractId, Contract>>,
+    metadata: Mutex<HashMap<ContractId, ContractMetadata>>,
+    channels: Mutex<HashMap<ChannelId, Channel>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn get_contracts_where<T>(&self, f: impl Fn(&Contract) -> Option<T>) -> Vec<T> {
+        self.contracts
+            .lock()
+            .expect("contracts lock poisoned")
+            .values()
+            .filter_map(f)
+            .collect()
+    }
+}
+
+impl AsyncStorage for MemoryStorage {
+    async fn get_contract(&self, id: &ContractId) -> Result<Option<Contract>, Error> {
+        Ok(self
+            .contracts
+            .lock()
+            .expect("contracts lock poisoned")
+            .get(id)
+            .cloned())
+    }
+
+    async fn get_contracts(&self) -> Result<Vec<Contract>, Error> {
+        Ok(self.get_contracts_where(|c| Some(c.clone())))
+    }
+
+    async fn create_contract(&self, contract: &OfferedContract) -> Result<(), Error> {
+        self.contracts
+            .lock()
+            .expect("contracts lock poisoned")
+            .insert(contract.id, Contract::Offered(contract.clone()));
+        Ok(())
+    }
+
+    async fn delete_contract(&self, id: &ContractId) -> Result<(), Error> {
+        self.contracts
+            .lock()
+            .expect("contracts lock poisoned")
+            .remove(id);
+        Ok(())
+    }
+
+    async fn update_contract(&self, contract: &Contract) -> Result<(), Error> {
+        let mut contracts = self.contracts.lock().expect("contracts lock poisoned");
+        if let Contract::Accepted(_) | Contract::Signed(_) = contract {
+            contracts.remove(&contract.get_temporary_id());
+        }
+        contracts.insert(contract.get_id(), contract.clone());
+        Ok(())
+    }
+
+    async fn replace_contract(&self, id: &ContractId, contract: &Contract) -> Result<(), Error> {
+        let mut contracts = self.contracts.lock().expect("contracts lock poisoned");
+        contracts.remove(id);
+        contracts.insert(contract.get_id(), contract.clone());
+        Ok(())
+    }
+
+    async fn get_contract_offers(&self) -> Result<Vec<OfferedContract>, Error> {
+        Ok(self.get_contracts_where(|c| match c {
+            Contract::Offered(o) => Some(o.clone()),
+            _ => None,
+        }))
+    }
+
+    async fn get_signed_contracts(&self) -> Result<Vec<SignedContract>, Error> {
+        Ok(self.get_contracts_where(|c| match c {
+            Contract::Signed(s) => Some(s.clone()),
+            _ => None,
+        }))
+    }
+
+    async fn get_confirmed_contracts(&self) -> Result<Vec<SignedContract>, Error> {
+        Ok(self.get_contracts_where(|c| match c {
+            Contract::Confirmed(s) => Some(s.clone()),
+            _ => None,
+        }))
+    }
+
+    async fn get_preclosed_contracts(&self) -> Result<Vec<PreClosedContract>, Error> {
+        Ok(self.get_contracts_where(|c| match c {
+            Contract::PreClosed(p) => Some(p.clone()),
+            _ => None,
+        }))
+    }
+
+    async fn get_contracts_in_state(&self, state: ContractState) -> Result<Vec<Contract>, Error> {
+        Ok(self.get_contracts_where(|c| {
+            if ContractState::from(c) == state {
+                Some(c.clone())
+            } else {
+                None
+            }
+        }))
+    }
+
+    async fn get_contract_metadata(
+        &self,
+        id: &ContractId,
+    ) -> Result<Option<ContractMetadata>, Error> {
+        Ok(self
+            .metadata
+            .lock()
+            .expect("metadata lock poisoned")
+            .get(id)
+            .cloned())
+    }
+
+    async fn delete_contract_metadata(&self, id: &ContractId) -> Result<(), Error> {
+        self.metadata
+            .lock()
+            .expect("metadata lock poisoned")
+            .remove(id);
+        Ok(())
+    }
+
+    async fn upsert_contract_metadata(
+        &self,
+        id: &ContractId,
+        metadata: &ContractMetadata,
+    ) -> Result<(), Error> {
+        self.metadata
+            .lock()
+            .expect("metadata lock poisoned")
+            .insert(*id, metadata.clone());
+        Ok(())
+    }
+
+    async fn get_channel(&self, id: &ChannelId) -> Result<Option<Channel>, Error> {
+        Ok(self
+            .channels
+            .lock()
+            .expect("channels lock poisoned")
+            .get(id)
+            .cloned())
+    }
+
+    async fn upsert_channel(&self, channel: &Channel) -> Result<(), Error> {
+        let mut channels = self.channels.lock().expect("channels lock poisoned");
+        if let Channel::Accepted(_) | Channel::Signed(_) | Channel::FailedSign(_) = channel {
+            channels.remove(&channel.get_temporary_id());
+        }
+        channels.insert(channel.get_id(), channel.clone());
+        Ok(())
+    }
+
+    async fn delete_channel(&self, id: &ChannelId) -> Result<(), Error> {
+        self.channels
+            .lock()
+            .expect("channels lock poisoned")
+            .remove(id);
+        Ok(())
+    }
+
+    async fn get_offered_channels(&self) -> Result<Vec<OfferedChannel>, Error> {
+        Ok(self
+            .channels
+            .lock()
+            .expect("channels lock poisoned")
+            .values()
+            .filter_map(|c| match c {
+                Channel::Offered(o) => Some(o.clone()),
+                _ => None,
+            })
+            .collect())
+    }
+
+    async fn get_signed_channels(
+        &self,
+        channel_state: Option<SignedChannelStateType>,
+    ) -> Result<Vec<SignedChannel>, Error> {
+        Ok(self
+            .channels
+            .lock()
+            .expect("channels lock poisoned")
+            .values()
+            .filter_map(|c| match c {
+                Channel::Signed(s)
+                    if channel_state.map_or(true, |state| s.state.get_type() == state) =>
+                {
+                    Some(s.clone())
+                }
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+struct MockEvent {
+    announcement: OracleAnnouncement,
+    nonces: Vec<SecretKey>,
+    attestation: Option<OracleAttestation>,
+}
+
+/// An [`AsyncOracle`] announcing and attesting events on demand. It can be
+/// made unreachable to test how missing attestations are handled.
+pub struct MockOracle {
+    secp: Secp256k1<All>,
+    key_pair: KeyPair,
+    events: Mutex<HashMap<String, MockEvent>>,
+    unavailable: AtomicBool,
+}
+
+impl Default for MockOracle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockOracle {
+    /// Create an oracle with a random key.
+    pub fn new() -> Self {
+        let secp = Secp256k1::new();
+        let key_pair = KeyPair::new(&secp, &mut thread_rng());
+        MockOracle {
+            secp,
+            key_pair,
+            events: Mutex::new(HashMap::new()),
+            unavailable: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the public key of the oracle.
+    pub fn public_key(&self) -> XOnlyPublicKey {
+        self.key_pair.public_key().into()
+    }
+
+    /// Make every request to the oracle fail until made available again.
+    pub fn set_available(&self, available: bool) {
+        self.unavailable.store(!available, Ordering::SeqCst);
+    }
+
+    /// Announce an event with one nonce per digit of digit decomposition events
+    /// and a single nonce for enumeration events.
+    pub fn announce(
+        &self,
+        event_id: &str,
+        event_maturity_epoch: u32,
+        event_descriptor: EventDescriptor,
+    ) -> OracleAnnouncement {
+        let nb_nonces = match &event_descriptor {
+            EventDescriptor::EnumEvent(_) => 1,
+            EventDescriptor::DigitDecompositionEvent(d) => {
+                d.nb_digits as usize + usize::from(d.is_signed)
+            }
+        };
+        let nonces: Vec<SecretKey> = (0..nb_nonces)
+            .map(|_| SecretKey::new(&mut thread_rng()))
+            .collect();
+        let oracle_event = OracleEvent {
+            oracle_nonces: nonces
+                .iter()
+                .map(|nonce| {
+                    KeyPair::from_secret_key(&self.secp, nonce)
+                        .public_key()
+                        .into()
+                })
+                .collect(),
+            event_maturity_epoch,
+            event_descriptor,
+            event_id: event_id.to_string(),
+        };
+        let announcement = OracleAnnouncement {
+            announcement_signature: self.secp.sign_schnorr(
+                &Message::from_hashed_data::<sha256::Hash>(&oracle_event.encode()),
+                &self.key_pair,
+            ),
+            oracle_public_key: self.public_key(),
+            oracle_event,
+        };
+        self.events.lock().expect("events lock poisoned").insert(
+            event_id.to_string(),
+            MockEvent {
+                announcement: announcement.clone(),
+                nonces,
+                attestation: None,
+            },
+        );
+        announcement
+    }
+
+    /// Attest the given outcomes, one per announced nonce, for the event.
+    pub fn attest(
+        &self,
+        event_id: &str,
+        outcomes: Vec<String>,
+    ) -> Result<OracleAttestation, Error> {
+        let mut events = self.events.lock().expect("events lock poisoned");
+        let event = events
+            .get_mut(event_id)
+            .ok_or_else(|| Error::OracleError(format!("Unknown event {}", event_id)))?;
+        if outcomes.len() != event.nonces.len() {
+            return Err(Error::OracleError(format!(
+                "Expected {} outcomes for event {}, got {}",
+                event.nonces.len(),
+                event_id,
+                outcomes.len()
+            )));
+        }
+        let signatures = outcomes
+            .iter()
+            .zip(event.nonces.iter())
+            .map(|(outcome, nonce)| {
+                schnorrsig_sign_with_nonce(
+                    &self.secp,
+                    &Message::from_hashed_data::<sha256::Hash>(outcome.as_bytes()),
+                    &self.key_pair,
+                    nonce.as_ref(),
+                )
+            })
+            .collect();
+        let attestation = OracleAttestation {
+            oracle_public_key: self.public_key(),
+            signatures,
+            outcomes,
+        };
+        event.attestation = Some(attestation.clone());
+        Ok(attestation)
+    }
+
+    /// Attest the digits of the given value for a digit decomposition event.
+    pub fn attest_numerical(&self, event_id: &str, value: u64) -> Result<OracleAttestation, Error> {
+        let descriptor = {
+            let events = self.events.lock().expect("events lock poisoned");
+            let event = events
+                .get(event_id)
+                .ok_or_else(|| Error::OracleError(format!("Unknown event {}", event_id)))?;
+            match &event.announcement.oracle_event.event_descriptor {
+                EventDescriptor::DigitDecompositionEvent(d) => d.clone(),
+                EventDescriptor::EnumEvent(_) => {
+                    return Err(Error::OracleError(format!(
+                        "Event {} is not a digit decomposition event",
+                        event_id
+                    )))
+                }
+            }
+        };
+        let base = descriptor.base as u64;
+        let mut outcomes: Vec<String> = (0..descriptor.nb_digits as u32)
+            .rev()
+            .map(|position| ((value / base.pow(position)) % base).to_string())
+            .collect();
+        if descriptor.is_signed {
+            outcomes.insert(0, "+".to_string());
+        }
+        self.attest(event_id, outcomes)
+    }
+
+    fn check_available(&self) -> Result<(), Error> {
+        if self.unavailable.load(Ordering::SeqCst) {
+            return Err(Error::OracleError("Oracle is unavailable".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl AsyncOracle for MockOracle {
+    async fn get_public_key(&self) -> XOnlyPublicKey {
+        self.public_key()
+    }
+
+    async fn get_announcement(&self, event_id: &str) -> Result<OracleAnnouncement, Error> {
+        self.check_available()?;
+        self.events
+            .lock()
+            .expect("events lock poisoned")
+            .get(event_id)
+            .map(|event| event.announcement.clone())
+            .ok_or_else(|| Error::OracleError(format!("Unknown event {}", event_id)))
+    }
+
+    async fn get_attestation(&self, event_id: &str) -> Result<OracleAttestation, Error> {
+        self.check_available()?;
+        self.events
+            .lock()
+            .expect("events lock poisoned")
+            .get(event_id)
+            .and_then(|event| event.attestation.clone())
+            .ok_or_else(|| Error::OracleError(format!("Event {} is not attested", event_id)))
+    }
+}
+
+#[derive(Default)]
+struct ChainState {
+    height: u64,
+    /// Known transactions, with the height of the block confirming them or
+    /// `None` while they are in the mempool.
+    transactions: HashMap<Txid, (Transaction, Option<u64>)>,
+    spends: HashMap<OutPoint, Txid>,
+    /// Used to make the transactions created by `fund` unique.
+    nb_funding_transactions: u32,
+}
+
+impl ChainState {
+    fn get_output(&self, outpoint: &OutPoint) -> Option<&TxOut> {
+        self.transactions
+            .get(&outpoint.txid)
+            .and_then(|(tx, _)| tx.output.get(outpoint.vout as usize))
+    }
+
+    fn evict(&mut self, txid: &Txid) {
+        let tx = match self.transactions.get(txid) {
+            Some((tx, None)) => tx.clone(),
+            _ => return,
+        };
+        for vout in 0..tx.output.len() as u32 {
+            if let Some(child) = self.spends.get(&OutPoint::new(*txid, vout)).cloned() {
+                self.evict(&child);
+            }
+        }
+        for input in &tx.input {
+            self.spends.remove(&input.previous_output);
+        }
+        self.transactions.remove(txid);
+    }
+}
+
+/// A simulated chain with a mempool. Broadcast transactions must spend known
+/// and unspent outputs and be final in the next block by height, and are
+/// confirmed by the next mined block. Scripts and signatures are not verified, and time
+/// lock times are not enforced as the chain has no clock.
+pub struct MockBlockchain {
+    network: Network,
+    state: Mutex<ChainState>,
+    fee_rate: AtomicU64,
+    broadcast_error: Mutex<Option<String>>,
+}
+
+impl MockBlockchain {
+    /// Create an empty chain with a fee rate of 1 sat/vB.
+    pub fn new(network: Network) -> Self {
+        MockBlockchain {
+            network,
+            state: Mutex::new(ChainState::default()),
+            fee_rate: AtomicU64::new(1),
+            broadcast_error: Mutex::new(None),
+        }
+    }
+
+    /// Create a confirmed output of the given value paying to the script,
+    /// from nowhere.
+    pub fn fund(&self, script_pubkey: Script, value: u64) -> OutPoint {
+        let mut state = self.state.lock().expect("chain lock poisoned");
+        state.nb_funding_transactions += 1;
+        let tx = Transaction {
+            version: 2,
+            lock_time: PackedLockTime(state.nb_funding_transactions),
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Script::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey,
+            }],
+        };
+        let txid = tx.txid();
+        state.height += 1;
+        let height = state.height;
+        state.transactions.insert(txid, (tx, Some(height)));
+        OutPoint::new(txid, 0)
+    }
+
+    /// Mine the given number of blocks, the first one confirming every
+    /// transaction of the mempool.
+    pub fn mine_blocks(&self, nb_blocks: u64) {
+        if nb_blocks == 0 {
+            return;
+        }
+        let mut state = self.state.lock().expect("chain lock poisoned");
+        let height = state.height + 1;
+        for (_, confirmed_at) in state.transactions.values_mut() {
+            confirmed_at.get_or_insert(height);
+        }
+        state.height += nb_blocks;
+    }
+
+    /// Mine the given number of blocks without confirming the mempool, as if
+    /// its transactions paid too little to be mined.
+    pub fn mine_empty_blocks(&self, nb_blocks: u64) {
+        self.state.lock().expect("chain lock poisoned").height += nb_blocks;
+    }
+
+    /// Returns the ids of the transactions in the mempool.
+    pub fn get_mempool(&self) -> Vec<Txid> {
+        self.state
+            .lock()
+            .expect("chain lock poisoned")
+            .transactions
+            .iter()
+            .filter(|(_, (_, confirmed_at))| confirmed_at.is_none())
+            .map(|(txid, _)| *txid)
+            .collect()
+    }
+
+    /// Drop an unconfirmed transaction and its descendants from the mempool,
+    /// as if it had expired.
+    pub fn evict(&self, txid: &Txid) {
+        self.state.lock().expect("chain lock poisoned").evict(txid);
+    }
+
+    /// Returns the total value of the unspent outputs paying to the script,
+    /// counting unconfirmed ones only if `include_mempool` is set.
+    pub fn get_balance(&self, script_pubkey: &Script, include_mempool: bool) -> u64 {
+        let state = self.state.lock().expect("chain lock poisoned");
+        state
+            .transactions
+            .iter()
+            .filter(|(_, (_, confirmed_at))| include_mempool || confirmed_at.is_some())
+            .flat_map(|(txid, (tx, _))| {
+                tx.output
+                    .iter()
+                    .enumerate()
+                    .map(move |(vout, output)| (OutPoint::new(*txid, vout as u32), output))
+            })
+            .filter(|(outpoint, output)| {
+                &output.script_pubkey == script_pubkey && !state.spends.contains_key(outpoint)
+            })
+            .map(|(_, output)| output.value)
+            .sum()
+    }
+
+    /// Set the fee rate, in sats per vbyte, returned for every target.
+    pub fn set_fee_rate(&self, fee_rate: u64) {
+        self.fee_rate.store(fee_rate, Ordering::SeqCst);
+    }
+
+    /// Make every broadcast fail with the given error until reset with `None`.
+    pub fn set_broadcast_error(&self, error: Option<String>) {
+        *self
+            .broadcast_error
+            .lock()
+            .expect("broadcast error lock poisoned") = error;
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<(), Error> {
+        if let Some(error) = self
+            .broadcast_error
+            .lock()
+            .expect("broadcast error lock poisoned")
+            .clone()
+        {
+            return Err(Error::BlockchainError(error));
+        }
+        let mut state = self.state.lock().expect("chain lock poisoned");
+        let txid = tx.txid();
+        if state.transactions.contains_key(&txid) {
+            return Ok(());
+        }
+        let lock_time = tx.lock_time.0;
+        if lock_time < LOCK_TIME_THRESHOLD
+            && lock_time as u64 > state.height
+            && tx.input.iter().any(|input| input.sequence != Sequence::MAX)
+        {
+            return Err(Error::BlockchainError(format!(
+                "Transaction {} is not final before height {}",
+                txid, lock_time
+            )));
+        }
+        for input in &tx.input {
+            if state.get_output(&input.previous_output).is_none() {
+                return Err(Error::BlockchainError(format!(
+                    "Transaction {} spends unknown output {}",
+                    txid, input.previous_output
+                )));
+            }
+            if let Some(spending_txid) = state.spends.get(&input.previous_output) {
+                return Err(Error::BlockchainError(format!(
+                    "Output {} is already spent by {}",
+                    input.previous_output, spending_txid
+                )));
+            }
+        }
+        for input in &tx.input {
+            state.spends.insert(input.previous_output, txid);
+        }
+        state.transactions.insert(txid, (tx.clone(), None));
+        Ok(())
+    }
+
+    fn get_tx(&self, txid: &Txid) -> Result<Transaction, Error> {
+        self.state
+            .lock()
+            .expect("chain lock poisoned")
+            .transactions
+            .get(txid)
+            .map(|(tx, _)| tx.clone())
+            .ok_or_else(|| Error::BlockchainError(format!("Unknown transaction {}", txid)))
+    }
+
+    fn get_confirmations(&self, txid: &Txid) -> u32 {
+        let state = self.state.lock().expect("chain lock poisoned");
+        match state.transactions.get(txid) {
+            Some((_, Some(confirmed_at))) => (state.height - confirmed_at + 1) as u32,
+            _ => 0,
+        }
+    }
+
+    fn get_height(&self) -> u64 {
+        self.state.lock().expect("chain lock poisoned").height
+    }
+}
+
+impl AsyncBlockchain for MockBlockchain {
+    async fn get_transaction_confirmations_async(&self, txid: &Txid) -> Result<u32, Error> {
+        Ok(self.get_confirmations(txid))
+    }
+
+    async fn send_transaction_async(&self, tx: &Transaction) -> Result<(), Error> {
+        self.broadcast(tx)
+    }
+
+    async fn get_network_async(&self) -> Result<Network, Error> {
+        Ok(self.network)
+    }
+
+    async fn get_transaction_async(&self, tx_id: &Txid) -> Result<Transaction, Error> {
+        self.get_tx(tx_id)
+    }
+
+    async fn get_spending_txid_async(&self, outpoint: &OutPoint) -> Result<Option<Txid>, Error> {
+        Ok(self
+            .state
+            .lock()
+            .expect("chain lock poisoned")
+            .spends
+            .get(outpoint)
+            .cloned())
+    }
+
+    async fn is_transaction_known_async(&self, txid: &Txid) -> Result<bool, Error> {
+        Ok(self
+            .state
+            .lock()
+            .expect("chain lock poisoned")
+            .transactions
+            .contains_key(txid))
+    }
+
+    async fn get_blockchain_height_async(&self) -> Result<u64, Error> {
+        Ok(self.get_height())
+    }
+
+    async fn get_fee_rate_async(&self, _confirmation_target: u16) -> Result<u64, Error> {
+        Ok(self.fee_rate.load(Ordering::SeqCst))
+    }
+}
+
+impl Blockchain for MockBlockchain {
+    fn send_transaction(&self, transaction: &Transaction) -> Result<(), Error> {
+        self.broadcast(transaction)
+    }
+
+    fn get_network(&self) -> Result<Network, Error> {
+        Ok(self.network)
+    }
+
+    fn get_blockchain_height(&self) -> Result<u64, Error> {
+        Ok(self.get_height())
+    }
+
+    fn get_block_at_height(&self, _height: u64) -> Result<Block, Error> {
+        Err(Error::BlockchainError(
+            "The mock chain does not keep blocks".to_string(),
+        ))
+    }
+
+    fn get_transaction(&self, tx_id: &Txid) -> Result<Transaction, Error> {
+        self.get_tx(tx_id)
+    }
+
+    fn get_transaction_confirmations(&self, tx_id: &Txid) -> Result<u32, Error> {
+        Ok(self.get_confirmations(tx_id))
+    }
+}
+
+impl FeeEstimator for MockBlockchain {
+    fn get_est_sat_per_1000_weight(&self, _confirmation_target: ConfirmationTarget) -> u32 {
+        (self.fee_rate.load(Ordering::SeqCst) * 250) as u32
+    }
+}
+
+/// A single key P2WPKH [`Wallet`] spending the outputs created for it on a
+/// [`MockBlockchain`].
+pub struct MockWallet {
+    secp: Secp256k1<All>,
+    seckey: SecretKey,
+    pub address: Address,
+    utxos: Mutex<Vec<Utxo>>,
+    signing_error: Mutex<Option<String>>,
+}
+
+impl MockWallet {
+    /// Create a wallet with a random key.
+    pub fn new(network: Network) -> Self {
+        let secp = Secp256k1::new();
+        let seckey = SecretKey::new(&mut thread_rng());
+        let public_key = bitcoin::PublicKey::new(PublicKey::from_secret_key(&secp, &seckey));
+        let address =
+            Address::p2wpkh(&public_key, network).expect("compressed keys have a P2WPKH address");
+        MockWallet {
+            secp,
+            seckey,
+            address,
+            utxos: Mutex::new(Vec::new()),
+            signing_error: Mutex::new(None),
+        }
+    }
+
+    /// Returns the public key of the wallet.
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey::from_secret_key(&self.secp, &self.seckey)
+    }
+
+    /// Returns the key identifying the wallet owner to its counter parties,
+    /// derived from the wallet key.
+    pub fn identity_key(&self) -> SecretKey {
+        derive_identity_key(&self.seckey).expect("to derive the identity key")
+    }
+
+    /// Create a confirmed output of the given value for the wallet on the
+    /// chain.
+    pub fn fund(&self, blockchain: &MockBlockchain, value: u64) -> Utxo {
+        let outpoint = blockchain.fund(self.address.script_pubkey(), value);
+        let utxo = Utxo {
+            tx_out: TxOut {
+                value,
+                script_pubkey: self.address.script_pubkey(),
+            },
+            outpoint,
+            address: self.address.clone(),
+            redeem_script: Script::new(),
+            reserved: false,
+        };
+        self.utxos
+            .lock()
+            .expect("utxos lock poisoned")
+            .push(utxo.clone());
+        utxo
+    }
+
+    /// Make every signature fail with the given error until reset with `None`.
+    pub fn set_signing_error(&self, error: Option<String>) {
+        *self
+            .signing_error
+            .lock()
+            .expect("signing error lock poisoned") = error;
+    }
+
+    fn check_signing_error(&self) -> Result<(), Error> {
+        match self
+            .signing_error
+            .lock()
+            .expect("signing error lock poisoned")
+            .clone()
+        {
+            Some(error) => Err(Error::WalletError(error.into())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Signer for MockWallet {
+    fn sign_tx_input(
+        &self,
+        tx: &mut Transaction,
+        input_index: usize,
+        tx_out: &TxOut,
+        _: Option<Script>,
+    ) -> Result<(), Error> {
+        self.check_signing_error()?;
+        dlc::util::sign_p2wpkh_input(
+            &self.secp,
+            &self.seckey,
+            tx,
+            input_index,
+            bitcoin::EcdsaSighashType::All,
+            tx_out.value,
+        )?;
+        Ok(())
+    }
+
+    fn get_secret_key_for_pubkey(&self, pubkey: &PublicKey) -> Result<SecretKey, Error> {
+        self.check_signing_error()?;
+        if *pubkey != self.public_key() {
+            return Err(Error::WalletError(
+                format!("Unknown public key {}", pubkey).into(),
+            ));
+        }
+        Ok(self.seckey)
+    }
+}
+
+impl Wallet for MockWallet {
+    fn get_new_address(&self) -> Result<Address, Error> {
+        Ok(self.address.clone())
+    }
+
+    fn get_new_secret_key(&self) -> Result<SecretKey, Error> {
+        Ok(self.seckey)
+    }
+
+    fn get_utxos_for_amount(
+        &self,
+        amount: u64,
+        _fee_rate: Option<u64>,
+        lock_utxos: bool,
+    ) -> Result<Vec<Utxo>, Error> {
+        let mut utxos = self.utxos.lock().expect("utxos lock poisoned");
+        let mut selected = Vec::new();
+        let mut total = 0;
+        for utxo in utxos.iter_mut().filter(|utxo| !utxo.reserved) {
+            if total >= amount {
+                break;
+            }
+            total += utxo.tx_out.value;
+            utxo.reserved = lock_utxos;
+            selected.push(utxo.clone());
+        }
+        if total < amount {
+            return Err(Error::WalletError(
+                format!(
+                    "Not enough funds for {} sats, only {} available",
+                    amount, total
+                )
+                .into(),
+            ));
+        }
+        Ok(selected)
+    }
+
+    fn import_address(&self, _: &Address) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ReservingWallet for MockWallet {
+    fn unreserve_utxos(&self, outpoints: &[OutPoint]) -> Result<(), Error> {
+        let mut utxos = self.utxos.lock().expect("utxos lock poisoned");
+        for utxo in utxos
+            .iter_mut()
+            .filter(|utxo| outpoints.contains(&utxo.outpoint))
+        {
+            utxo.reserved = false;
+        }
+        Ok(())
+    }
+}
+
+/// A [`Time`] provider and [`AsyncClock`] only moving when told to.
+pub struct MockTime {
+    now: AtomicU64,
+}
+
+impl MockTime {
+    pub fn new(now: u64) -> Self {
+        MockTime {
+            now: AtomicU64::new(now),
+        }
+    }
+
+    pub fn set_time(&self, now: u64) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, seconds: u64) {
+        self.now.fetch_add(seconds, Ordering::SeqCst);
+    }
+}
+
+impl Time for MockTime {
+    fn unix_time_now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+impl AsyncClock for MockTime {
+    async fn get_unix_time(&self) -> Result<u64, Error> {
+        Ok(self.unix_time_now())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn spend(outpoint: OutPoint, value: u64) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: vec![TxIn {
+                previous_output: outpoint,
+                script_sig: Script::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: Script::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_mempool_and_confirmations() {
+        let chain = MockBlockchain::new(Network::Regtest);
+        let outpoint = chain.fund(Script::new(), 10_000);
+        let tx = spend(outpoint, 9_000);
+
+        chain.send_transaction(&tx).expect("spends a known output");
+        assert_eq!(chain.get_mempool(), vec![tx.txid()]);
+        assert_eq!(chain.get_confirmations(&tx.txid()), 0);
+        assert!(chain.send_transaction(&spend(outpoint, 8_000)).is_err());
+
+        chain.mine_blocks(3);
+        assert!(chain.get_mempool().is_empty());
+        assert_eq!(chain.get_confirmations(&tx.txid()), 3);
+        assert_eq!(chain.get_balance(&Script::new(), false), 9_000);
+    }
+
+    #[test]
+    fn test_evict_descendants() {
+        let chain = MockBlockchain::new(Network::Regtest);
+        let parent = spend(chain.fund(Script::new(), 10_000), 9_000);
+        let child = spend(OutPoint::new(parent.txid(), 0), 8_000);
+        chain
+            .send_transaction(&parent)
+            .expect("spends a known output");
+        chain.send_transaction(&child).expect("spends the parent");
+
+        chain.evict(&parent.txid());
+        assert!(chain.get_mempool().is_empty());
+        assert_eq!(chain.get_balance(&Script::new(), true), 10_000);
+    }
+}
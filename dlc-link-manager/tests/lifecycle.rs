@@ -0,0 +1,1549 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use bitcoin::Network;
+use dlc::Payout;
+use dlc_link_manager::test_utils::{
+    MemoryStorage, MockBlockchain, MockOracle, MockTime, MockWallet,
+};
+use dlc_link_manager::{
+    get_charged_protocol_fee, sign_dlc_message, Acceptor, AsyncStorage, ContractObserver,
+    ContractState, ContractTransition, FailureKind, Manager, ManagerConfig, ManagerRole,
+    NoOracleResolver, Offerer, Outcome, CET_NSEQUENCE, PEER_TIMEOUT,
+};
+use dlc_manager::channel::signed_channel::{SignedChannel, SignedChannelState};
+use dlc_manager::channel::Channel;
+use dlc_manager::contract::contract_input::{ContractInput, ContractInputInfo, OracleInput};
+use dlc_manager::contract::enum_descriptor::{EnumDescriptor, EnumerationPayout};
+use dlc_manager::contract::{Contract, ContractDescriptor};
+use dlc_manager::error::Error;
+use dlc_manager::{ChannelId, ContractId, Wallet};
+use dlc_messages::oracle_msgs::{EnumEventDescriptor, EventDescriptor};
+use dlc_messages::Message as DlcMessage;
+use futures::executor::block_on;
+use secp256k1_zkp::{PublicKey, Secp256k1};
+
+const START_TIME: u64 = 1_700_000_000;
+const MATURITY: u32 = 1_700_000_600;
+const REFUND_DELAY: u32 = 86_400;
+const COLLATERAL: u64 = 50_000;
+
+type TestManager<Role> = Manager<
+    Arc<MockWallet>,
+    Arc<MockBlockchain>,
+    Arc<MemoryStorage>,
+    Arc<MockOracle>,
+    Arc<MockTime>,
+    Role,
+    NoOracleResolver,
+    Arc<MockTime>,
+>;
+
+struct Party<Role> {
+    manager: TestManager<Role>,
+    wallet: Arc<MockWallet>,
+    store: Arc<MemoryStorage>,
+}
+
+impl<Role: ManagerRole> Party<Role> {
+    /// Returns the key identifying the party to its counter party.
+    fn identity(&self) -> PublicKey {
+        PublicKey::from_secret_key(&Secp256k1::new(), &self.wallet.identity_key())
+    }
+
+    /// Passes a message sent by `sender` to the party, signed by the sender.
+    async fn receive<SenderRole: ManagerRole>(
+        &self,
+        msg: &DlcMessage,
+        sender: &Party<SenderRole>,
+    ) -> Result<Option<DlcMessage>, Error> {
+        let signature = sign_dlc_message(&Secp256k1::new(), msg, &sender.wallet.identity_key())?;
+        self.manager
+            .on_signed_dlc_message(msg, sender.identity(), &signature)
+            .await
+    }
+}
+
+struct TestSetup {
+    blockchain: Arc<MockBlockchain>,
+    oracle: Arc<MockOracle>,
+    time: Arc<MockTime>,
+    offerer: Party<Offerer>,
+    acceptor: Party<Acceptor>,
+}
+
+/// Keeps every transition it is notified of.
+#[derive(Clone, Default)]
+struct RecordingObserver {
+    transitions: Arc<Mutex<Vec<ContractTransition>>>,
+}
+
+impl ContractObserver for RecordingObserver {
+    fn on_transition(&self, transition: &ContractTransition) {
+        self.transitions
+            .lock()
+            .expect("transitions lock poisoned")
+            .push(transition.clone());
+    }
+}
+
+fn get_config() -> ManagerConfig {
+    ManagerConfig {
+        funding_confirmations: 1,
+        closing_confirmations: 1,
+        ..Default::default()
+    }
+}
+
+fn setup() -> TestSetup {
+    setup_with_config(get_config())
+}
+
+fn setup_with_config(config: ManagerConfig) -> TestSetup {
+    let blockchain = Arc::new(MockBlockchain::new(Network::Regtest));
+    let oracle = Arc::new(MockOracle::new());
+    let time = Arc::new(MockTime::new(START_TIME));
+    let oracles = HashMap::from([(oracle.public_key(), oracle.clone())]);
+
+    let offerer_wallet = Arc::new(MockWallet::new(Network::Regtest));
+    offerer_wallet.fund(&blockchain, 1_000_000);
+    let offerer_store = Arc::new(MemoryStorage::new());
+    let offerer = Party {
+        manager: Manager::new_offerer(
+            offerer_wallet.clone(),
+            blockchain.clone(),
+            offerer_store.clone(),
+            oracles.clone(),
+            time.clone(),
+            config.clone(),
+        )
+        .expect("to create the offerer")
+        .with_clock(time.clone()),
+        wallet: offerer_wallet,
+        store: offerer_store,
+    };
+
+    let acceptor_wallet = Arc::new(MockWallet::new(Network::Regtest));
+    acceptor_wallet.fund(&blockchain, 1_000_000);
+    let acceptor_store = Arc::new(MemoryStorage::new());
+    let acceptor = Party {
+        manager: Manager::new_acceptor(
+            acceptor_wallet.clone(),
+            blockchain.clone(),
+            acceptor_store.clone(),
+            oracles,
+            time.clone(),
+            config,
+        )
+        .expect("to create the acceptor")
+        .with_clock(time.clone()),
+        wallet: acceptor_wallet,
+        store: acceptor_store,
+    };
+
+    TestSetup {
+        blockchain,
+        oracle,
+        time,
+        offerer,
+        acceptor,
+    }
+}
+
+fn get_contract_input(oracle: &MockOracle, event_id: &str) -> ContractInput {
+    let outcome_payouts = vec![
+        EnumerationPayout {
+            outcome: "offer".to_string(),
+            payout: Payout {
+                offer: 2 * COLLATERAL,
+                accept: 0,
+            },
+        },
+        EnumerationPayout {
+            outcome: "accept".to_string(),
+            payout: Payout {
+                offer: 0,
+                accept: 2 * COLLATERAL,
+            },
+        },
+    ];
+    ContractInput {
+        offer_collateral: COLLATERAL,
+        accept_collateral: COLLATERAL,
+        fee_rate: 2,
+        contract_infos: vec![ContractInputInfo {
+            contract_descriptor: ContractDescriptor::Enum(EnumDescriptor { outcome_payouts }),
+            oracles: OracleInput {
+                public_keys: vec![oracle.public_key()],
+                event_id: event_id.to_string(),
+                threshold: 1,
+            },
+        }],
+    }
+}
+
+async fn get_contract(store: &MemoryStorage, contract_id: &ContractId) -> Contract {
+    store
+        .get_contract(contract_id)
+        .await
+        .expect("to read the store")
+        .expect("to find the contract")
+}
+
+async fn get_rebroadcast_attempts(store: &MemoryStorage, contract_id: &ContractId) -> u32 {
+    store
+        .get_contract_metadata(contract_id)
+        .await
+        .expect("to read the metadata")
+        .and_then(|metadata| metadata.rebroadcast)
+        .map_or(0, |rebroadcast| rebroadcast.attempts)
+}
+
+/// Offers, accepts and signs a contract on the given event, leaving its
+/// funding transaction in the mempool.
+async fn sign_contract(setup: &TestSetup, event_id: &str) -> ContractId {
+    setup.oracle.announce(
+        event_id,
+        MATURITY,
+        EventDescriptor::EnumEvent(EnumEventDescriptor {
+            outcomes: vec!["offer".to_string(), "accept".to_string()],
+        }),
+    );
+    let acceptor_key = setup.acceptor.identity();
+
+    let offer = setup
+        .offerer
+        .manager
+        .send_offer(
+            &get_contract_input(&setup.oracle, event_id),
+            acceptor_key,
+            REFUND_DELAY,
+            0,
+            setup.offerer.wallet.address.clone(),
+        )
+        .await
+        .expect("to send the offer");
+    setup
+        .acceptor
+        .receive(&DlcMessage::Offer(offer.clone()), &setup.offerer)
+        .await
+        .expect("to receive the offer");
+
+    let (_, _, accept) = setup
+        .acceptor
+        .manager
+        .accept_contract_offer(&offer.temporary_contract_id)
+        .await
+        .expect("to accept the offer");
+    let sign = setup
+        .offerer
+        .receive(&DlcMessage::Accept(accept), &setup.acceptor)
+        .await
+        .expect("to sign the contract")
+        .expect("to reply with a sign message");
+    let contract_id = match &sign {
+        DlcMessage::Sign(s) => s.contract_id,
+        _ => panic!("Expected a sign message"),
+    };
+    setup
+        .acceptor
+        .receive(&sign, &setup.offerer)
+        .await
+        .expect("to receive the sign message");
+
+    assert!(matches!(
+        get_contract(&setup.offerer.store, &contract_id).await,
+        Contract::Signed(_)
+    ));
+    assert_eq!(setup.blockchain.get_mempool().len(), 1);
+
+    contract_id
+}
+
+/// Offers, accepts and signs a contract on the given event, then confirms its
+/// funding transaction.
+async fn fund_contract(setup: &TestSetup, event_id: &str) -> ContractId {
+    let contract_id = sign_contract(setup, event_id).await;
+
+    setup.blockchain.mine_blocks(1);
+    setup.offerer.manager.periodic_check().await.expect("check");
+    setup
+        .acceptor
+        .manager
+        .periodic_check()
+        .await
+        .expect("check");
+    assert!(matches!(
+        get_contract(&setup.offerer.store, &contract_id).await,
+        Contract::Confirmed(_)
+    ));
+    assert!(matches!(
+        get_contract(&setup.acceptor.store, &contract_id).await,
+        Contract::Confirmed(_)
+    ));
+
+    contract_id
+}
+
+#[test]
+fn test_close_on_attestation() {
+    let mut setup = setup();
+    let observer = RecordingObserver::default();
+    setup
+        .acceptor
+        .manager
+        .add_observer(Box::new(observer.clone()));
+    block_on(async {
+        let contract_id = fund_contract(&setup, "close").await;
+        let acceptor_script = setup.acceptor.wallet.address.script_pubkey();
+        let balance_before = setup.blockchain.get_balance(&acceptor_script, false);
+
+        setup.time.set_time(MATURITY as u64 + 1);
+        setup
+            .oracle
+            .attest("close", vec!["accept".to_string()])
+            .expect("to attest the event");
+        setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert!(matches!(
+            get_contract(&setup.acceptor.store, &contract_id).await,
+            Contract::PreClosed(_)
+        ));
+
+        setup.blockchain.mine_blocks(1);
+        setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        setup.offerer.manager.periodic_check().await.expect("check");
+        assert!(matches!(
+            get_contract(&setup.acceptor.store, &contract_id).await,
+            Contract::Closed(_)
+        ));
+        assert!(matches!(
+            get_contract(&setup.offerer.store, &contract_id).await,
+            Contract::Closed(_)
+        ));
+        let payout = setup.blockchain.get_balance(&acceptor_script, false) - balance_before;
+        assert!(payout > 2 * COLLATERAL - 10_000 && payout <= 2 * COLLATERAL);
+
+        let transitions = observer
+            .transitions
+            .lock()
+            .expect("transitions lock poisoned");
+        let closed = transitions
+            .iter()
+            .find(|t| t.new_state == ContractState::Closed)
+            .expect("a transition to Closed");
+        assert_eq!(closed.previous_state, Some(ContractState::PreClosed));
+        assert_eq!(closed.event_id.as_deref(), Some("close"));
+    });
+}
+
+#[test]
+fn test_refund_without_attestation() {
+    let setup = setup();
+    block_on(async {
+        let contract_id = fund_contract(&setup, "refund").await;
+
+        setup.time.set_time(MATURITY as u64 + 1);
+        setup.offerer.manager.periodic_check().await.expect("check");
+        assert!(matches!(
+            get_contract(&setup.offerer.store, &contract_id).await,
+            Contract::Confirmed(_)
+        ));
+
+        setup.time.set_time((MATURITY + REFUND_DELAY) as u64 + 1);
+        setup.offerer.manager.periodic_check().await.expect("check");
+        assert_eq!(setup.blockchain.get_mempool().len(), 1);
+        // The contract waits for its refund to confirm.
+        setup.offerer.manager.periodic_check().await.expect("check");
+        assert!(matches!(
+            get_contract(&setup.offerer.store, &contract_id).await,
+            Contract::Confirmed(_)
+        ));
+
+        setup.blockchain.mine_blocks(1);
+        setup.offerer.manager.periodic_check().await.expect("check");
+        assert!(matches!(
+            get_contract(&setup.offerer.store, &contract_id).await,
+            Contract::Refunded(_)
+        ));
+        setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert!(matches!(
+            get_contract(&setup.acceptor.store, &contract_id).await,
+            Contract::Refunded(_)
+        ));
+    });
+}
+
+#[test]
+fn test_refuse_message_not_signed_by_identity_key() {
+    let setup = setup();
+    block_on(async {
+        setup.oracle.announce(
+            "identity",
+            MATURITY,
+            EventDescriptor::EnumEvent(EnumEventDescriptor {
+                outcomes: vec!["offer".to_string(), "accept".to_string()],
+            }),
+        );
+        let offer = DlcMessage::Offer(
+            setup
+                .offerer
+                .manager
+                .send_offer(
+                    &get_contract_input(&setup.oracle, "identity"),
+                    setup.acceptor.identity(),
+                    REFUND_DELAY,
+                    0,
+                    setup.offerer.wallet.address.clone(),
+                )
+                .await
+                .expect("to send the offer"),
+        );
+
+        // The wallet key is not the identity key.
+        let secp = Secp256k1::new();
+        let wallet_key = setup
+            .offerer
+            .wallet
+            .get_new_secret_key()
+            .expect("the wallet key");
+        let signature = sign_dlc_message(&secp, &offer, &wallet_key).expect("to sign the offer");
+        setup
+            .acceptor
+            .manager
+            .on_signed_dlc_message(&offer, setup.offerer.identity(), &signature)
+            .await
+            .expect_err("the offer to be refused");
+        assert!(setup
+            .acceptor
+            .store
+            .get_contracts()
+            .await
+            .expect("contracts")
+            .is_empty());
+
+        setup
+            .acceptor
+            .receive(&offer, &setup.offerer)
+            .await
+            .expect("to receive the offer");
+    });
+}
+
+#[test]
+fn test_retry_failed_accept() {
+    let setup = setup();
+    block_on(async {
+        setup.oracle.announce(
+            "retry",
+            MATURITY,
+            EventDescriptor::EnumEvent(EnumEventDescriptor {
+                outcomes: vec!["offer".to_string(), "accept".to_string()],
+            }),
+        );
+        let acceptor_key = setup.acceptor.identity();
+        let offer = setup
+            .offerer
+            .manager
+            .send_offer(
+                &get_contract_input(&setup.oracle, "retry"),
+                acceptor_key,
+                REFUND_DELAY,
+                0,
+                setup.offerer.wallet.address.clone(),
+            )
+            .await
+            .expect("to send the offer");
+        let temporary_id = offer.temporary_contract_id;
+        setup
+            .acceptor
+            .receive(&DlcMessage::Offer(offer), &setup.offerer)
+            .await
+            .expect("to receive the offer");
+        let (_, _, accept) = setup
+            .acceptor
+            .manager
+            .accept_contract_offer(&temporary_id)
+            .await
+            .expect("to accept the offer");
+
+        setup
+            .offerer
+            .wallet
+            .set_signing_error(Some("wallet locked".to_string()));
+        setup
+            .offerer
+            .receive(&DlcMessage::Accept(accept), &setup.acceptor)
+            .await
+            .expect_err("signing to fail");
+        assert!(matches!(
+            get_contract(&setup.offerer.store, &temporary_id).await,
+            Contract::FailedAccept(_)
+        ));
+        setup
+            .offerer
+            .manager
+            .retry_failed_accept(&temporary_id)
+            .await
+            .expect_err("signing to fail again");
+        let failure = setup
+            .offerer
+            .manager
+            .get_contract_metadata(&temporary_id)
+            .await
+            .expect("to read the metadata")
+            .and_then(|metadata| metadata.failure)
+            .expect("the failure to be recorded");
+        assert_eq!(failure.kind, FailureKind::Transient);
+        assert_eq!(failure.attempts, 2);
+
+        setup.offerer.wallet.set_signing_error(None);
+        let (sign, counter_party) = setup
+            .offerer
+            .manager
+            .retry_failed_accept(&temporary_id)
+            .await
+            .expect("the retry to succeed");
+        assert_eq!(counter_party, acceptor_key);
+        setup
+            .acceptor
+            .receive(&DlcMessage::Sign(sign.clone()), &setup.offerer)
+            .await
+            .expect("to receive the sign message");
+
+        assert!(matches!(
+            get_contract(&setup.offerer.store, &sign.contract_id).await,
+            Contract::Signed(_)
+        ));
+        assert_eq!(setup.blockchain.get_mempool().len(), 1);
+        let metadata = setup
+            .offerer
+            .manager
+            .get_contract_metadata(&temporary_id)
+            .await
+            .expect("to read the metadata");
+        assert!(metadata.and_then(|metadata| metadata.failure).is_none());
+    });
+}
+
+#[test]
+fn test_mutual_close() {
+    let mut setup = setup();
+    let observer = RecordingObserver::default();
+    setup
+        .acceptor
+        .manager
+        .add_observer(Box::new(observer.clone()));
+    block_on(async {
+        let contract_id = fund_contract(&setup, "mutual").await;
+        let offerer_key = setup.offerer.identity();
+        let acceptor_key = setup.acceptor.identity();
+
+        let close_offer = setup
+            .offerer
+            .manager
+            .offer_mutual_close(&contract_id, 30_000)
+            .await
+            .expect("to offer to close");
+        setup
+            .offerer
+            .manager
+            .offer_mutual_close(&contract_id, 40_000)
+            .await
+            .expect_err("an offer paying the counter party less to be refused");
+        setup
+            .acceptor
+            .manager
+            .on_close_offer(&close_offer, offerer_key, 80_000)
+            .await
+            .expect_err("an offer paying too little to be refused");
+
+        // The refused offer is replaced by one paying the acceptor more.
+        let close_offer = setup
+            .offerer
+            .manager
+            .offer_mutual_close(&contract_id, 20_000)
+            .await
+            .expect("to offer to close again");
+        let close_accept = setup
+            .acceptor
+            .manager
+            .on_close_offer(&close_offer, offerer_key, 80_000)
+            .await
+            .expect("to accept the close offer");
+        setup
+            .offerer
+            .manager
+            .on_close_accept(&close_accept, acceptor_key)
+            .await
+            .expect("to receive the close acceptance");
+
+        let close_txid = match setup.blockchain.get_mempool().as_slice() {
+            [txid] => *txid,
+            mempool => panic!("Expected the close transaction alone, got {:?}", mempool),
+        };
+        assert!(matches!(
+            get_contract(&setup.acceptor.store, &contract_id).await,
+            Contract::Confirmed(_)
+        ));
+
+        setup.blockchain.mine_blocks(1);
+        let report = setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert_eq!(report.closed.len(), 1);
+        assert_eq!(report.closed[0].txid, close_txid);
+        setup.offerer.manager.periodic_check().await.expect("check");
+        for store in &[&setup.offerer.store, &setup.acceptor.store] {
+            match get_contract(store, &contract_id).await {
+                Contract::Closed(c) => assert!(c.signed_cet.is_none()),
+                _ => panic!("Expected the contract to be closed"),
+            }
+            let metadata = store
+                .get_contract_metadata(&contract_id)
+                .await
+                .expect("to read the metadata")
+                .expect("the contract to have metadata");
+            assert_eq!(
+                metadata.mutual_close_tx.map(|close_tx| close_tx.txid()),
+                Some(close_txid)
+            );
+        }
+
+        let transitions = observer
+            .transitions
+            .lock()
+            .expect("transitions lock poisoned");
+        let closed = transitions
+            .iter()
+            .find(|t| t.new_state == ContractState::Closed)
+            .expect("a transition to Closed");
+        assert_eq!(closed.previous_state, Some(ContractState::Confirmed));
+        assert_eq!(closed.event_id.as_deref(), Some("mutual"));
+        assert_eq!(closed.txids, vec![close_txid]);
+    });
+}
+
+#[test]
+fn test_bump_stuck_funding_fee() {
+    let setup = setup_with_config(ManagerConfig {
+        fee_bump_delay: Some(2),
+        ..get_config()
+    });
+    block_on(async {
+        let contract_id = sign_contract(&setup, "stuck_funding").await;
+        let fund_txid = setup.blockchain.get_mempool()[0];
+
+        // The first check only records since when the transaction is stuck.
+        setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        setup.blockchain.mine_empty_blocks(1);
+        setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert_eq!(setup.blockchain.get_mempool(), vec![fund_txid]);
+
+        setup.blockchain.mine_empty_blocks(1);
+        setup.blockchain.set_fee_rate(20);
+        setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        let metadata = setup
+            .acceptor
+            .manager
+            .get_contract_metadata(&contract_id)
+            .await
+            .expect("to read the metadata")
+            .expect("the contract to have metadata");
+        let fee_bump = match metadata.fee_bumps.as_slice() {
+            [fee_bump] => fee_bump.clone(),
+            fee_bumps => panic!("Expected a single fee bump, got {:?}", fee_bumps),
+        };
+        assert_eq!(fee_bump.parent_txid, fund_txid);
+        let mut mempool = setup.blockchain.get_mempool();
+        mempool.sort();
+        let mut expected = vec![fund_txid, fee_bump.txid];
+        expected.sort();
+        assert_eq!(mempool, expected);
+
+        setup.blockchain.mine_blocks(1);
+        setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert!(matches!(
+            get_contract(&setup.acceptor.store, &contract_id).await,
+            Contract::Confirmed(_)
+        ));
+    });
+}
+
+#[test]
+fn test_bump_stuck_cet_fee() {
+    let setup = setup_with_config(ManagerConfig {
+        fee_bump_delay: Some(1),
+        ..get_config()
+    });
+    block_on(async {
+        let contract_id = fund_contract(&setup, "stuck_cet").await;
+
+        setup.time.set_time(MATURITY as u64 + 1);
+        setup
+            .oracle
+            .attest("stuck_cet", vec!["accept".to_string()])
+            .expect("to attest the event");
+        setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        let cet_txid = match get_contract(&setup.acceptor.store, &contract_id).await {
+            Contract::PreClosed(c) => c.signed_cet.txid(),
+            _ => panic!("Expected the contract to be pre-closed"),
+        };
+
+        setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert_eq!(setup.blockchain.get_mempool(), vec![cet_txid]);
+        setup.blockchain.mine_empty_blocks(1);
+        setup.blockchain.set_fee_rate(20);
+        setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert_eq!(setup.blockchain.get_mempool().len(), 2);
+        let metadata = setup
+            .acceptor
+            .manager
+            .get_contract_metadata(&contract_id)
+            .await
+            .expect("to read the metadata")
+            .expect("the contract to have metadata");
+        assert_eq!(metadata.fee_bumps.len(), 1);
+        assert_eq!(metadata.fee_bumps[0].parent_txid, cet_txid);
+
+        setup.blockchain.mine_blocks(1);
+        setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert!(matches!(
+            get_contract(&setup.acceptor.store, &contract_id).await,
+            Contract::Closed(_)
+        ));
+    });
+}
+
+#[test]
+fn test_rebroadcast_evicted_funding() {
+    let setup = setup_with_config(ManagerConfig {
+        rebroadcast_interval: 60,
+        max_rebroadcast_attempts: 2,
+        ..get_config()
+    });
+    block_on(async {
+        let contract_id = sign_contract(&setup, "evicted").await;
+        let fund_txid = setup.blockchain.get_mempool()[0];
+
+        // Only the acceptor, which broadcast the funding transaction, holds
+        // all its signatures.
+        setup.blockchain.evict(&fund_txid);
+        setup.offerer.manager.periodic_check().await.expect("check");
+        assert!(setup.blockchain.get_mempool().is_empty());
+        setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert_eq!(setup.blockchain.get_mempool(), vec![fund_txid]);
+        assert_eq!(
+            get_rebroadcast_attempts(&setup.acceptor.store, &contract_id).await,
+            1
+        );
+
+        // Further attempts wait for the rebroadcast delay.
+        setup.blockchain.evict(&fund_txid);
+        setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert!(setup.blockchain.get_mempool().is_empty());
+        setup.time.advance(60);
+        setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert_eq!(setup.blockchain.get_mempool(), vec![fund_txid]);
+        assert_eq!(
+            get_rebroadcast_attempts(&setup.acceptor.store, &contract_id).await,
+            2
+        );
+
+        // Once out of attempts, the contract is reported instead.
+        setup.blockchain.evict(&fund_txid);
+        setup.time.advance(120);
+        let report = setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert!(setup.blockchain.get_mempool().is_empty());
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].contract_id, contract_id);
+    });
+}
+
+#[test]
+fn test_expire_accepted_offer() {
+    let setup = setup_with_config(ManagerConfig {
+        offer_expiry: Some(600),
+        ..get_config()
+    });
+    block_on(async {
+        setup.oracle.announce(
+            "expire",
+            MATURITY,
+            EventDescriptor::EnumEvent(EnumEventDescriptor {
+                outcomes: vec!["offer".to_string(), "accept".to_string()],
+            }),
+        );
+        let offer = setup
+            .offerer
+            .manager
+            .send_offer(
+                &get_contract_input(&setup.oracle, "expire"),
+                setup.acceptor.identity(),
+                REFUND_DELAY,
+                0,
+                setup.offerer.wallet.address.clone(),
+            )
+            .await
+            .expect("to send the offer");
+        setup
+            .acceptor
+            .receive(&DlcMessage::Offer(offer.clone()), &setup.offerer)
+            .await
+            .expect("to receive the offer");
+        let (contract_id, _, _) = setup
+            .acceptor
+            .manager
+            .accept_contract_offer(&offer.temporary_contract_id)
+            .await
+            .expect("to accept the offer");
+        // The only UTXO of the acceptor is reserved to fund the contract.
+        assert!(setup
+            .acceptor
+            .wallet
+            .get_utxos_for_amount(1, None, false)
+            .is_err());
+
+        setup.time.set_time(START_TIME + 599);
+        let report = setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert!(report.expired_offers.is_empty());
+
+        setup.time.set_time(START_TIME + 600);
+        let report = setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert_eq!(report.expired_offers, vec![contract_id]);
+        assert!(setup
+            .acceptor
+            .wallet
+            .get_utxos_for_amount(1, None, false)
+            .is_ok());
+        assert!(setup
+            .acceptor
+            .store
+            .get_contract(&contract_id)
+            .await
+            .expect("to read the store")
+            .is_none());
+        assert!(matches!(
+            get_contract(&setup.acceptor.store, &offer.temporary_contract_id).await,
+            Contract::Rejected(_)
+        ));
+    });
+}
+
+#[test]
+fn test_accept_with_protocol_fee() {
+    let setup = setup();
+    block_on(async {
+        setup.oracle.announce(
+            "fee",
+            MATURITY,
+            EventDescriptor::EnumEvent(EnumEventDescriptor {
+                outcomes: vec!["offer".to_string(), "accept".to_string()],
+            }),
+        );
+        let fee_address = MockWallet::new(Network::Regtest).address;
+        // 9,999 basis points of 100,000 sats are 99,990 sats, which no fee
+        // denominator charges.
+        setup
+            .offerer
+            .manager
+            .send_offer(
+                &get_contract_input(&setup.oracle, "fee"),
+                setup.acceptor.identity(),
+                REFUND_DELAY,
+                9_999,
+                fee_address.clone(),
+            )
+            .await
+            .expect_err("a fee that cannot be charged exactly to be refused");
+        let offer = setup
+            .offerer
+            .manager
+            .send_offer(
+                &get_contract_input(&setup.oracle, "fee"),
+                setup.acceptor.identity(),
+                REFUND_DELAY,
+                100,
+                fee_address,
+            )
+            .await
+            .expect("to send the offer");
+        setup
+            .acceptor
+            .receive(&DlcMessage::Offer(offer.clone()), &setup.offerer)
+            .await
+            .expect("to receive the offer");
+
+        assert!(setup
+            .acceptor
+            .manager
+            .accept_contract_offer_with_protocol_fee(&offer.temporary_contract_id, 50)
+            .await
+            .is_err());
+        assert!(matches!(
+            get_contract(&setup.acceptor.store, &offer.temporary_contract_id).await,
+            Contract::Offered(_)
+        ));
+
+        let (contract_id, _, _) = setup
+            .acceptor
+            .manager
+            .accept_contract_offer_with_protocol_fee(&offer.temporary_contract_id, 100)
+            .await
+            .expect("to accept the offer");
+        match get_contract(&setup.acceptor.store, &contract_id).await {
+            Contract::Accepted(c) => {
+                let fee = get_charged_protocol_fee(2 * COLLATERAL, 100).expect("a valid fee");
+                assert_eq!(fee, 1_000);
+                assert!(c
+                    .dlc_transactions
+                    .fund
+                    .output
+                    .iter()
+                    .any(|output| output.value == fee));
+            }
+            _ => panic!("Expected an accepted contract"),
+        }
+    });
+}
+
+#[test]
+fn test_payout_table_per_contract_info() {
+    let setup = setup();
+    block_on(async {
+        for event_id in ["first", "second"] {
+            setup.oracle.announce(
+                event_id,
+                MATURITY,
+                EventDescriptor::EnumEvent(EnumEventDescriptor {
+                    outcomes: vec!["offer".to_string(), "accept".to_string()],
+                }),
+            );
+        }
+        let mut contract_input = get_contract_input(&setup.oracle, "first");
+        let even_payout = Payout {
+            offer: COLLATERAL,
+            accept: COLLATERAL,
+        };
+        contract_input.contract_infos.push(ContractInputInfo {
+            contract_descriptor: ContractDescriptor::Enum(EnumDescriptor {
+                outcome_payouts: vec![
+                    EnumerationPayout {
+                        outcome: "offer".to_string(),
+                        payout: even_payout.clone(),
+                    },
+                    EnumerationPayout {
+                        outcome: "accept".to_string(),
+                        payout: even_payout,
+                    },
+                ],
+            }),
+            oracles: OracleInput {
+                public_keys: vec![setup.oracle.public_key()],
+                event_id: "second".to_string(),
+                threshold: 1,
+            },
+        });
+        let acceptor_key = setup.acceptor.identity();
+
+        let offer = setup
+            .offerer
+            .manager
+            .send_offer(
+                &contract_input,
+                acceptor_key,
+                REFUND_DELAY,
+                0,
+                setup.offerer.wallet.address.clone(),
+            )
+            .await
+            .expect("to send the offer");
+        setup
+            .acceptor
+            .receive(&DlcMessage::Offer(offer.clone()), &setup.offerer)
+            .await
+            .expect("to receive the offer");
+        let (contract_id, _, accept) = setup
+            .acceptor
+            .manager
+            .accept_contract_offer(&offer.temporary_contract_id)
+            .await
+            .expect("to accept the offer");
+
+        let payout_table = setup
+            .acceptor
+            .manager
+            .get_payout_table(&contract_id)
+            .await
+            .expect("to get the payout table");
+        let event_ids: Vec<_> = payout_table
+            .contract_infos
+            .iter()
+            .map(|contract_info| contract_info.event_id.as_str())
+            .collect();
+        assert_eq!(event_ids, vec!["first", "second"]);
+        let offer_outcome = Outcome::Enumerated("offer".to_string());
+        let first = setup
+            .acceptor
+            .manager
+            .simulate_payout(&contract_id, "first", &offer_outcome)
+            .await
+            .expect("to simulate the first event");
+        assert_eq!(first.offer_payout, 2 * COLLATERAL);
+        assert_eq!(first.accept_payout, 0);
+        let second = setup
+            .acceptor
+            .manager
+            .simulate_payout(&contract_id, "second", &offer_outcome)
+            .await
+            .expect("to simulate the second event");
+        assert_eq!(second.offer_payout, COLLATERAL);
+        assert_eq!(second.accept_payout, COLLATERAL);
+        assert!(setup
+            .acceptor
+            .manager
+            .simulate_payout(&contract_id, "third", &offer_outcome)
+            .await
+            .is_err());
+
+        setup
+            .offerer
+            .receive(&DlcMessage::Accept(accept), &setup.acceptor)
+            .await
+            .expect("to sign the contract");
+        let exposure = setup
+            .offerer
+            .manager
+            .get_exposure()
+            .await
+            .expect("to get the exposure");
+        assert_eq!(exposure.contracts.len(), 1);
+        assert_eq!(exposure.total_collateral, COLLATERAL);
+        assert!(exposure.worst_pnl < 0);
+        assert!(exposure.best_pnl > 0);
+    });
+}
+
+async fn get_signed_channel(store: &MemoryStorage, channel_id: &ChannelId) -> SignedChannel {
+    match store
+        .get_channel(channel_id)
+        .await
+        .expect("to read the store")
+        .expect("to find the channel")
+    {
+        Channel::Signed(c) => c,
+        _ => panic!("Expected a signed channel"),
+    }
+}
+
+/// Offers, accepts and signs a channel whose contract is on the given event,
+/// then confirms its funding transaction.
+async fn open_channel(setup: &TestSetup, event_id: &str) -> (ChannelId, ContractId) {
+    setup.oracle.announce(
+        event_id,
+        MATURITY,
+        EventDescriptor::EnumEvent(EnumEventDescriptor {
+            outcomes: vec!["offer".to_string(), "accept".to_string()],
+        }),
+    );
+    let acceptor_key = setup.acceptor.identity();
+
+    let offer = setup
+        .offerer
+        .manager
+        .offer_channel(
+            &get_contract_input(&setup.oracle, event_id),
+            acceptor_key,
+            REFUND_DELAY,
+        )
+        .await
+        .expect("to offer the channel");
+    setup
+        .acceptor
+        .receive(&DlcMessage::OfferChannel(offer.clone()), &setup.offerer)
+        .await
+        .expect("to receive the channel offer");
+    let (accept, channel_id, contract_id, _) = setup
+        .acceptor
+        .manager
+        .accept_channel(&offer.temporary_channel_id)
+        .await
+        .expect("to accept the channel");
+    let sign = setup
+        .offerer
+        .receive(&DlcMessage::AcceptChannel(accept), &setup.acceptor)
+        .await
+        .expect("to sign the channel")
+        .expect("to reply with a sign message");
+    setup
+        .acceptor
+        .receive(&sign, &setup.offerer)
+        .await
+        .expect("to receive the sign message");
+
+    setup.blockchain.mine_blocks(1);
+    setup.offerer.manager.periodic_check().await.expect("check");
+    setup
+        .acceptor
+        .manager
+        .periodic_check()
+        .await
+        .expect("check");
+    for store in &[&setup.offerer.store, &setup.acceptor.store] {
+        assert!(matches!(
+            get_contract(store, &contract_id).await,
+            Contract::Confirmed(_)
+        ));
+        assert!(matches!(
+            get_signed_channel(store, &channel_id).await.state,
+            SignedChannelState::Established { .. }
+        ));
+    }
+
+    (channel_id, contract_id)
+}
+
+#[test]
+fn test_channel_collaborative_close() {
+    let setup = setup();
+    block_on(async {
+        let (channel_id, contract_id) = open_channel(&setup, "channel_close").await;
+
+        let (close_offer, _) = setup
+            .offerer
+            .manager
+            .offer_collaborative_close(&channel_id, COLLATERAL)
+            .await
+            .expect("to offer to close the channel");
+        setup
+            .acceptor
+            .receive(
+                &DlcMessage::CollaborativeCloseOffer(close_offer),
+                &setup.offerer,
+            )
+            .await
+            .expect("to receive the close offer");
+        setup
+            .acceptor
+            .manager
+            .accept_collaborative_close(&channel_id)
+            .await
+            .expect("to accept the close offer");
+        assert_eq!(setup.blockchain.get_mempool().len(), 1);
+        assert!(matches!(
+            get_contract(&setup.acceptor.store, &contract_id).await,
+            Contract::Closed(_)
+        ));
+
+        setup.blockchain.mine_blocks(1);
+        let report = setup.offerer.manager.periodic_check().await.expect("check");
+        assert_eq!(report.closed_channels, vec![channel_id]);
+        assert!(matches!(
+            get_contract(&setup.offerer.store, &contract_id).await,
+            Contract::Closed(_)
+        ));
+        assert!(matches!(
+            get_signed_channel(&setup.offerer.store, &channel_id)
+                .await
+                .state,
+            SignedChannelState::CollaborativelyClosed
+        ));
+    });
+}
+
+#[test]
+fn test_channel_close_offer_on_attestation() {
+    let setup = setup();
+    block_on(async {
+        let (channel_id, contract_id) = open_channel(&setup, "channel_close_offer").await;
+        let acceptor_script = setup.acceptor.wallet.address.script_pubkey();
+        let balance_before = setup.blockchain.get_balance(&acceptor_script, false);
+
+        setup.time.set_time(MATURITY as u64 + 1);
+        setup
+            .oracle
+            .attest("channel_close_offer", vec!["accept".to_string()])
+            .expect("to attest the event");
+        let report = setup.offerer.manager.periodic_check().await.expect("check");
+        let close_offer = match report.close_offers.as_slice() {
+            [offer] => offer.clone(),
+            offers => panic!("Expected a single close offer, got {:?}", offers),
+        };
+        assert_eq!(close_offer.channel_id, channel_id);
+        assert_eq!(close_offer.counter_party, setup.acceptor.identity());
+
+        setup
+            .acceptor
+            .receive(
+                &DlcMessage::CollaborativeCloseOffer(close_offer.close_offer),
+                &setup.offerer,
+            )
+            .await
+            .expect("to receive the close offer");
+        setup
+            .acceptor
+            .manager
+            .accept_collaborative_close(&channel_id)
+            .await
+            .expect("to accept the close offer");
+        assert_eq!(setup.blockchain.get_mempool().len(), 1);
+        // The close transaction pays the accept party what the attested CET
+        // would have.
+        let payout = setup.blockchain.get_balance(&acceptor_script, true) - balance_before;
+        assert!(payout > 2 * COLLATERAL - 10_000 && payout <= 2 * COLLATERAL);
+
+        setup.blockchain.mine_blocks(1);
+        let report = setup.offerer.manager.periodic_check().await.expect("check");
+        assert_eq!(report.closed_channels, vec![channel_id]);
+        assert!(report.force_closed_channels.is_empty());
+        assert!(matches!(
+            get_contract(&setup.offerer.store, &contract_id).await,
+            Contract::Closed(_)
+        ));
+    });
+}
+
+#[test]
+fn test_channel_force_close_on_attestation() {
+    let setup = setup();
+    block_on(async {
+        let (channel_id, contract_id) = open_channel(&setup, "channel_force_close").await;
+        let acceptor_script = setup.acceptor.wallet.address.script_pubkey();
+        let balance_before = setup.blockchain.get_balance(&acceptor_script, false);
+
+        setup.time.set_time(MATURITY as u64 + 1);
+        setup
+            .oracle
+            .attest("channel_force_close", vec!["accept".to_string()])
+            .expect("to attest the event");
+        // The offer party offers to close the channel cooperatively first.
+        let report = setup.offerer.manager.periodic_check().await.expect("check");
+        assert_eq!(report.close_offers.len(), 1);
+        assert_eq!(report.close_offers[0].channel_id, channel_id);
+        assert!(report.force_closed_channels.is_empty());
+        assert!(setup.blockchain.get_mempool().is_empty());
+
+        // The offer never reaches the accept party, which waits for it before
+        // closing the channel unilaterally.
+        let report = setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert!(report.force_closed_channels.is_empty());
+        assert!(setup.blockchain.get_mempool().is_empty());
+
+        setup.time.set_time(MATURITY as u64 + PEER_TIMEOUT + 2);
+        let report = setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert_eq!(report.force_closed_channels, vec![channel_id]);
+        assert_eq!(setup.blockchain.get_mempool().len(), 1);
+
+        // The CET only becomes valid once the buffer transaction is deep enough.
+        setup.blockchain.mine_blocks(1);
+        let report = setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert!(report.closed_channels.is_empty());
+        assert!(setup.blockchain.get_mempool().is_empty());
+
+        setup.blockchain.mine_blocks(CET_NSEQUENCE as u64);
+        let report = setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert_eq!(report.closed_channels, vec![channel_id]);
+        assert!(matches!(
+            get_contract(&setup.acceptor.store, &contract_id).await,
+            Contract::PreClosed(_)
+        ));
+        assert!(matches!(
+            get_signed_channel(&setup.acceptor.store, &channel_id)
+                .await
+                .state,
+            SignedChannelState::Closed
+        ));
+
+        setup.blockchain.mine_blocks(1);
+        setup
+            .acceptor
+            .manager
+            .periodic_check()
+            .await
+            .expect("check");
+        assert!(matches!(
+            get_contract(&setup.acceptor.store, &contract_id).await,
+            Contract::Closed(_)
+        ));
+        let payout = setup.blockchain.get_balance(&acceptor_script, false) - balance_before;
+        assert!(payout > 2 * COLLATERAL - 10_000 && payout <= 2 * COLLATERAL);
+    });
+}
+
+#[test]
+fn test_channel_timeout_rolls_back() {
+    let setup = setup();
+    block_on(async {
+        let (channel_id, _) = open_channel(&setup, "channel_timeout").await;
+
+        setup
+            .offerer
+            .manager
+            .settle_offer(&channel_id, COLLATERAL)
+            .await
+            .expect("to offer to settle the channel");
+        assert!(matches!(
+            get_signed_channel(&setup.offerer.store, &channel_id)
+                .await
+                .state,
+            SignedChannelState::SettledOffered { .. }
+        ));
+
+        // Before the event is attested, the channel can only be rolled back.
+        setup.time.advance(PEER_TIMEOUT + 1);
+        let report = setup.offerer.manager.periodic_check().await.expect("check");
+        assert!(report.force_closed_channels.is_empty());
+        assert!(report.channel_errors.is_empty());
+        assert!(matches!(
+            get_signed_channel(&setup.offerer.store, &channel_id)
+                .await
+                .state,
+            SignedChannelState::Established { .. }
+        ));
+
+        setup.time.set_time(MATURITY as u64 + 1);
+        setup
+            .oracle
+            .attest("channel_timeout", vec!["offer".to_string()])
+            .expect("to attest the event");
+        let report = setup.offerer.manager.periodic_check().await.expect("check");
+        assert_eq!(report.close_offers.len(), 1);
+        assert!(report.force_closed_channels.is_empty());
+        assert!(matches!(
+            get_signed_channel(&setup.offerer.store, &channel_id)
+                .await
+                .state,
+            SignedChannelState::CollaborativeCloseOffered { .. }
+        ));
+
+        // The counter party does not answer the close offer either.
+        setup.time.advance(PEER_TIMEOUT + 1);
+        let report = setup.offerer.manager.periodic_check().await.expect("check");
+        assert_eq!(report.force_closed_channels, vec![channel_id]);
+        assert!(matches!(
+            get_signed_channel(&setup.offerer.store, &channel_id)
+                .await
+                .state,
+            SignedChannelState::Closing { .. }
+        ));
+    });
+}
+
+/// Renews the contract of the channel with a contract on the given event,
+/// returning the id of the new contract.
+async fn renew_channel(setup: &TestSetup, channel_id: &ChannelId, event_id: &str) -> ContractId {
+    setup.oracle.announce(
+        event_id,
+        MATURITY,
+        EventDescriptor::EnumEvent(EnumEventDescriptor {
+            outcomes: vec!["offer".to_string(), "accept".to_string()],
+        }),
+    );
+
+    let (renew_offer, _) = setup
+        .offerer
+        .manager
+        .renew_offer(
+            channel_id,
+            COLLATERAL,
+            &get_contract_input(&setup.oracle, event_id),
+            REFUND_DELAY,
+        )
+        .await
+        .expect("to offer the renewal");
+    setup
+        .acceptor
+        .receive(&DlcMessage::RenewOffer(renew_offer), &setup.offerer)
+        .await
+        .expect("to receive the renew offer");
+    let (renew_accept, _) = setup
+        .acceptor
+        .manager
+        .accept_renew_offer(channel_id)
+        .await
+        .expect("to accept the renewal");
+    let renew_confirm = setup
+        .offerer
+        .receive(&DlcMessage::RenewAccept(renew_accept), &setup.acceptor)
+        .await
+        .expect("to confirm the renewal")
+        .expect("to reply with a renew confirm message");
+    let renew_finalize = setup
+        .acceptor
+        .receive(&renew_confirm, &setup.offerer)
+        .await
+        .expect("to finalize the renewal")
+        .expect("to reply with a renew finalize message");
+    setup
+        .offerer
+        .receive(&renew_finalize, &setup.acceptor)
+        .await
+        .expect("to receive the renew finalize message");
+
+    match get_signed_channel(&setup.offerer.store, channel_id)
+        .await
+        .state
+    {
+        SignedChannelState::Established {
+            signed_contract_id, ..
+        } => signed_contract_id,
+        _ => panic!("Expected the renewed channel to be established"),
+    }
+}
+
+#[test]
+fn test_channel_renewal_links_contracts() {
+    let setup = setup();
+    block_on(async {
+        let (channel_id, first_id) = open_channel(&setup, "renew_first").await;
+        let second_id = renew_channel(&setup, &channel_id, "renew_second").await;
+        let third_id = renew_channel(&setup, &channel_id, "renew_third").await;
+
+        for store in &[&setup.offerer.store, &setup.acceptor.store] {
+            assert!(matches!(
+                get_contract(store, &first_id).await,
+                Contract::Closed(_)
+            ));
+            assert!(matches!(
+                get_contract(store, &second_id).await,
+                Contract::Closed(_)
+            ));
+            assert!(matches!(
+                get_contract(store, &third_id).await,
+                Contract::Confirmed(_)
+            ));
+            let metadata = store
+                .get_contract_metadata(&second_id)
+                .await
+                .expect("to read the metadata")
+                .expect("the renewed contract to have metadata");
+            assert_eq!(metadata.predecessor, Some(first_id));
+            assert_eq!(metadata.successor, Some(third_id));
+        }
+
+        for contract_id in &[first_id, second_id] {
+            let renewed = setup
+                .acceptor
+                .manager
+                .get_renewed_contract(contract_id)
+                .await
+                .expect("to follow the renewals")
+                .expect("the contract to be renewed");
+            assert_eq!(renewed.get_id(), third_id);
+        }
+        assert!(setup
+            .acceptor
+            .manager
+            .get_renewed_contract(&third_id)
+            .await
+            .expect("to follow the renewals")
+            .is_none());
+    });
+}
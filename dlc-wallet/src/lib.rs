@@ -1,4 +1,5 @@
-use bitcoin::Address;
+use bitcoin::{Address, OutPoint};
+use dlc_link_manager::ReservingWallet;
 use dlc_manager::{error::Error, Signer, Utxo, Wallet};
 use secp256k1_zkp::{All, PublicKey, Secp256k1, SecretKey};
 
@@ -67,3 +68,10 @@ impl Wallet for DlcWallet {
         Ok(())
     }
 }
+
+impl ReservingWallet for DlcWallet {
+    fn unreserve_utxos(&self, _outpoints: &[OutPoint]) -> Result<()> {
+        // No UTXOs are ever reserved, see get_utxos_for_amount.
+        Ok(())
+    }
+}
@@ -11,7 +11,7 @@ use dlc_manager::{error::Error, Blockchain, Utxo};
 
 use js_interface_wallet::WalletBlockchainProvider;
 use lightning::chain::chaininterface::FeeEstimator;
-use reqwest::Response;
+use reqwest::{Response, StatusCode};
 
 use serde::{Deserialize, Serialize};
 
@@ -52,6 +52,7 @@ pub enum UtxoStatus {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct UTXOSpent {
     spent: bool,
+    txid: Option<String>,
 }
 
 pub struct EsploraAsyncBlockchainProviderJsWallet {
@@ -316,6 +317,58 @@ impl AsyncBlockchain for EsploraAsyncBlockchainProviderJsWallet {
         Transaction::consensus_decode(&mut std::io::Cursor::new(&*raw_tx))
             .map_err(|e| Error::BlockchainError(e.to_string()))
     }
+
+    async fn get_spending_txid_async(&self, outpoint: &OutPoint) -> Result<Option<Txid>, Error> {
+        let outspend = self
+            .get_from_json::<UTXOSpent>(&format!("tx/{}/outspend/{}", outpoint.txid, outpoint.vout))
+            .await?;
+        match (outspend.spent, outspend.txid) {
+            (true, Some(txid)) => Txid::from_str(&txid)
+                .map(Some)
+                .map_err(|e| Error::BlockchainError(e.to_string())),
+            _ => Ok(None),
+        }
+    }
+
+    async fn is_transaction_known_async(&self, tx_id: &Txid) -> Result<bool, Error> {
+        // The status endpoint answers unknown transactions as unconfirmed,
+        // only the transaction endpoint fails for them.
+        let response = self.get(&format!("tx/{tx_id}")).await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(false),
+            status if status.is_success() => Ok(true),
+            status => Err(Error::BlockchainError(format!(
+                "Unexpected status {} looking up transaction {}",
+                status, tx_id
+            ))),
+        }
+    }
+
+    async fn get_blockchain_height_async(&self) -> Result<u64, Error> {
+        self.blockchain
+            .get_height()
+            .await
+            .map(|height| height as u64)
+            .map_err(|e| Error::BlockchainError(format!("Error getting blockchain height: {}", e)))
+    }
+
+    async fn get_fee_rate_async(&self, confirmation_target: u16) -> Result<u64, Error> {
+        // Esplora returns fee rates in sats per vbyte, keyed by confirmation
+        // target, for a fixed set of targets.
+        let estimates = self
+            .get_from_json::<HashMap<String, f64>>("fee-estimates")
+            .await?;
+        estimates
+            .into_iter()
+            .filter_map(|(target, fee_rate)| target.parse::<u16>().ok().map(|t| (t, fee_rate)))
+            .filter(|(target, _)| *target <= confirmation_target.max(1))
+            .max_by_key(|(target, _)| *target)
+            .map(|(_, fee_rate)| fee_rate.ceil() as u64)
+            .ok_or(Error::BlockchainError(format!(
+                "No fee estimate for a target of {} blocks",
+                confirmation_target
+            )))
+    }
 }
 
 impl Blockchain for EsploraAsyncBlockchainProviderJsWallet {
@@ -420,4 +473,45 @@ mod tests {
             chain_data.deref()
         );
     }
+
+    #[actix_rt::test]
+    async fn is_transaction_known_only_for_existing_transactions() {
+        let mut server = mockito::Server::new();
+        let known_txid =
+            Txid::from_str("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b")
+                .expect("to parse the txid");
+        let unknown_txid =
+            Txid::from_str("0e3e2357e806b6cdb1f70b54c3a3a17b6714ee1f0e68bebb44a74b1efd512098")
+                .expect("to parse the txid");
+        server
+            .mock("GET", format!("/tx/{}", known_txid).as_str())
+            .with_status(200)
+            .with_body("{}")
+            .create_async()
+            .await;
+        server
+            .mock("GET", format!("/tx/{}", unknown_txid).as_str())
+            .with_status(404)
+            .with_body("Transaction not found")
+            .create_async()
+            .await;
+        // Esplora reports unknown transactions as unconfirmed.
+        server
+            .mock("GET", format!("/tx/{}/status", unknown_txid).as_str())
+            .with_status(200)
+            .with_body(r#"{"confirmed":false}"#)
+            .create_async()
+            .await;
+
+        let provider =
+            EsploraAsyncBlockchainProviderJsWallet::new(server.url(), bitcoin::Network::Regtest);
+        assert!(provider
+            .is_transaction_known_async(&known_txid)
+            .await
+            .expect("to look up the transaction"));
+        assert!(!provider
+            .is_transaction_known_async(&unknown_txid)
+            .await
+            .expect("to look up the transaction"));
+    }
 }
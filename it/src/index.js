@@ -60,9 +60,21 @@ async function attest(attestorURL, uuid, outcome) {
   }
 }
 
-async function fetchOfferFromProtocolWallet(uuid, overrides = {}) {
+async function fetchProtocolWalletPublicKey() {
+  try {
+    const res = await fetch(`${protocolWalletURL}/info`);
+    const info = await res.json();
+    return info.wallet.publicKey;
+  } catch (error) {
+    console.error('Error fetching protocol wallet info: ', error);
+    process.exit(1);
+  }
+}
+
+async function fetchOfferFromProtocolWallet(uuid, counterpartyPublicKey, overrides = {}) {
   let body = {
     uuid,
+    counterpartyPublicKey,
     acceptCollateral,
     refundDelay: 86400 * 7,
     btcFeeRecipient: btcFeeRecipient,
@@ -86,12 +98,14 @@ async function fetchOfferFromProtocolWallet(uuid, overrides = {}) {
   }
 }
 
-async function sendAcceptedOfferToProtocolWallet(accepted_offer) {
+async function sendAcceptedOfferToProtocolWallet(accepted_offer, counterpartyPublicKey, signature) {
   try {
     const res = await fetch(`${protocolWalletURL}/offer/accept`, {
       method: 'put',
       body: JSON.stringify({
         acceptMessage: accepted_offer,
+        counterpartyPublicKey,
+        signature,
       }),
       headers: { 'Content-Type': 'application/json' },
     });
@@ -179,7 +193,7 @@ async function setupDLC(dlcManager, uuid, time, overrides = {}) {
 
   //Fetching Offer
   console.log('Fetching Offer from Protocol Wallet');
-  const offerResponse = await fetchOfferFromProtocolWallet(uuid, { ...overrides });
+  const offerResponse = await fetchOfferFromProtocolWallet(uuid, dlcManager.get_public_key(), { ...overrides });
 
   //Check if the offer is valid
   if (!offerResponse.temporaryContractId) {
@@ -198,7 +212,11 @@ async function setupDLC(dlcManager, uuid, time, overrides = {}) {
   }
 
   //Sending Accepted Offer to Protocol Wallet
-  const signedContract = await sendAcceptedOfferToProtocolWallet(acceptedContract);
+  const signedContract = await sendAcceptedOfferToProtocolWallet(
+    acceptedContract,
+    dlcManager.get_public_key(),
+    dlcManager.sign_accept_message(acceptedContract)
+  );
 
   //Check if the signed contract is valid
   if (!signedContract.contractId) {
@@ -334,7 +352,8 @@ async function main() {
     testWalletAddress,
     bitcoinNetwork,
     bitcoinNetworkURL,
-    storageApiUrl
+    storageApiUrl,
+    await fetchProtocolWalletPublicKey()
   );
 
   await checkBalance(dlcManager, '[STARTING BALANCE]');
@@ -7,7 +7,8 @@ use bdk::{
     wallet::coin_selection::{BranchAndBoundCoinSelection, CoinSelectionAlgorithm},
     FeeRate, KeychainKind, LocalUtxo, Utxo as BdkUtxo, WeightedUtxo,
 };
-use bitcoin::{hashes::Hash, Address, PrivateKey, Script, Txid};
+use bitcoin::{hashes::Hash, Address, OutPoint, PrivateKey, Script, Txid};
+use dlc_link_manager::ReservingWallet;
 use dlc_manager::{error::Error, Blockchain, Signer, Utxo, Wallet};
 use lightning::chain::chaininterface::FeeEstimator;
 use secp256k1_zkp::{All, PublicKey, Secp256k1, SecretKey};
@@ -146,6 +147,14 @@ impl Wallet for JSInterfaceWallet {
     }
 }
 
+impl ReservingWallet for JSInterfaceWallet {
+    fn unreserve_utxos(&self, _outpoints: &[OutPoint]) -> Result<()> {
+        // Selected UTXOs are not reserved, the set is refreshed from the
+        // blockchain instead.
+        Ok(())
+    }
+}
+
 #[derive(Clone)]
 struct UtxoWrap {
     utxo: Utxo,
@@ -0,0 +1,55 @@
+use crate::DbPool;
+use actix_web::web;
+use actix_web::web::{Data, Json};
+use actix_web::{delete, get, put, HttpResponse, Responder};
+use dlc_storage_common::models::{ChannelRequestParams, DeleteChannel, UpsertChannel};
+use log::{debug, warn};
+use serde_json::json;
+
+#[get("/channels")]
+pub async fn get_channels(
+    pool: Data<DbPool>,
+    channel_params: web::Query<ChannelRequestParams>,
+) -> impl Responder {
+    let mut conn = pool.get().expect("couldn't get db connection from pool");
+    match dlc_storage_reader::get_channels(&mut conn, channel_params.into_inner()) {
+        Ok(channels) => HttpResponse::Ok().json(channels),
+        Err(e) => {
+            warn!("Error getting channels: {:?}", e);
+            HttpResponse::BadRequest().body(e.to_string())
+        }
+    }
+}
+
+#[put("/channels")]
+pub async fn upsert_channel(
+    pool: Data<DbPool>,
+    channel_params: Json<UpsertChannel>,
+) -> impl Responder {
+    let mut conn = pool.get().expect("couldn't get db connection from pool");
+    match dlc_storage_writer::upsert_channel(&mut conn, channel_params.into_inner()) {
+        Ok(channel) => {
+            debug!("Upserted channel: {:?}", channel.uuid);
+            HttpResponse::Ok().json(channel)
+        }
+        Err(e) => {
+            warn!("Error upserting channel: {:?}", e);
+            HttpResponse::BadRequest().body(e.to_string())
+        }
+    }
+}
+
+#[delete("/channel")]
+pub async fn delete_channel(
+    pool: Data<DbPool>,
+    channel_params: Json<DeleteChannel>,
+) -> impl Responder {
+    let mut conn = pool.get().expect("couldn't get db connection from pool");
+    match dlc_storage_writer::delete_channel(&mut conn, channel_params.into_inner()) {
+        Ok(num_deleted) => HttpResponse::Ok().json(json!({ "effected_num": num_deleted })),
+        Err(e) => {
+            warn!("Error deleting channel: {:?}", e);
+            HttpResponse::BadRequest().body(e.to_string())
+        }
+    }
+}
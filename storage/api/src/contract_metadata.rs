@@ -0,0 +1,55 @@
+use crate::DbPool;
+use actix_web::web;
+use actix_web::web::{Data, Json};
+use actix_web::{delete, get, put, HttpResponse, Responder};
+use dlc_storage_common::models::{
+    ContractMetadataRequestParams, DeleteContractMetadata, NewContractMetadata,
+};
+use log::warn;
+use serde_json::json;
+
+#[get("/contract-metadata")]
+pub async fn get_contract_metadata(
+    pool: Data<DbPool>,
+    metadata_params: web::Query<ContractMetadataRequestParams>,
+) -> impl Responder {
+    let mut conn = pool.get().expect("couldn't get db connection from pool");
+    match dlc_storage_reader::get_contract_metadata(&mut conn, metadata_params.into_inner()) {
+        Ok(Some(metadata)) => HttpResponse::Ok().json(metadata),
+        Ok(None) => HttpResponse::NotFound().body("No contract metadata found"),
+        Err(e) => {
+            warn!("Error getting contract metadata: {:?}", e);
+            HttpResponse::BadRequest().body(e.to_string())
+        }
+    }
+}
+
+#[put("/contract-metadata")]
+pub async fn upsert_contract_metadata(
+    pool: Data<DbPool>,
+    metadata: Json<NewContractMetadata>,
+) -> impl Responder {
+    let mut conn = pool.get().expect("couldn't get db connection from pool");
+    match dlc_storage_writer::upsert_contract_metadata(&mut conn, metadata.into_inner()) {
+        Ok(metadata) => HttpResponse::Ok().json(metadata),
+        Err(e) => {
+            warn!("Error upserting contract metadata: {:?}", e);
+            HttpResponse::BadRequest().body(e.to_string())
+        }
+    }
+}
+
+#[delete("/contract-metadata")]
+pub async fn delete_contract_metadata(
+    pool: Data<DbPool>,
+    metadata: Json<DeleteContractMetadata>,
+) -> impl Responder {
+    let mut conn = pool.get().expect("couldn't get db connection from pool");
+    match dlc_storage_writer::delete_contract_metadata(&mut conn, metadata.into_inner()) {
+        Ok(num_deleted) => HttpResponse::Ok().json(json!({ "effected_num": num_deleted })),
+        Err(e) => {
+            warn!("Error deleting contract metadata: {:?}", e);
+            HttpResponse::BadRequest().body(e.to_string())
+        }
+    }
+}
@@ -3,7 +3,7 @@ use actix_web::web;
 use actix_web::web::{Data, Json, Path};
 use actix_web::{delete, get, post, put, HttpResponse, Responder};
 use dlc_storage_common::models::{
-    ContractRequestParams, DeleteContract, NewContract, UpdateContract,
+    ContractRequestParams, DeleteContract, NewContract, ReplaceContract, UpdateContract,
 };
 use log::{debug, warn};
 use serde_json::json;
@@ -61,6 +61,26 @@ pub async fn update_contract(
     }
 }
 
+#[put("/contracts/replace")]
+pub async fn replace_contract(
+    pool: Data<DbPool>,
+    contract_params: Json<ReplaceContract>,
+) -> impl Responder {
+    let mut conn = pool.get().expect("couldn't get db connection from pool");
+    let num_updated =
+        match dlc_storage_writer::replace_contract(&mut conn, contract_params.into_inner()) {
+            Ok(num_updated) => num_updated,
+            Err(e) => {
+                warn!("Error replacing contract: {:?}", e);
+                return HttpResponse::BadRequest().body(e.to_string());
+            }
+        };
+    match num_updated {
+        0 => HttpResponse::NotFound().body("No contract found"),
+        _ => HttpResponse::Ok().json(json!({ "effected_num": num_updated })),
+    }
+}
+
 #[delete("/contract")]
 pub async fn delete_contract(
     pool: Data<DbPool>,
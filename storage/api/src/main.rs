@@ -1,11 +1,15 @@
 #![deny(clippy::unwrap_used)]
 #![deny(unused_mut)]
 #![deny(dead_code)]
+mod channels;
+mod contract_metadata;
 mod contracts;
 mod events;
 mod verify_sigs;
 
 use actix_cors::Cors;
+use channels::*;
+use contract_metadata::*;
 use contracts::*;
 use events::*;
 use rand::distributions::{Alphanumeric, DistString};
@@ -115,8 +119,15 @@ async fn main() -> std::io::Result<()> {
             .service(get_contracts)
             .service(create_contract)
             .service(update_contract)
+            .service(replace_contract)
             .service(delete_contract)
             .service(delete_contracts)
+            .service(get_contract_metadata)
+            .service(upsert_contract_metadata)
+            .service(delete_contract_metadata)
+            .service(get_channels)
+            .service(upsert_channel)
+            .service(delete_channel)
             .service(get_events)
             .service(create_event)
             .service(update_event)
@@ -147,7 +158,7 @@ mod tests {
 
     use serde_json::Value;
 
-    use crate::verify_sigs::AuthenticatedContractQueryParams;
+    use crate::verify_sigs::{AuthenticatedChannelQueryParams, AuthenticatedContractQueryParams};
 
     use super::*;
 
@@ -317,6 +328,72 @@ mod tests {
         Ok(())
     }
 
+    #[actix_web::test]
+    async fn test_get_channels_with_bad_sig() -> Result<(), Error> {
+        let secp = Secp256k1::new();
+        let (secret_key, public_key) = secp.generate_keypair(&mut OsRng);
+        let nonces = Data::new(Mutex::new(ServerNonce { nonces: vec![] }));
+        let unprotected_paths = Data::new(UnprotectedPaths {
+            paths: vec!["/health".to_string(), "/request_nonce".to_string()],
+        });
+        let app = init_service(
+            App::new()
+                .app_data(nonces.clone())
+                .app_data(unprotected_paths.clone())
+                .wrap_fn(|req, srv| {
+                    let header_nonce = req.headers().get("authorization");
+                    if let Some(header_nonce) = header_nonce {
+                        req.app_data::<Data<Mutex<ServerNonce>>>()
+                            .expect("Failed to get nonces from app data")
+                            .lock()
+                            .expect("Failed to unlock nonce vec")
+                            .nonces
+                            .retain(|x| x != header_nonce);
+                    }
+                    srv.call(req)
+                })
+                .wrap(verify_sigs::Verifier)
+                .service(request_nonce)
+                .service(get_channels),
+        )
+        .await;
+
+        let nonce_request = TestRequest::default()
+            .method(Method::GET)
+            .uri("/request_nonce")
+            .to_request();
+
+        let res = test::call_service(&app, nonce_request).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = to_bytes(res.into_body()).await.expect("Failed to get body");
+        let nonce = body.as_str();
+
+        let digest = Message::from(sha256::Hash::hash("nonce".to_string().as_bytes()));
+        let sig = secp.sign_ecdsa(&digest, &secret_key);
+
+        let fetch_channels = AuthenticatedChannelQueryParams {
+            uuid: None,
+            state: Some("signed".to_string()),
+            signed_state: None,
+            signature: sig.to_string(),
+            key: public_key.to_string(),
+        };
+
+        let request_query = serde_urlencoded::to_string(&fetch_channels).expect("to go!");
+
+        let req = TestRequest::default()
+            .method(Method::GET)
+            .insert_header((header::AUTHORIZATION, nonce))
+            .uri(&format!("/channels?{}", request_query))
+            .to_request();
+
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        Ok(())
+    }
+
     // POST REQUESTS WITH JSON BODY
     #[actix_web::test]
     async fn test_with_good_auth() -> Result<(), Error> {
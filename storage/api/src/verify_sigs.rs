@@ -8,6 +8,7 @@ use std::{
 use actix_http::{h1, StatusCode};
 use actix_web::{
     dev::{self, Service, ServiceRequest, ServiceResponse, Transform},
+    error::ErrorForbidden,
     web::{self, Data},
     Error,
 };
@@ -40,6 +41,15 @@ pub struct AuthenticatedContractQueryParams {
     pub signature: String,
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct AuthenticatedChannelQueryParams {
+    pub key: String, // the public key
+    pub uuid: Option<String>,
+    pub state: Option<String>,
+    pub signed_state: Option<String>,
+    pub signature: String,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct AuthenticatedEventQueryParams {
     pub key: String, // the public key
@@ -172,8 +182,31 @@ where
                     }
                     Ok(res)
                 }
+                (&actix_web::http::Method::GET, p) if p.contains("/channel") => {
+                    let query_params = req
+                        .extract::<web::Query<AuthenticatedChannelQueryParams>>()
+                        .await
+                        .expect("unable to extract query params");
+
+                    // Checked before the request reaches the handler, so that
+                    // nothing is read for an unauthenticated caller.
+                    if verify_query_params(
+                        query_params.signature.clone(),
+                        query_params.key.clone(),
+                        auth_header_nonce,
+                    )
+                    .is_err()
+                        || !nonces.contains(&auth_header_nonce.to_string())
+                    {
+                        error!("Failed to verify signature or nonce on channel endpoint");
+                        error!("checking for {} in nonces: {:?}", auth_header_nonce, nonces);
+                        error!("query params: {:?}", query_params);
+                        return Err(ErrorForbidden("Invalid signature or nonce"));
+                    }
+                    svc.call(req).await
+                }
                 _ => {
-                    // POST / PUT / DELETE requests to the /event or /contract endpoints
+                    // POST / PUT / DELETE requests to the /event, /contract or /channel endpoints
                     let body = req
                         .extract::<web::Bytes>()
                         .await
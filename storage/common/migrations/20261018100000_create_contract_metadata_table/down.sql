@@ -0,0 +1 @@
+DROP TABLE contract_metadata;
\ No newline at end of file
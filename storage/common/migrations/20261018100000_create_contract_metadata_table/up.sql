@@ -0,0 +1,7 @@
+CREATE TABLE contract_metadata (
+    id serial PRIMARY KEY,
+    uuid VARCHAR NOT NULL,
+    content TEXT NOT NULL,
+    key VARCHAR NOT NULL,
+    UNIQUE (key, uuid)
+);
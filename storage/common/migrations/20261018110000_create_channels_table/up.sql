@@ -0,0 +1,9 @@
+CREATE TABLE channels (
+    id serial PRIMARY KEY,
+    uuid VARCHAR NOT NULL,
+    state VARCHAR NOT NULL,
+    signed_state VARCHAR,
+    content TEXT NOT NULL,
+    key VARCHAR NOT NULL,
+    UNIQUE (key, uuid)
+);
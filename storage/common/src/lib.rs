@@ -4,6 +4,7 @@ pub mod schema;
 use crate::models::*;
 use diesel::expression_methods::ExpressionMethods;
 use diesel::query_dsl::QueryDsl;
+use diesel::Connection;
 use diesel::RunQueryDsl;
 use diesel::{r2d2::Error, PgConnection};
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
@@ -96,6 +97,27 @@ pub fn update_contract(
     Ok(num_updated)
 }
 
+pub fn replace_contract(
+    conn: &mut PgConnection,
+    contract: ReplaceContract,
+) -> Result<usize, diesel::result::Error> {
+    use crate::schema::contracts::dsl::*;
+    // A single statement, so that the contract is never missing nor stored
+    // twice.
+    let num_updated = diesel::update(
+        contracts
+            .filter(uuid.eq(contract.uuid))
+            .filter(key.eq(contract.key)),
+    )
+    .set((
+        uuid.eq(contract.new_uuid),
+        state.eq(contract.state),
+        content.eq(contract.content),
+    ))
+    .execute(conn)?;
+    Ok(num_updated)
+}
+
 pub fn create_event(
     conn: &mut PgConnection,
     event: NewEvent,
@@ -170,3 +192,125 @@ pub fn delete_all_events(
     let num_deleted = diesel::delete(events.filter(key.eq(ckey))).execute(conn)?;
     Ok(num_deleted)
 }
+
+pub fn get_contract_metadata(
+    conn: &mut PgConnection,
+    params: ContractMetadataRequestParams,
+) -> Result<Option<ContractMetadata>, diesel::result::Error> {
+    use crate::schema::contract_metadata::dsl::*;
+    let mut results = contract_metadata
+        .filter(key.eq(params.key))
+        .filter(uuid.eq(params.uuid))
+        .load::<ContractMetadata>(conn)?;
+    Ok(results.pop())
+}
+
+pub fn upsert_contract_metadata(
+    conn: &mut PgConnection,
+    metadata: NewContractMetadata,
+) -> Result<ContractMetadata, diesel::result::Error> {
+    use crate::schema::contract_metadata::dsl::*;
+    match diesel::insert_into(contract_metadata)
+        .values(&metadata)
+        .on_conflict((key, uuid))
+        .do_update()
+        .set(&metadata)
+        .get_result(conn)
+    {
+        Ok(result) => Ok(result),
+        Err(e) => {
+            warn!("Got an error upserting contract metadata: {:?}", e);
+            Err(e)
+        }
+    }
+}
+
+pub fn delete_contract_metadata(
+    conn: &mut PgConnection,
+    metadata: DeleteContractMetadata,
+) -> Result<usize, diesel::result::Error> {
+    use crate::schema::contract_metadata::dsl::*;
+    let num_deleted = diesel::delete(
+        contract_metadata
+            .filter(uuid.eq(metadata.uuid))
+            .filter(key.eq(metadata.key)),
+    )
+    .execute(conn)?;
+    Ok(num_deleted)
+}
+
+pub fn get_channels(
+    conn: &mut PgConnection,
+    params: ChannelRequestParams,
+) -> Result<Vec<Channel>, diesel::result::Error> {
+    use crate::schema::channels::dsl::*;
+    let mut query = channels.into_boxed();
+    query = query.filter(key.eq(params.key));
+
+    if let Some(cuuid) = params.uuid {
+        query = query.filter(uuid.eq(cuuid));
+    }
+
+    if let Some(cstate) = params.state {
+        query = query.filter(state.eq(cstate));
+    }
+
+    if let Some(csigned_state) = params.signed_state {
+        query = query.filter(signed_state.eq(csigned_state));
+    }
+
+    let results = query.load::<Channel>(conn)?;
+    Ok(results)
+}
+
+pub fn upsert_channel(
+    conn: &mut PgConnection,
+    channel: UpsertChannel,
+) -> Result<Channel, diesel::result::Error> {
+    use crate::schema::channels::dsl::*;
+    let new_channel = NewChannel {
+        uuid: channel.uuid,
+        state: channel.state,
+        signed_state: channel.signed_state,
+        content: channel.content,
+        key: channel.key,
+    };
+    // The record under the temporary id goes away with the same transaction,
+    // so that the channel is never missing nor stored twice.
+    match conn.transaction(|conn| {
+        if let Some(temporary_uuid) = channel.temporary_uuid {
+            diesel::delete(
+                channels
+                    .filter(uuid.eq(temporary_uuid))
+                    .filter(key.eq(&new_channel.key)),
+            )
+            .execute(conn)?;
+        }
+        diesel::insert_into(channels)
+            .values(&new_channel)
+            .on_conflict((key, uuid))
+            .do_update()
+            .set(&new_channel)
+            .get_result(conn)
+    }) {
+        Ok(result) => Ok(result),
+        Err(e) => {
+            warn!("Got an error upserting channel: {:?}", e);
+            Err(e)
+        }
+    }
+}
+
+pub fn delete_channel(
+    conn: &mut PgConnection,
+    channel: DeleteChannel,
+) -> Result<usize, diesel::result::Error> {
+    use crate::schema::channels::dsl::*;
+    let num_deleted = diesel::delete(
+        channels
+            .filter(uuid.eq(channel.uuid))
+            .filter(key.eq(channel.key)),
+    )
+    .execute(conn)?;
+    Ok(num_deleted)
+}
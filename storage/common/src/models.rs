@@ -29,6 +29,16 @@ pub struct UpdateContract {
     pub key: String,
 }
 
+/// Replaces the contract stored under `uuid` by one stored under `new_uuid`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReplaceContract {
+    pub uuid: String,
+    pub new_uuid: String,
+    pub state: String,
+    pub content: String,
+    pub key: String,
+}
+
 #[derive(Serialize, Deserialize, AsChangeset, Debug)]
 #[diesel(table_name = contracts)]
 pub struct DeleteContract {
@@ -79,3 +89,77 @@ pub struct EventRequestParams {
     pub key: String,
     pub event_id: Option<String>,
 }
+
+#[derive(Insertable, Serialize, Deserialize, AsChangeset, Debug)]
+#[diesel(table_name = contract_metadata)]
+pub struct NewContractMetadata {
+    pub uuid: String,
+    pub content: String,
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize, Queryable, Debug)]
+pub struct ContractMetadata {
+    pub id: i32,
+    pub uuid: String,
+    pub content: String,
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeleteContractMetadata {
+    pub uuid: String,
+    pub key: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContractMetadataRequestParams {
+    pub key: String,
+    pub uuid: String,
+}
+
+#[derive(Insertable, Serialize, Deserialize, AsChangeset, Debug)]
+#[diesel(table_name = channels, treat_none_as_null = true)]
+pub struct NewChannel {
+    pub uuid: String,
+    pub state: String,
+    pub signed_state: Option<String>,
+    pub content: String,
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize, Queryable, Debug)]
+pub struct Channel {
+    pub id: i32,
+    pub uuid: String,
+    pub state: String,
+    pub signed_state: Option<String>,
+    pub content: String,
+    pub key: String,
+}
+
+/// Creates or replaces the channel stored under `uuid`, removing the record
+/// kept under `temporary_uuid` if any.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UpsertChannel {
+    pub uuid: String,
+    pub temporary_uuid: Option<String>,
+    pub state: String,
+    pub signed_state: Option<String>,
+    pub content: String,
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeleteChannel {
+    pub uuid: String,
+    pub key: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChannelRequestParams {
+    pub key: String,
+    pub uuid: Option<String>,
+    pub state: Option<String>,
+    pub signed_state: Option<String>,
+}
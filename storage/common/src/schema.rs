@@ -18,3 +18,23 @@ diesel::table! {
         key -> Varchar,
     }
 }
+
+diesel::table! {
+    contract_metadata (id) {
+        id -> Int4,
+        uuid -> Varchar,
+        content -> Text,
+        key -> Varchar,
+    }
+}
+
+diesel::table! {
+    channels (id) {
+        id -> Int4,
+        uuid -> Varchar,
+        state -> Varchar,
+        signed_state -> Nullable<Varchar>,
+        content -> Text,
+        key -> Varchar,
+    }
+}
@@ -1,5 +1,9 @@
 use diesel::PgConnection;
+use dlc_storage_common::models::Channel;
+use dlc_storage_common::models::ChannelRequestParams;
 use dlc_storage_common::models::Contract;
+use dlc_storage_common::models::ContractMetadata;
+use dlc_storage_common::models::ContractMetadataRequestParams;
 use dlc_storage_common::models::ContractRequestParams;
 use dlc_storage_common::models::Event;
 use dlc_storage_common::models::EventRequestParams;
@@ -17,3 +21,17 @@ pub fn get_events(
 ) -> Result<Vec<Event>, diesel::result::Error> {
     dlc_storage_common::get_events(conn, event_params)
 }
+
+pub fn get_contract_metadata(
+    conn: &mut PgConnection,
+    params: ContractMetadataRequestParams,
+) -> Result<Option<ContractMetadata>, diesel::result::Error> {
+    dlc_storage_common::get_contract_metadata(conn, params)
+}
+
+pub fn get_channels(
+    conn: &mut PgConnection,
+    params: ChannelRequestParams,
+) -> Result<Vec<Channel>, diesel::result::Error> {
+    dlc_storage_common::get_channels(conn, params)
+}
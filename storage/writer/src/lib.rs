@@ -1,7 +1,8 @@
 use diesel::PgConnection;
 use dlc_storage_common::models::{
-    Contract, DeleteContract, DeleteEvent, Event, NewContract, NewEvent, UpdateContract,
-    UpdateEvent,
+    Channel, Contract, ContractMetadata, DeleteChannel, DeleteContract, DeleteContractMetadata,
+    DeleteEvent, Event, NewContract, NewContractMetadata, NewEvent, ReplaceContract,
+    UpdateContract, UpdateEvent, UpsertChannel,
 };
 
 pub fn apply_migrations(conn: &mut PgConnection) {
@@ -22,6 +23,13 @@ pub fn update_contract(
     dlc_storage_common::update_contract(conn, contract)
 }
 
+pub fn replace_contract(
+    conn: &mut PgConnection,
+    contract: ReplaceContract,
+) -> Result<usize, diesel::result::Error> {
+    dlc_storage_common::replace_contract(conn, contract)
+}
+
 pub fn delete_contract(
     conn: &mut PgConnection,
     contract: DeleteContract,
@@ -60,3 +68,31 @@ pub fn delete_event(
 pub fn delete_events(conn: &mut PgConnection, ckey: &str) -> Result<usize, diesel::result::Error> {
     dlc_storage_common::delete_all_events(conn, ckey)
 }
+
+pub fn upsert_contract_metadata(
+    conn: &mut PgConnection,
+    metadata: NewContractMetadata,
+) -> Result<ContractMetadata, diesel::result::Error> {
+    dlc_storage_common::upsert_contract_metadata(conn, metadata)
+}
+
+pub fn delete_contract_metadata(
+    conn: &mut PgConnection,
+    metadata: DeleteContractMetadata,
+) -> Result<usize, diesel::result::Error> {
+    dlc_storage_common::delete_contract_metadata(conn, metadata)
+}
+
+pub fn upsert_channel(
+    conn: &mut PgConnection,
+    channel: UpsertChannel,
+) -> Result<Channel, diesel::result::Error> {
+    dlc_storage_common::upsert_channel(conn, channel)
+}
+
+pub fn delete_channel(
+    conn: &mut PgConnection,
+    channel: DeleteChannel,
+) -> Result<usize, diesel::result::Error> {
+    dlc_storage_common::delete_channel(conn, channel)
+}
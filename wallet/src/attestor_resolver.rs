@@ -0,0 +1,102 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use attestor_client::AttestorClient;
+use bitcoin::XOnlyPublicKey;
+use dlc_link_manager::{AsyncOracle, OracleResolver};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::get_attestors;
+
+/// How long an attestor missing from the list of the blockchain interface is
+/// not looked up again.
+const MISS_TTL: Duration = Duration::from_secs(600);
+
+#[derive(Default)]
+struct ResolvedAttestors {
+    attestors: HashMap<XOnlyPublicKey, Arc<AttestorClient>>,
+    misses: HashMap<XOnlyPublicKey, Instant>,
+}
+
+impl ResolvedAttestors {
+    /// Returns the attestor if it is known, `Some(None)` if it was recently
+    /// looked up without being found and `None` if it has to be looked up.
+    fn get(&self, public_key: &XOnlyPublicKey) -> Option<Option<Arc<AttestorClient>>> {
+        if let Some(attestor) = self.attestors.get(public_key) {
+            return Some(Some(attestor.clone()));
+        }
+        match self.misses.get(public_key) {
+            Some(missed_at) if missed_at.elapsed() < MISS_TTL => Some(None),
+            _ => None,
+        }
+    }
+}
+
+/// Finds the attestors announced in a contract but missing from the Manager's
+/// attestors among the attestors currently listed by the blockchain interface.
+/// Every attestor found is kept, so that contracts keep closing after their
+/// attestors leave the list. Attestors that are not listed are only looked up
+/// again after [`MISS_TTL`].
+pub(crate) struct AttestorResolver {
+    blockchain_interface_url: String,
+    attestation_timeout: Duration,
+    resolved: Mutex<ResolvedAttestors>,
+    // Held while the list is downloaded, so that concurrent lookups wait for
+    // one download instead of each starting their own. Known attestors are
+    // returned without waiting on it.
+    lookup: Mutex<()>,
+}
+
+impl AttestorResolver {
+    pub(crate) fn new(blockchain_interface_url: String, attestation_timeout: Duration) -> Self {
+        AttestorResolver {
+            blockchain_interface_url,
+            attestation_timeout,
+            resolved: Mutex::new(ResolvedAttestors::default()),
+            lookup: Mutex::new(()),
+        }
+    }
+}
+
+impl OracleResolver<Arc<AttestorClient>> for AttestorResolver {
+    async fn resolve_oracle(
+        &self,
+        public_key: &XOnlyPublicKey,
+    ) -> Result<Option<Arc<AttestorClient>>, dlc_manager::error::Error> {
+        if let Some(attestor) = self.resolved.lock().await.get(public_key) {
+            return Ok(attestor);
+        }
+
+        let _lookup = self.lookup.lock().await;
+        // Another lookup may have found the attestor while we were waiting.
+        if let Some(attestor) = self.resolved.lock().await.get(public_key) {
+            return Ok(attestor);
+        }
+
+        info!("Looking up unknown attestor {}", public_key);
+        let mut found = Vec::new();
+        for url in get_attestors(self.blockchain_interface_url.clone()).await? {
+            match AttestorClient::new(&url).await {
+                Ok(client) => {
+                    let attestor =
+                        Arc::new(client.with_attestation_timeout(self.attestation_timeout));
+                    found.push((attestor.get_public_key().await, attestor));
+                }
+                Err(e) => warn!("Error creating attestor client for {}: {}", url, e),
+            }
+        }
+
+        let mut resolved = self.resolved.lock().await;
+        resolved.attestors.extend(found);
+        let attestor = resolved.attestors.get(public_key).cloned();
+        if attestor.is_none() {
+            warn!("Attestor {} is not listed", public_key);
+            resolved.misses.insert(*public_key, Instant::now());
+        }
+        Ok(attestor)
+    }
+}
@@ -0,0 +1,56 @@
+use std::sync::{Arc, Mutex};
+
+use bitcoin::Txid;
+use dlc_link_manager::{ContractObserver, ContractState, ContractTransition};
+use tracing::warn;
+
+/// Queues the contracts to report to the blockchain interface as closed, from
+/// every transition of the Manager rather than from the periodic check report,
+/// so that mutual closes and closes found on chain are reported too.
+#[derive(Clone, Default)]
+pub(crate) struct CloseNotifications {
+    pending: Arc<Mutex<Vec<(String, Txid)>>>,
+}
+
+impl CloseNotifications {
+    /// Returns the queued notifications, emptying the queue.
+    pub(crate) fn take(&self) -> Vec<(String, Txid)> {
+        std::mem::take(
+            &mut *self
+                .pending
+                .lock()
+                .expect("close notifications lock poisoned"),
+        )
+    }
+
+    /// Queues again notifications that could not be sent.
+    pub(crate) fn requeue(&self, notifications: Vec<(String, Txid)>) {
+        self.pending
+            .lock()
+            .expect("close notifications lock poisoned")
+            .extend(notifications);
+    }
+}
+
+impl ContractObserver for CloseNotifications {
+    fn on_transition(&self, transition: &ContractTransition) {
+        // Contracts are reported once their closing transaction is broadcast,
+        // which is when they move to PreClosed, or to Closed directly when the
+        // transaction was already confirmed.
+        let is_closing = match transition.new_state {
+            ContractState::PreClosed => true,
+            ContractState::Closed => transition.previous_state != Some(ContractState::PreClosed),
+            _ => false,
+        };
+        if !is_closing {
+            return;
+        }
+        match (&transition.event_id, transition.txids.last()) {
+            (Some(event_id), Some(txid)) => self.requeue(vec![(event_id.clone(), *txid)]),
+            _ => warn!(
+                "Cannot report the close of contract {:02x?} without its event id and closing transaction",
+                transition.contract_id
+            ),
+        }
+    }
+}
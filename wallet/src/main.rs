@@ -12,7 +12,8 @@ use hyper::service::{make_service_fn, service_fn};
 use hyper::{header, Body, Method, Response, Server, StatusCode};
 
 use bdk::descriptor;
-use secp256k1_zkp::SecretKey;
+use secp256k1_zkp::ecdsa::Signature;
+use secp256k1_zkp::{Secp256k1, SecretKey};
 use serde::{Deserialize, Serialize};
 use tokio::{task, time};
 
@@ -23,13 +24,16 @@ use std::{collections::HashMap, env, str::FromStr, sync::Arc};
 
 use bitcoin::{Address, PublicKey, XOnlyPublicKey};
 
-use dlc_link_manager::{AsyncOracle, AsyncStorage, Manager, ONE_DAY_IN_SECONDS};
+use dlc_link_manager::{
+    derive_identity_key, sign_dlc_message, AsyncOracle, AsyncStorage, Manager, ManagerConfig,
+    Offerer, PeriodicCheckReport, ONE_DAY_IN_SECONDS,
+};
 use dlc_manager::{
     contract::{
         contract_input::{ContractInput, ContractInputInfo, OracleInput},
         Contract,
     },
-    SystemTimeProvider,
+    ContractId, SystemTimeProvider,
 };
 use dlc_messages::{AcceptDlc, Message};
 use dlc_wallet::DlcWallet;
@@ -41,8 +45,12 @@ use dlc_clients::async_storage_provider::AsyncStorageApiProvider;
 use serde_json::json;
 use std::fmt::{self, Write as _};
 
+use attestor_resolver::AttestorResolver;
+use close_notifications::CloseNotifications;
 use utils::get_numerical_contract_info;
 
+mod attestor_resolver;
+mod close_notifications;
 mod utils;
 #[macro_use]
 mod macros;
@@ -64,12 +72,10 @@ type DlcManager<'a> = Manager<
     Arc<AsyncStorageApiProvider>,
     Arc<AttestorClient>,
     Arc<SystemTimeProvider>,
+    Offerer,
+    AttestorResolver,
 >;
 
-// The contracts in dlc-manager expect a node id, but web extensions often don't have this, so hardcode it for now. Should not have any ramifications.
-const STATIC_COUNTERPARTY_NODE_ID: &str =
-    "02fc8e97419286cf05e5d133f41ff6d51f691dda039e9dc007245a421e2c7ec61c";
-
 const REQWEST_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Serialize, Deserialize)]
@@ -153,6 +159,7 @@ async fn get_chain_from_attestors(
 
 async fn generate_attestor_client(
     attestor_urls: Vec<String>,
+    attestation_timeout: Duration,
 ) -> HashMap<XOnlyPublicKey, Arc<AttestorClient>> {
     let mut attestor_clients = HashMap::new();
 
@@ -168,11 +175,20 @@ async fn generate_attestor_client(
                 panic!("Error creating attestor client: {}", e);
             }
         };
-        let attestor = Arc::new(p2p_client);
+        let attestor = Arc::new(p2p_client.with_attestation_timeout(attestation_timeout));
         attestor_clients.insert(attestor.get_public_key().await, attestor.clone());
     }
     attestor_clients
 }
+
+fn hex_str(value: &[u8]) -> String {
+    let mut res = String::with_capacity(64);
+    for v in value {
+        write!(res, "{:02x}", v).unwrap();
+    }
+    res
+}
+
 fn build_success_response(message: String) -> Result<Response<Body>, GenericError> {
     Ok(Response::builder()
         .status(StatusCode::OK)
@@ -209,22 +225,27 @@ async fn process_request(
     manager: Arc<DlcManager<'_>>,
     dlc_store: Arc<AsyncStorageApiProvider>,
     wallet: Arc<DlcWallet>,
+    identity_key: SecretKey,
     active_network: String,
     blockchain_interface_url: String,
+    attestor_threshold: Option<usize>,
+    close_notifications: CloseNotifications,
 ) -> Result<Response<Body>, GenericError> {
     match (req.method(), req.uri().path()) {
         (&Method::GET, "/health") => build_success_response(
             json!({"data": [{"status": "healthy", "message": ""}]}).to_string(),
         ),
-        (&Method::GET, "/info") => get_wallet_info(dlc_store, wallet).await,
+        (&Method::GET, "/info") => {
+            let public_key =
+                secp256k1_zkp::PublicKey::from_secret_key(&Secp256k1::new(), &identity_key);
+            get_wallet_info(dlc_store, wallet, public_key.to_string()).await
+        }
         (&Method::GET, path) if path.starts_with("/get_chain/") => {
             let event_id = path.trim_start_matches("/get_chain/").to_string();
             info!("Getting chain for event id {}", event_id);
             let result = async {
-                let attestors: HashMap<XOnlyPublicKey, Arc<AttestorClient>> = manager
-                    .oracles
-                    .clone()
-                    .ok_or(WalletError("No attestors from Manager".to_string()))?;
+                let attestors: HashMap<XOnlyPublicKey, Arc<AttestorClient>> =
+                    manager.oracles.clone();
 
                 get_chain_from_attestors(attestors, event_id).await
             };
@@ -237,8 +258,9 @@ async fn process_request(
             }
         }
         (&Method::GET, "/periodic_check") => {
-            let result =
-                async { periodic_check(manager, dlc_store, blockchain_interface_url).await };
+            let result = async {
+                periodic_check(manager, blockchain_interface_url, close_notifications).await
+            };
             match result.await {
                 Ok(_) => (),
                 Err(e) => {
@@ -260,12 +282,11 @@ async fn process_request(
                 refund_delay: u32,
                 btc_fee_recipient: String,
                 btc_fee_basis_points: u64,
+                counterparty_public_key: String,
             }
             let result = async {
-                let attestors: HashMap<XOnlyPublicKey, Arc<AttestorClient>> = manager
-                    .oracles
-                    .clone()
-                    .ok_or(WalletError("No attestors from Manager".to_string()))?;
+                let attestors: HashMap<XOnlyPublicKey, Arc<AttestorClient>> =
+                    manager.oracles.clone();
 
                 let whole_body = hyper::body::aggregate(req)
                     .await
@@ -290,6 +311,9 @@ async fn process_request(
                     req.refund_delay,
                     req.btc_fee_recipient,
                     req.btc_fee_basis_points,
+                    req.counterparty_public_key,
+                    attestor_threshold,
+                    identity_key,
                 )
                 .await
             };
@@ -312,10 +336,19 @@ async fn process_request(
                 #[serde(rename_all = "camelCase")]
                 struct AcceptOfferRequest {
                     accept_message: String,
+                    counterparty_public_key: String,
+                    signature: String,
                 }
                 let data: AcceptOfferRequest = serde_json::from_reader(whole_body.reader())?;
                 let accept_dlc: AcceptDlc = serde_json::from_str(&data.accept_message)?;
-                accept_offer(accept_dlc, manager).await
+                accept_offer(
+                    accept_dlc,
+                    data.counterparty_public_key,
+                    data.signature,
+                    manager,
+                    identity_key,
+                )
+                .await
             };
             match result.await {
                 Ok(sign_message) => build_success_response(sign_message),
@@ -325,6 +358,26 @@ async fn process_request(
                 }
             }
         }
+        (&Method::OPTIONS, "/offer/abandon") => build_success_response("".to_string()),
+        (&Method::PUT, "/offer/abandon") => {
+            let result = async {
+                let whole_body = hyper::body::aggregate(req).await?;
+                #[derive(Deserialize)]
+                #[serde(rename_all = "camelCase")]
+                struct AbandonOfferRequest {
+                    temporary_contract_id: String,
+                }
+                let data: AbandonOfferRequest = serde_json::from_reader(whole_body.reader())?;
+                abandon_offer(data.temporary_contract_id, manager).await
+            };
+            match result.await {
+                Ok(_) => build_success_response("Offer abandoned".to_string()),
+                Err(e) => {
+                    warn!("Error abandoning offer - {}", e);
+                    build_error_response(e.to_string())
+                }
+            }
+        }
         _ => {
             // Return 404 not found response.
             Ok(Response::builder()
@@ -412,6 +465,8 @@ async fn main() -> Result<(), GenericError> {
         active_network,
     ));
     let (pubkey, wallet, secret_key) = setup_wallets(xpriv, active_network);
+    // Signs our DLC messages, so that counter parties know they come from us.
+    let identity_key = derive_identity_key(&secret_key)?;
 
     // Set up Attestor Clients
     let attestor_urls: Vec<String> = match retry!(
@@ -425,7 +480,27 @@ async fn main() -> Result<(), GenericError> {
             panic!("Error getting attestors: {}", e);
         }
     };
-    let protocol_wallet_attestors = generate_attestor_client(attestor_urls.clone()).await;
+    let attestation_timeout: u64 = env::var("ATTESTATION_TIMEOUT_SECONDS")
+        .unwrap_or("10".to_string())
+        .parse::<u64>()
+        .unwrap_or(10);
+    let attestor_threshold: Option<usize> = env::var("ATTESTOR_THRESHOLD").ok().map(|threshold| {
+        threshold
+            .parse::<usize>()
+            .expect("ATTESTOR_THRESHOLD must be a number of attestors")
+    });
+    if let Some(threshold) = attestor_threshold {
+        if threshold == 0 || threshold > attestor_urls.len() {
+            panic!(
+                "ATTESTOR_THRESHOLD {} must be between 1 and the number of attestors, {}",
+                threshold,
+                attestor_urls.len()
+            );
+        }
+    }
+    let attestation_timeout = Duration::from_secs(attestation_timeout);
+    let protocol_wallet_attestors =
+        generate_attestor_client(attestor_urls.clone(), attestation_timeout).await;
 
     match retry!(
         blockchain.blockchain.get_height().await,
@@ -450,13 +525,33 @@ async fn main() -> Result<(), GenericError> {
 
     // Set up time provider
     let time_provider = SystemTimeProvider {};
-    let manager = Arc::new(Manager::new(
+    let manager_config = match active_network {
+        bitcoin::Network::Regtest => ManagerConfig {
+            funding_confirmations: 1,
+            closing_confirmations: 1,
+            fee_bump_delay: Some(1),
+            ..Default::default()
+        },
+        _ => ManagerConfig {
+            fee_bump_delay: Some(6),
+            ..Default::default()
+        },
+    };
+    let mut manager = Manager::new_offerer(
         Arc::clone(&wallet),
         Arc::clone(&blockchain),
         dlc_store.clone(),
-        Some(protocol_wallet_attestors),
+        protocol_wallet_attestors,
         Arc::new(time_provider),
-    )?);
+        manager_config,
+    )?
+    .with_oracle_resolver(AttestorResolver::new(
+        blockchain_interface_url.clone(),
+        attestation_timeout,
+    ));
+    let close_notifications = CloseNotifications::default();
+    manager.add_observer(Box::new(close_notifications.clone()));
+    let manager = Arc::new(manager);
 
     let new_service = make_service_fn(move |_| {
         // For each connection, clone the counter to use in our service...
@@ -465,6 +560,7 @@ async fn main() -> Result<(), GenericError> {
         let wallet = wallet.clone();
         let blockchain_interface_url = blockchain_interface_url.clone();
         let active_network = active_network.to_string();
+        let close_notifications = close_notifications.clone();
 
         async move {
             Ok::<_, GenericError>(service_fn(move |req| {
@@ -473,8 +569,11 @@ async fn main() -> Result<(), GenericError> {
                     manager.to_owned(),
                     dlc_store.to_owned(),
                     wallet.to_owned(),
+                    identity_key,
                     active_network.to_owned(),
                     blockchain_interface_url.to_owned(),
+                    attestor_threshold,
+                    close_notifications.to_owned(),
                 )
             }))
         }
@@ -559,7 +658,21 @@ async fn create_new_offer(
     refund_delay: u32,
     btc_fee_recipient: String,
     btc_fee_basis_points: u64,
+    counterparty_public_key: String,
+    attestor_threshold: Option<usize>,
+    identity_key: SecretKey,
 ) -> Result<String, WalletError> {
+    // Without a configured threshold, every attestor must attest the outcome.
+    let threshold = attestor_threshold.unwrap_or(attestors.len());
+    if threshold == 0 || threshold > attestors.len() {
+        return Err(WalletError(format!(
+            "Invalid attestor threshold {} for {} attestors",
+            threshold,
+            attestors.len()
+        )));
+    }
+    let counterparty = secp256k1_zkp::PublicKey::from_str(&counterparty_public_key)
+        .map_err(|e| WalletError(format!("Error parsing counterparty public key: {}", e)))?;
     let active_network = bitcoin::Network::from_str(&active_network)
         .map_err(|e| WalletError(format!("Unknown Network in offer creation: {}", e)))?;
     let (_event_descriptor, descriptor) = get_numerical_contract_info(
@@ -581,7 +694,7 @@ async fn create_new_offer(
         oracles: OracleInput {
             public_keys,
             event_id: event_id.clone(),
-            threshold: attestors.len() as u16,
+            threshold: threshold as u16,
         },
         contract_descriptor: descriptor,
     };
@@ -621,53 +734,74 @@ async fn create_new_offer(
     let offer = man
         .send_offer(
             &contract_input,
-            STATIC_COUNTERPARTY_NODE_ID
-                .parse()
-                .expect("To be able to parse the static counterparty id to a pubkey"),
+            counterparty,
             adjusted_refund_delay,
             btc_fee_basis_points,
             fee_address,
         )
         .await
         .map_err(|e| WalletError(e.to_string()))?;
-    serde_json::to_string(&offer).map_err(|e| WalletError(e.to_string()))
+    to_signed_json(&offer, &Message::Offer(offer.clone()), &identity_key)
+}
+
+/// Serializes a message for the counter party with our signature of it added
+/// as a `signature` field, proving the message comes from us.
+fn to_signed_json<T: Serialize>(
+    message: &T,
+    dlc_message: &Message,
+    identity_key: &SecretKey,
+) -> Result<String, WalletError> {
+    let signature = sign_dlc_message(&Secp256k1::new(), dlc_message, identity_key)
+        .map_err(|e| WalletError(e.to_string()))?;
+    let mut json = serde_json::to_value(message).map_err(|e| WalletError(e.to_string()))?;
+    json.as_object_mut()
+        .ok_or(WalletError("Message is not a JSON object".to_string()))?
+        .insert("signature".to_string(), signature.to_string().into());
+    Ok(json.to_string())
 }
 
 async fn accept_offer(
     accept_dlc: AcceptDlc,
+    counterparty_public_key: String,
+    signature: String,
     manager: Arc<DlcManager<'_>>,
+    identity_key: SecretKey,
 ) -> Result<String, GenericError> {
+    // The accept message must be signed with the key the offer was made to,
+    // so that nobody else can accept it.
+    let counterparty = secp256k1_zkp::PublicKey::from_str(&counterparty_public_key)?;
+    let signature = Signature::from_str(&signature)?;
     let dlc = manager
-        .on_dlc_message(
-            &Message::Accept(accept_dlc),
-            STATIC_COUNTERPARTY_NODE_ID
-                .parse()
-                .expect("To be able to parse the static counterparty id to a pubkey"),
-        )
+        .on_signed_dlc_message(&Message::Accept(accept_dlc), counterparty, &signature)
         .await?;
 
     match dlc {
-        Some(Message::Sign(sign)) => serde_json::to_string(&sign).map_err(|e| e.into()),
+        Some(Message::Sign(sign)) => {
+            to_signed_json(&sign, &Message::Sign(sign.clone()), &identity_key).map_err(|e| e.into())
+        }
         _ => Err("Error: invalid Sign message for accept_offer function".into()),
     }
 }
 
+async fn abandon_offer(
+    temporary_contract_id: String,
+    manager: Arc<DlcManager<'_>>,
+) -> Result<(), GenericError> {
+    let temporary_id: ContractId = hex::decode(&temporary_contract_id)?
+        .try_into()
+        .map_err(|_| WalletError("Contract ids are 32 bytes long".to_string()))?;
+    manager.abandon_offer(&temporary_id).await?;
+    Ok(())
+}
+
 async fn get_wallet_info(
     store: Arc<AsyncStorageApiProvider>,
     wallet: Arc<DlcWallet>,
-    // static_address: String,
+    public_key: String,
 ) -> Result<Response<Body>, GenericError> {
     let mut info_response = json!({});
     let mut contracts_json = json!({});
 
-    fn hex_str(value: &[u8]) -> String {
-        let mut res = String::with_capacity(64);
-        for v in value {
-            write!(res, "{:02x}", v).unwrap();
-        }
-        res
-    }
-
     let mut collected_contracts: Vec<Vec<String>> = vec![vec![]; 9];
 
     let contracts = match store.get_contracts().await {
@@ -704,7 +838,8 @@ async fn get_wallet_info(
     contracts_json["PreClosed"] = collected_contracts[8].clone().into();
 
     info_response["wallet"] = json!({
-        "address": wallet.address
+        "address": wallet.address,
+        "publicKey": public_key
     });
     info_response["contracts"] = contracts_json;
 
@@ -718,65 +853,37 @@ async fn get_wallet_info(
 
 async fn periodic_check(
     manager: Arc<DlcManager<'_>>,
-    store: Arc<AsyncStorageApiProvider>,
     blockchain_interface_url: String,
+    close_notifications: CloseNotifications,
 ) -> Result<String, GenericError> {
     let funded_url = format!("{}/set-status-funded", blockchain_interface_url);
     let closed_url = format!("{}/post-close-dlc", blockchain_interface_url);
 
-    let attestors: HashMap<XOnlyPublicKey, Arc<AttestorClient>> = match manager.oracles.clone() {
-        Some(oracles) => oracles,
-        None => {
-            return Err("No attestors from manager".into());
-        }
-    };
+    let attestors: HashMap<XOnlyPublicKey, Arc<AttestorClient>> = manager.oracles.clone();
 
-    let updated_contracts = match manager.periodic_check().await {
-        Ok(updated_contracts) => updated_contracts,
+    let report = match manager.periodic_check().await {
+        Ok(report) => report,
         Err(e) => {
             info!("Error in periodic_check, will retry: {}", e.to_string());
-            vec![]
+            PeriodicCheckReport::default()
         }
     };
-    let mut newly_confirmed_uuids: Vec<(String, bitcoin::Txid)> = vec![];
-    let mut newly_closed_uuids: Vec<(String, bitcoin::Txid)> = vec![];
-
-    for (id, uuid) in updated_contracts {
-        let contract = match store.get_contract(&id).await {
-            Ok(Some(contract)) => contract,
-            Ok(None) => {
-                error!("Error retrieving contract: {:?}", id);
-                continue;
-            }
-            Err(e) => {
-                error!("Error retrieving contract: {}", e.to_string());
-                continue;
-            }
-        };
-        match contract {
-            Contract::Confirmed(c) => {
-                newly_confirmed_uuids
-                    .push((uuid, c.accepted_contract.dlc_transactions.fund.txid()));
-            }
-            Contract::PreClosed(c) => {
-                newly_closed_uuids.push((uuid, c.signed_cet.txid()));
-            }
-            Contract::Closed(_) | Contract::Signed(_) => {
-                debug!(
-                    "Contract is being set to the Closed or Signed state: {}",
-                    uuid
-                );
-            }
-            Contract::Refunded(_) => {
-                debug!("Contract is being set to the Refunded state: {}", uuid);
-            }
-            _ => error!(
-                "Error retrieving contract in periodic_check: {}, skipping",
-                uuid
-            ),
-        };
+
+    // Unexpected spends, attestor disagreements and check errors are logged by
+    // the Manager as it builds the report.
+    for update in report.closed.iter().chain(report.refunded.iter()) {
+        debug!(
+            "Contract is being set to the Closed or Refunded state: {}",
+            update.event_id
+        );
     }
 
+    let newly_confirmed_uuids: Vec<(String, bitcoin::Txid)> = report
+        .confirmed
+        .into_iter()
+        .map(|update| (update.event_id, update.txid))
+        .collect();
+
     for (uuid, txid) in newly_confirmed_uuids {
         debug!(
             "Contract is funded, setting funded to true: {}, btc tx id: {}",
@@ -799,23 +906,27 @@ async fn periodic_check(
         };
     }
 
-    for (uuid, txid) in newly_closed_uuids {
+    // Closes are queued by the Manager's observer, whether they were found by
+    // this check or happened in between, and queued again if they could not
+    // be reported.
+    let newly_closed_uuids = close_notifications.take();
+    for (i, (uuid, txid)) in newly_closed_uuids.iter().enumerate() {
         debug!("Contract is closed, firing post-close url: {}", uuid);
 
-        match get_chain_from_attestors(attestors.clone(), uuid.clone()).await {
-            Ok(chain) => {
-                reqwest::Client::new()
-                    .post(&closed_url)
-                    .timeout(REQWEST_TIMEOUT)
-                    .json(&json!({"uuid": uuid, "btcTxId": txid.to_string(), "chain": chain}))
-                    .send()
-                    .await?
-            }
-            Err(e) => {
-                error!("Failed to get chain from attestors: {}", e);
-                return Err(e);
-            }
+        let result = async {
+            let chain = get_chain_from_attestors(attestors.clone(), uuid.clone()).await?;
+            reqwest::Client::new()
+                .post(&closed_url)
+                .timeout(REQWEST_TIMEOUT)
+                .json(&json!({"uuid": uuid, "btcTxId": txid.to_string(), "chain": chain}))
+                .send()
+                .await?;
+            Ok::<_, GenericError>(())
         };
+        if let Err(e) = result.await {
+            close_notifications.requeue(newly_closed_uuids[i..].to_vec());
+            return Err(format!("Failed to report the close of contract {}: {}", uuid, e).into());
+        }
     }
     Ok("Success running periodic check".to_string())
 }
@@ -26,11 +26,15 @@ router.get('/info', express.json(), async (req, res) => {
 
 router.post('/offer', express.json(), async (req, res) => {
     console.log('[WBI] POST /offer');
-    const { uuid } = req.body;
+    const { uuid, counterpartyPublicKey } = req.body;
     if (!uuid) {
         res.status(400).send('Missing UUID');
         return;
     }
+    if (!counterpartyPublicKey) {
+        res.status(400).send('Missing counterparty public key');
+        return;
+    }
 
     let valueLocked: BigNumber;
     let refundDelay: BigNumber;
@@ -45,6 +49,7 @@ router.post('/offer', express.json(), async (req, res) => {
         refundDelay: number;
         btcFeeRecipient: string;
         btcFeeBasisPoints: number;
+        counterpartyPublicKey: string;
     };
 
     if (TESTMODE) {
@@ -76,6 +81,7 @@ router.post('/offer', express.json(), async (req, res) => {
         refundDelay: refundDelay.toNumber(),
         btcFeeRecipient,
         btcFeeBasisPoints: btcFeeBasisPoints.toNumber(),
+        counterpartyPublicKey,
     };
 
     console.log('[WBI] Offer Request:', offerRequest);
@@ -11,22 +11,25 @@ use bitcoin::XOnlyPublicKey;
 use bitcoin::{Network, PrivateKey};
 use dlc_messages::{Message, OfferDlc, SignDlc};
 use log::{error, info, warn};
-use secp256k1_zkp::UpstreamError;
 use wasm_bindgen::prelude::*;
 
 use lightning::util::ser::Readable;
 
+use secp256k1_zkp::ecdsa::Signature;
 use secp256k1_zkp::hashes::*;
-use secp256k1_zkp::Secp256k1;
+use secp256k1_zkp::{PublicKey, Secp256k1, SecretKey};
 
 use core::panic;
 use std::collections::HashMap;
 use std::fmt;
-use std::{io::Cursor, str::FromStr, sync::Arc};
+use std::{io::Cursor, str::FromStr, sync::Arc, time::Duration};
 
 use dlc_manager::{contract::Contract, ContractId, SystemTimeProvider};
 
-use dlc_link_manager::{AsyncOracle, AsyncStorage, Manager};
+use dlc_link_manager::{
+    derive_identity_key, sign_dlc_message, AcceptPolicy, Acceptor, AsyncOracle, AsyncStorage,
+    Manager, ManagerConfig, PayoutCurveShape,
+};
 
 use std::fmt::Write as _;
 
@@ -37,11 +40,22 @@ use esplora_async_blockchain_provider_js_wallet::EsploraAsyncBlockchainProviderJ
 use js_interface_wallet::JSInterfaceWallet;
 
 use attestor_client::AttestorClient;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 #[macro_use]
 mod macros;
 
+// Limits on the offers of the router wallet, which offers numerical contracts
+// with a refund delay of at most ten days, a fee rate of at most 400 sats/vbyte
+// and a small protocol fee.
+const MAX_REFUND_DELAY: u32 = 10 * 24 * 60 * 60;
+const MAX_FEE_RATE: u64 = 400;
+const MAX_PROTOCOL_FEE_BASIS_POINTS: u64 = 100;
+/// How long to wait for each attestor to answer an attestation request, as
+/// the router wallet does by default.
+const ATTESTATION_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[derive(Debug)]
 struct WalletError(String);
 impl fmt::Display for WalletError {
@@ -57,12 +71,9 @@ type DlcManager = Manager<
     Box<AsyncStorageApiProvider>,
     Arc<AttestorClient>,
     Arc<SystemTimeProvider>,
+    Acceptor,
 >;
 
-// The contracts in dlc-manager expect a node id, but web extensions often don't have this, so hardcode it for now. Should not have any ramifications.
-const STATIC_COUNTERPARTY_NODE_ID: &str =
-    "02fc8e97419286cf05e5d133f41ff6d51f691dda039e9dc007245a421e2c7ec61c";
-
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct ErrorResponse {
@@ -90,6 +101,11 @@ pub struct JsDLCInterface {
     manager: DlcManager,
     wallet: Arc<JSInterfaceWallet>,
     blockchain: Arc<EsploraAsyncBlockchainProviderJsWallet>,
+    // Signs our messages so the router wallet knows they come from us,
+    // derived from the wallet key so that the latter is not used to sign them.
+    identity_key: SecretKey,
+    // The router wallet we make contracts with, from its /info endpoint.
+    router_public_key: PublicKey,
 }
 
 // #[wasm_bindgen]
@@ -118,6 +134,21 @@ where
     WalletError(e.to_string())
 }
 
+// Splits a message of the router wallet from the signature it adds to it
+fn parse_signed_message<T: DeserializeOwned>(json: &str) -> Result<(T, Signature), WalletError> {
+    let mut value: serde_json::Value = serde_json::from_str(json).map_err(to_wallet_error)?;
+    let signature = value
+        .as_object_mut()
+        .and_then(|object| object.remove("signature"))
+        .and_then(|signature| signature.as_str().map(Signature::from_str))
+        .ok_or(WalletError(
+            "Message is not signed by the router wallet".to_string(),
+        ))?
+        .map_err(to_wallet_error)?;
+    let message = serde_json::from_value(value).map_err(to_wallet_error)?;
+    Ok((message, signature))
+}
+
 pub async fn generate_attestor_client(
     attestor_urls: Vec<String>,
 ) -> HashMap<XOnlyPublicKey, Arc<AttestorClient>> {
@@ -135,7 +166,7 @@ pub async fn generate_attestor_client(
                 panic!("Error creating attestor client: {}", e);
             }
         };
-        let attestor = Arc::new(p2p_client);
+        let attestor = Arc::new(p2p_client.with_attestation_timeout(ATTESTATION_TIMEOUT));
         attestor_clients.insert(attestor.get_public_key().await, attestor.clone());
     }
     attestor_clients
@@ -149,6 +180,7 @@ impl JsDLCInterface {
         network: String,
         electrs_url: String,
         storage_api_url: String,
+        router_public_key: String,
     ) -> Result<JsDLCInterface, JsError> {
         console_error_panic_hook::set_once();
 
@@ -167,8 +199,11 @@ impl JsDLCInterface {
             ));
 
         // Generate keypair from secret key
-        let seckey = secp256k1_zkp::SecretKey::from_str(&privkey)
+        let seckey = SecretKey::from_str(&privkey)
             .map_err(|e| JsError::new(&format!("Error parsing private key: {}", e)))?;
+        let router_public_key = PublicKey::from_str(&router_public_key)
+            .map_err(|e| JsError::new(&format!("Error parsing router public key: {}", e)))?;
+        let identity_key = derive_identity_key(&seckey)?;
 
         let secp = Secp256k1::new();
 
@@ -210,13 +245,36 @@ impl JsDLCInterface {
         // Set up time provider
         let time_provider = SystemTimeProvider {};
 
+        // Only offers using our attestors are accepted.
+        let accept_policy = AcceptPolicy {
+            allowed_oracle_pubkeys: Some(protocol_wallet_attestors.keys().cloned().collect()),
+            max_fee_rate: Some(MAX_FEE_RATE),
+            payout_curve_shape: Some(PayoutCurveShape::Numerical),
+            max_protocol_fee_basis_points: Some(MAX_PROTOCOL_FEE_BASIS_POINTS),
+            ..Default::default()
+        };
+        let base_config = ManagerConfig {
+            max_refund_delay: MAX_REFUND_DELAY,
+            accept_policy,
+            ..Default::default()
+        };
+        let manager_config = match active_network {
+            Network::Regtest => ManagerConfig {
+                funding_confirmations: 1,
+                closing_confirmations: 1,
+                ..base_config
+            },
+            _ => base_config,
+        };
+
         // Create the DLC Manager
-        let manager = Manager::new(
+        let manager = Manager::new_acceptor(
             Arc::clone(&wallet),
             Arc::clone(&blockchain),
             Box::new(dlc_store),
-            Some(protocol_wallet_attestors),
+            protocol_wallet_attestors,
             Arc::new(time_provider),
+            manager_config,
         )?;
 
         Ok(JsDLCInterface {
@@ -224,6 +282,8 @@ impl JsDLCInterface {
             manager,
             wallet,
             blockchain,
+            identity_key,
+            router_public_key,
         })
     }
 
@@ -231,6 +291,11 @@ impl JsDLCInterface {
         Ok(serde_wasm_bindgen::to_value(&self.options)?)
     }
 
+    // The key identifying us to the router wallet, to send along with offer requests
+    pub fn get_public_key(&self) -> String {
+        PublicKey::from_secret_key(&Secp256k1::new(), &self.identity_key).to_string()
+    }
+
     pub async fn get_wallet_balance(&self) -> Result<u64, JsError> {
         self.blockchain
             .refresh_chain_data(self.options.address.clone())
@@ -295,15 +360,16 @@ impl JsDLCInterface {
         //could consider doing a refresh_chain_data here to have the newest utxos
 
         let accept_msg_result = async {
-            let dlc_offer_message: OfferDlc =
-                serde_json::from_str(&offer_json).map_err(to_wallet_error)?;
+            let (dlc_offer_message, signature): (OfferDlc, Signature) =
+                parse_signed_message(&offer_json)?;
             let temporary_contract_id = dlc_offer_message.temporary_contract_id;
 
-            let counterparty = STATIC_COUNTERPARTY_NODE_ID
-                .parse()
-                .map_err(|e: UpstreamError| WalletError(e.to_string()))?;
             self.manager
-                .on_dlc_message(&Message::Offer(dlc_offer_message.clone()), counterparty)
+                .on_signed_dlc_message(
+                    &Message::Offer(dlc_offer_message),
+                    self.router_public_key,
+                    &signature,
+                )
                 .await
                 .map_err(to_wallet_error)?;
             let (_contract_id, _public_key, accept_msg) = self
@@ -322,19 +388,30 @@ impl JsDLCInterface {
         }
     }
 
+    // Signs the accept message returned by accept_offer, the router wallet only
+    // takes accept messages signed by the key the offer was made to
+    pub fn sign_accept_message(&self, accept_json: String) -> Result<String, JsError> {
+        let accept_msg = serde_json::from_str(&accept_json)?;
+        let signature = sign_dlc_message(
+            &Secp256k1::new(),
+            &Message::Accept(accept_msg),
+            &self.identity_key,
+        )?;
+        Ok(signature.to_string())
+    }
+
     pub async fn countersign_and_broadcast(
         &self,
         dlc_sign_message: String,
     ) -> Result<String, JsError> {
         let dlc_sign_result = async {
-            let dlc_sign_message: SignDlc =
-                serde_json::from_str(&dlc_sign_message).map_err(to_wallet_error)?;
+            let (dlc_sign_message, signature): (SignDlc, Signature) =
+                parse_signed_message(&dlc_sign_message)?;
             self.manager
-                .on_dlc_message(
+                .on_signed_dlc_message(
                     &Message::Sign(dlc_sign_message.clone()),
-                    STATIC_COUNTERPARTY_NODE_ID
-                        .parse()
-                        .map_err(to_wallet_error)?,
+                    self.router_public_key,
+                    &signature,
                 )
                 .await
                 .map_err(to_wallet_error)?;
@@ -369,30 +446,17 @@ impl JsDLCInterface {
         let reject_result = async {
             let contract_id =
                 ContractId::read(&mut Cursor::new(&contract_id)).map_err(to_wallet_error)?;
-            let contract = self
-                .manager
-                .get_store()
-                .get_contract(&contract_id)
+            self.manager
+                .reject_offer(&contract_id)
                 .await
                 .map_err(to_wallet_error)?;
-
-            if let Some(Contract::Offered(c)) = contract {
-                self.manager
-                    .get_store()
-                    .update_contract(&Contract::Rejected(c))
-                    .await
-                    .map_err(to_wallet_error)?;
-            }
             Ok(()) as Result<(), WalletError>
         };
         match reject_result.await {
             Ok(_) => Ok(()),
             Err(e) => {
-                log_to_console!("Error signing and broadcasting: {}", e);
-                Err(JsError::new(&format!(
-                    "Error signing and broadcasting: {}",
-                    e
-                )))
+                log_to_console!("Error rejecting offer: {}", e);
+                Err(JsError::new(&format!("Error rejecting offer: {}", e)))
             }
         }
     }